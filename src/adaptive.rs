@@ -0,0 +1,36 @@
+use crate::film::Film;
+
+/// Spends samples only on pixels whose estimated error is still too high.
+pub struct AdaptiveSampling {
+    /// Every pixel gets at least this many samples before its variance
+    /// estimate is trusted.
+    pub min_samples: u32,
+    /// Standard error of the pixel luminance, relative to the luminance,
+    /// below which a pixel counts as converged.
+    pub threshold: f32,
+}
+
+impl AdaptiveSampling {
+    /// A pixel keeps sampling while it, or any of its direct neighbours, is
+    /// above the threshold. The neighbours guard against pixels whose
+    /// variance estimate happens to be too low.
+    pub fn active_pixels(&self, film: &Film) -> Vec<bool> {
+        let (width, height) = (film.width, film.height);
+        let above_threshold = (0..width * height)
+            .map(|pixel| {
+                film.sample_counts[pixel] < self.min_samples
+                    || film.relative_error(pixel) > self.threshold
+            })
+            .collect::<Vec<bool>>();
+
+        (0..width * height)
+            .map(|pixel| {
+                let (x, y) = (pixel % width, pixel / width);
+                (y.saturating_sub(1)..(y + 2).min(height)).any(|ny| {
+                    (x.saturating_sub(1)..(x + 2).min(width))
+                        .any(|nx| above_threshold[ny * width + nx])
+                })
+            })
+            .collect()
+    }
+}
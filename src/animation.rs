@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::fs;
+use std::ops::RangeInclusive;
+
+use nalgebra::UnitQuaternion;
+
+use crate::camera::Camera;
+use crate::geometry::transform::{Keyframe, Keyframes};
+use crate::vector::Vec3;
+
+/// Keyframes per shutter interval when motion is turned into `Keyframes`.
+const MOTION_SAMPLES: usize = 4;
+
+/// How a channel moves from a key to the next one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    /// Holds the value until the next key.
+    Step,
+    Linear,
+    /// Eases in and out of both keys.
+    Smooth,
+    /// Catmull-Rom spline through the neighbouring keys.
+    Spline,
+}
+
+impl Interpolation {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "step" => Some(Interpolation::Step),
+            "linear" => Some(Interpolation::Linear),
+            "smooth" => Some(Interpolation::Smooth),
+            "spline" => Some(Interpolation::Spline),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Key {
+    pub frame: f32,
+    pub value: Vec<f32>,
+    /// Used between this key and the next.
+    pub interpolation: Interpolation,
+}
+
+/// The keys of one animated value, a number or a vector.
+#[derive(Clone, Debug, Default)]
+pub struct Channel {
+    keys: Vec<Key>,
+}
+
+impl Channel {
+    /// Replaces a key on the same frame.
+    pub fn insert(&mut self, key: Key) -> Result<(), String> {
+        if let Some(first) = self.keys.first() {
+            if first.value.len() != key.value.len() {
+                return Err(format!(
+                    "expected {} values, got {}",
+                    first.value.len(),
+                    key.value.len()
+                ));
+            }
+        }
+        match self.keys.iter().position(|k| k.frame >= key.frame) {
+            Some(i) if self.keys[i].frame == key.frame => self.keys[i] = key,
+            Some(i) => self.keys.insert(i, key),
+            None => self.keys.push(key),
+        }
+        Ok(())
+    }
+
+    /// Holds the first and last key outside of the keyed frames.
+    pub fn at(&self, frame: f32) -> Vec<f32> {
+        let next = self.keys.iter().position(|key| key.frame > frame);
+        let i = match next {
+            Some(0) => return self.keys[0].value.clone(),
+            None => return self.keys[self.keys.len() - 1].value.clone(),
+            Some(i) => i,
+        };
+        let (a, b) = (&self.keys[i - 1], &self.keys[i]);
+        let s = (frame - a.frame) / (b.frame - a.frame);
+        let lerp = |s: f32| a.value.iter().zip(&b.value).map(|(a, b)| a + (b - a) * s).collect();
+        match a.interpolation {
+            Interpolation::Step => a.value.clone(),
+            Interpolation::Linear => lerp(s),
+            Interpolation::Smooth => lerp(s * s * (3.0 - 2.0 * s)),
+            Interpolation::Spline => {
+                let before = &self.keys[i.saturating_sub(2)].value;
+                let after = &self.keys[(i + 1).min(self.keys.len() - 1)].value;
+                (0..a.value.len())
+                    .map(|j| catmull_rom(before[j], a.value[j], b.value[j], after[j], s))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Named channels over a range of frames, read by the scene while it is
+/// built for a frame. Channels that are not keyed fall back to the value
+/// the scene passes in.
+///
+/// Scripts have one key per line, `frame channel value... [interpolation]`,
+/// with the interpolation one of `step`, `linear` (the default), `smooth`
+/// or `spline`. `frames first last` sets the range, `shutter fraction`
+/// how much of a frame the shutter stays open and `exposure stops` a fixed
+/// exposure for all frames, `#` starts a comment:
+///
+/// ```text
+/// frames 0 47
+/// shutter 0.5
+/// exposure 1.5
+/// 0  camera.origin    0 2 10  smooth
+/// 47 camera.origin    4 3 8
+/// 0  light.intensity  5       step
+/// 24 light.intensity  20
+/// ```
+#[derive(Clone, Debug)]
+pub struct Animation {
+    pub channels: HashMap<String, Channel>,
+    pub frames: RangeInclusive<i32>,
+    /// Fraction of a frame, zero turns off motion blur.
+    pub shutter: f32,
+    /// Exposure value in stops for every frame, without one it is locked
+    /// after the first frame.
+    pub exposure: Option<f32>,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Animation {
+            channels: HashMap::new(),
+            frames: 0..=0,
+            shutter: 0.0,
+            exposure: None,
+        }
+    }
+}
+
+impl Animation {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let script = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&script).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(script: &str) -> Result<Self, String> {
+        let mut animation = Animation::default();
+        for (number, line) in script.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            animation
+                .parse_line(line)
+                .map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+        Ok(animation)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let number = |word: &str| {
+            word.parse::<f32>()
+                .map_err(|_| format!("`{}` is not a number", word))
+        };
+        let whole_number = |word: &str| {
+            word.parse::<i32>()
+                .map_err(|_| format!("`{}` is not a whole number", word))
+        };
+        match words[0] {
+            "frames" if words.len() == 3 => {
+                self.frames = whole_number(words[1])?..=whole_number(words[2])?;
+                Ok(())
+            }
+            "shutter" if words.len() == 2 => {
+                self.shutter = number(words[1])?;
+                Ok(())
+            }
+            "exposure" if words.len() == 2 => {
+                self.exposure = Some(number(words[1])?);
+                Ok(())
+            }
+            _ if words.len() >= 3 => {
+                let frame = number(words[0])?;
+                let (values, interpolation) = match Interpolation::parse(words[words.len() - 1]) {
+                    Some(interpolation) => (&words[2..words.len() - 1], interpolation),
+                    None => (&words[2..], Interpolation::Linear),
+                };
+                let value = values
+                    .iter()
+                    .map(|word| number(word))
+                    .collect::<Result<Vec<f32>, String>>()?;
+                self.set(words[1], frame, value, interpolation)
+            }
+            _ => Err(format!("can not read `{}`", line)),
+        }
+    }
+
+    pub fn set(
+        &mut self,
+        channel: &str,
+        frame: f32,
+        value: Vec<f32>,
+        interpolation: Interpolation,
+    ) -> Result<(), String> {
+        if value.is_empty() {
+            return Err(format!("{} has no value", channel));
+        }
+        self.channels
+            .entry(channel.to_string())
+            .or_default()
+            .insert(Key { frame, value, interpolation })
+            .map_err(|e| format!("{}: {}", channel, e))
+    }
+
+    pub fn float(&self, channel: &str, frame: f32, default: f32) -> f32 {
+        self.channels
+            .get(channel)
+            .map_or(default, |channel| channel.at(frame)[0])
+    }
+
+    /// A single value is used for all three components.
+    pub fn vec3(&self, channel: &str, frame: f32, default: Vec3) -> Vec3 {
+        match self.channels.get(channel).map(|channel| channel.at(frame)) {
+            Some(v) if v.len() >= 3 => Vec3::new(v[0], v[1], v[2]),
+            Some(v) => Vec3::repeat(v[0]),
+            None => default,
+        }
+    }
+
+    /// The transform of `name` while the shutter of `frame` is open, for
+    /// `Transform::animated`. It reads `name.translation`, `name.rotation`
+    /// in degrees and `name.scale`, times are in frames.
+    pub fn keyframes(&self, name: &str, frame: f32) -> Keyframes {
+        Keyframes::new(
+            self.shutter_times(frame)
+                .map(|time| {
+                    Keyframe::new(
+                        time,
+                        self.vec3(&format!("{}.translation", name), time, Vec3::zeros()),
+                        self.vec3(&format!("{}.rotation", name), time, Vec3::zeros()),
+                        self.vec3(&format!("{}.scale", name), time, Vec3::repeat(1.0)),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Sets `camera.origin`, `camera.lookat`, `camera.vfov`,
+    /// `camera.aperture` and `camera.focus_dist` where they are keyed, and
+    /// the shutter and the motion of the camera while it is open.
+    pub fn apply_to_camera(&self, camera: &mut Camera, frame: f32) {
+        let (scene_origin, scene_lookat) = (camera.origin, camera.lookat);
+        let pose = |time: f32| {
+            let origin = self.vec3("camera.origin", time, scene_origin);
+            let lookat = self.vec3("camera.lookat", time, scene_lookat);
+            (origin, lookat)
+        };
+        let (origin, lookat) = pose(frame);
+        camera.origin = origin;
+        camera.lookat = lookat;
+        camera.vfov = self.float("camera.vfov", frame, camera.vfov);
+        camera.focus_dist = self.float("camera.focus_dist", frame, camera.focus_dist);
+        camera.set_aperture(self.float("camera.aperture", frame, camera.aperture()));
+        camera.update();
+
+        camera.set_shutter(frame, frame + self.shutter);
+        camera.motion = if self.shutter > 0.0 {
+            let direction = lookat - origin;
+            let keys = self
+                .shutter_times(frame)
+                .map(|time| {
+                    let (moved_origin, moved_lookat) = pose(time);
+                    let turn = UnitQuaternion::rotation_between(&direction, &(moved_lookat - moved_origin))
+                        .unwrap_or_else(UnitQuaternion::identity);
+                    Keyframe {
+                        time,
+                        translation: moved_origin - origin,
+                        rotation: turn,
+                        scale: Vec3::repeat(1.0),
+                    }
+                })
+                .collect();
+            Some(Keyframes::new(keys))
+        } else {
+            None
+        };
+    }
+
+    fn shutter_times(&self, frame: f32) -> impl Iterator<Item = f32> {
+        let shutter = self.shutter;
+        let steps = if shutter > 0.0 { MOTION_SAMPLES } else { 1 };
+        (0..steps).map(move |i| frame + shutter * i as f32 / (MOTION_SAMPLES - 1) as f32)
+    }
+}
+
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, s: f32) -> f32 {
+    let s2 = s * s;
+    let s3 = s2 * s;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * s
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * s2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * s3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(keys: &[(f32, f32, Interpolation)]) -> Channel {
+        let mut channel = Channel::default();
+        for (frame, value, interpolation) in keys {
+            channel
+                .insert(Key { frame: *frame, value: vec![*value], interpolation: *interpolation })
+                .unwrap();
+        }
+        channel
+    }
+
+    fn assert_at(channel: &Channel, frame: f32, expected: f32) {
+        let value = channel.at(frame)[0];
+        assert!((value - expected).abs() < 1.0e-5, "{} at frame {} instead of {}", value, frame, expected);
+    }
+
+    #[test]
+    fn interpolates_between_keys() {
+        use Interpolation::*;
+        let step = channel(&[(0.0, 1.0, Step), (10.0, 3.0, Linear)]);
+        assert_at(&step, 9.9, 1.0);
+        assert_at(&step, 10.0, 3.0);
+        let linear = channel(&[(0.0, 1.0, Linear), (10.0, 3.0, Linear)]);
+        assert_at(&linear, 2.5, 1.5);
+        let smooth = channel(&[(0.0, 1.0, Smooth), (10.0, 3.0, Linear)]);
+        assert_at(&smooth, 2.5, 1.0 + 2.0 * 0.15625);
+        assert_at(&smooth, 5.0, 2.0);
+        // Through keys on a line the spline is that line.
+        let spline = channel(&[(0.0, 0.0, Spline), (10.0, 1.0, Spline), (20.0, 2.0, Spline), (30.0, 3.0, Linear)]);
+        assert_at(&spline, 12.5, 1.25);
+        assert_at(&spline, 20.0, 2.0);
+    }
+
+    #[test]
+    fn holds_the_ends() {
+        let linear = channel(&[(5.0, 1.0, Interpolation::Linear), (10.0, 3.0, Interpolation::Linear)]);
+        assert_at(&linear, -100.0, 1.0);
+        assert_at(&linear, 5.0, 1.0);
+        assert_at(&linear, 10.0, 3.0);
+        assert_at(&linear, 100.0, 3.0);
+    }
+
+    #[test]
+    fn keys_on_the_same_frame_are_replaced() {
+        let linear = channel(&[(0.0, 1.0, Interpolation::Linear), (10.0, 3.0, Interpolation::Linear), (0.0, 2.0, Interpolation::Linear)]);
+        assert_at(&linear, 0.0, 2.0);
+        assert_at(&linear, 5.0, 2.5);
+    }
+
+    #[test]
+    fn parses_scripts() {
+        let animation = Animation::parse(
+            "frames 2 47  # comment\n\
+             shutter 0.5\n\
+             exposure 1.5\n\
+             \n\
+             0  camera.origin  0 2 10  smooth\n\
+             47 camera.origin  4 3 8\n\
+             0  light.intensity  5  step\n",
+        )
+        .unwrap();
+        assert_eq!(animation.frames, 2..=47);
+        assert_eq!(animation.shutter, 0.5);
+        assert_eq!(animation.exposure, Some(1.5));
+        assert_eq!(animation.vec3("camera.origin", 0.0, Vec3::zeros()), Vec3::new(0.0, 2.0, 10.0));
+        assert_eq!(animation.float("light.intensity", 30.0, 0.0), 5.0);
+        assert_eq!(animation.float("light.color", 30.0, 7.0), 7.0);
+    }
+
+    #[test]
+    fn rejects_bad_scripts() {
+        for script in [
+            "frames 0 47.5",
+            "frames 0",
+            "shutter half",
+            "0 camera.origin 1 x 3",
+            "0 camera.origin 1 2 3\n10 camera.origin 1 2",
+            "0 camera.origin",
+            "camera",
+        ]
+        .iter()
+        {
+            assert!(Animation::parse(script).is_err(), "`{}` parsed", script);
+        }
+        let error = Animation::parse("frames 0 10\nframes 0 47.5").unwrap_err();
+        assert!(error.starts_with("line 2:"), "{}", error);
+    }
+}
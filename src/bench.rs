@@ -0,0 +1,119 @@
+use std::time::Instant;
+
+use crate::geometry::{
+    aarect::{AARect, AARectType},
+    bvh::{BVHNode, BvhOptions, LinearBvh, Qbvh},
+    sphere::Sphere,
+    Geometry,
+};
+use crate::material::{color::Reflectance, lambertian::Lambertian};
+use crate::ray::Ray;
+use crate::sampler::{IndependentSampler, Sampler};
+use crate::vector::{random_unit_vec, Vec2, Vec3};
+
+/// A named BVH layout and how to build it from the scene.
+type Layout<'a> = (&'static str, Box<dyn Fn() -> Box<dyn Geometry> + 'a>);
+
+/// Times closest hit queries against every BVH layout on a uniform and an
+/// uneven scene. Run with `cargo run --release -- --bench-bvh`.
+pub fn bvh() {
+    let options = BvhOptions::default();
+    let rays = 1_000_000;
+    for (name, objects) in &[("uniform", uniform_scene()), ("uneven", uneven_scene())] {
+        println!("{}: {} objects, {} rays", name, objects.len(), rays);
+        let layouts: Vec<Layout> = vec![
+            ("BVHNode", Box::new(|| BVHNode::build(objects.clone(), &options))),
+            ("LinearBvh", Box::new(|| Box::new(LinearBvh::build(objects.clone(), &options)))),
+            ("Qbvh", Box::new(|| Box::new(Qbvh::build(objects.clone(), &options)))),
+        ];
+        let mut reference = None;
+        for (layout, build) in layouts {
+            let start = Instant::now();
+            let bvh = build();
+            let build_time = start.elapsed();
+
+            let start = Instant::now();
+            let (hits, t_sum) = trace(&*bvh, rays);
+            let trace_time = start.elapsed().as_secs_f32();
+            let speed = rays as f32 / trace_time / 1e6;
+            let speedup = reference.map_or(1.0, |reference| speed / reference);
+            reference.get_or_insert(speed);
+            println!(
+                "  {:<10} build {:>8.2?}  {:>6.2} Mrays/s  {:>5.2}x  {} hits, t sum {:.1}",
+                layout, build_time, speed, speedup, hits, t_sum
+            );
+        }
+    }
+}
+
+/// Rays from random points in a 20 unit cube in random directions, the
+/// same for every layout.
+fn trace(bvh: &dyn Geometry, rays: usize) -> (usize, f64) {
+    let mut sampler = IndependentSampler::new(0);
+    let mut hits = 0;
+    let mut t_sum = 0.0;
+    for i in 0..rays {
+        sampler.start_sample(0, 0, i as u32);
+        let origin = Vec3::new(sampler.next_f32(), sampler.next_f32(), sampler.next_f32());
+        let origin = (origin - Vec3::repeat(0.5)) * 20.0;
+        let ray = Ray::new(origin, random_unit_vec(&mut sampler), 550.0, 0.0);
+        if let Some(hit) = bvh.hit(&ray, 0.001, f32::MAX) {
+            hits += 1;
+            t_sum += hit.t as f64;
+        }
+    }
+    (hits, t_sum)
+}
+
+fn sphere(center: Vec3, radius: f32) -> Box<dyn Geometry> {
+    Box::new(Sphere {
+        center,
+        radius,
+        material: Box::new(Lambertian {
+            reflectance: Reflectance::Uniform(0.5),
+        }),
+    })
+}
+
+/// Equally sized spheres spread evenly through the cube.
+fn uniform_scene() -> Vec<Box<dyn Geometry>> {
+    let mut sampler = IndependentSampler::new(1);
+    sampler.start_sample(0, 0, 0);
+    (0..20_000)
+        .map(|_| {
+            let center = Vec3::new(sampler.next_f32(), sampler.next_f32(), sampler.next_f32());
+            sphere((center - Vec3::repeat(0.5)) * 20.0, 0.05)
+        })
+        .collect()
+}
+
+/// A huge ground plane, tiny lights and clusters of small spheres, where a
+/// median split does badly.
+fn uneven_scene() -> Vec<Box<dyn Geometry>> {
+    let mut sampler = IndependentSampler::new(2);
+    sampler.start_sample(0, 0, 0);
+    let mut objects = vec![AARect {
+        xy0: Vec2::new(-1000.0, -1000.0),
+        xy1: Vec2::new(1000.0, 1000.0),
+        k: -5.0,
+        material: Box::new(Lambertian {
+            reflectance: Reflectance::Uniform(0.5),
+        }),
+        rect_type: AARectType::XZ,
+    }
+    .boxed()];
+    for _ in 0..16 {
+        let light = Vec3::new(sampler.next_f32(), 1.0, sampler.next_f32());
+        objects.push(sphere((light - Vec3::repeat(0.5)) * 20.0, 0.01));
+    }
+    for _ in 0..20 {
+        let cluster = (Vec3::new(sampler.next_f32(), sampler.next_f32(), sampler.next_f32())
+            - Vec3::repeat(0.5))
+            * 16.0;
+        for _ in 0..1000 {
+            let offset = Vec3::new(sampler.next_f32(), sampler.next_f32(), sampler.next_f32());
+            objects.push(sphere(cluster + offset, 0.02));
+        }
+    }
+    objects
+}
@@ -1,14 +1,36 @@
+use nalgebra::{Rotation3, Unit};
+use std::f32::consts::PI;
+
 use crate::mc::WavelengthSampler;
 use crate::ray::Ray;
-use crate::vector::{deg_to_rad, random_unit_in_disk, Vec2, Vec3};
+use crate::geometry::transform::{translation, Keyframes};
+use crate::sampler::{Sampler, LENS_DIMENSION, TIME_DIMENSION, WAVELENGTH_DIMENSION};
+use crate::vector::{deg_to_rad, random_unit_in_disk, Mat4, Vec2, Vec3};
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ApertureShape {
     Circle,
     Hexagon,
 }
 
+impl ApertureShape {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "circle" => Some(ApertureShape::Circle),
+            "hexagon" => Some(ApertureShape::Hexagon),
+            _ => None,
+        }
+    }
+}
+
 pub struct Camera {
     pub origin: Vec3,
+    pub lookat: Vec3,
+    pub vup: Vec3,
+    /// Vertical field of view in degrees.
+    pub vfov: f32,
+    pub aspect: f32,
+    pub focus_dist: f32,
     pub lower_left_corner: Vec3,
     pub horizontal: Vec3,
     pub vertical: Vec3,
@@ -18,6 +40,12 @@ pub struct Camera {
     pub lens_radius: f32,
     pub aperture_shape: ApertureShape,
     pub wavelength_sampler: WavelengthSampler,
+    /// Rays get times spread evenly between the shutter opening and
+    /// closing, equal times give no motion blur.
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+    /// Moves the camera over time, around its origin.
+    pub motion: Option<Keyframes>,
 }
 
 impl Camera {
@@ -31,63 +59,135 @@ impl Camera {
         focus_dist: f32,
     ) -> Camera {
         use ApertureShape::*;
-        let lens_radius = aperture / 2.0;
+        let mut camera = Camera {
+            origin,
+            lookat,
+            vup,
+            vfov,
+            aspect,
+            focus_dist,
+            lower_left_corner: Vec3::zeros(),
+            horizontal: Vec3::zeros(),
+            vertical: Vec3::zeros(),
+            u: Vec3::zeros(),
+            v: Vec3::zeros(),
+            w: Vec3::zeros(),
+            lens_radius: aperture / 2.0,
+            aperture_shape: Circle,
+            wavelength_sampler: WavelengthSampler::new(),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            motion: None,
+        };
+        camera.update();
+        camera
+    }
 
-        let theta = deg_to_rad(vfov);
+    /// Recomputes the image plane, call after changing the public fields.
+    pub fn update(&mut self) {
+        let theta = deg_to_rad(self.vfov);
         let half_height = (theta / 2.0).tan();
-        let half_width = aspect * half_height;
+        let half_width = self.aspect * half_height;
 
-        let w = (origin - lookat).normalize();
-        let u = vup.cross(&w).normalize();
-        let v = w.cross(&u);
+        self.w = (self.origin - self.lookat).normalize();
+        self.u = self.vup.cross(&self.w).normalize();
+        self.v = self.w.cross(&self.u);
 
-        let lower_left_corner =
-            origin - half_width * focus_dist * u - half_height * focus_dist * v - focus_dist * w;
-        let horizontal = 2.0 * half_width * focus_dist * u;
-        let vertical = 2.0 * half_height * focus_dist * v;
+        let focus_dist = self.focus_dist;
+        self.lower_left_corner = self.origin
+            - half_width * focus_dist * self.u
+            - half_height * focus_dist * self.v
+            - focus_dist * self.w;
+        self.horizontal = 2.0 * half_width * focus_dist * self.u;
+        self.vertical = 2.0 * half_height * focus_dist * self.v;
+    }
 
-        let wavelength_sampler = WavelengthSampler::new();
+    /// Rotates the camera around the look at point, by `yaw` degrees around
+    /// the up vector and `pitch` degrees up or down. Stops short of looking
+    /// straight along the up vector.
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        let up = Unit::new_normalize(self.vup);
+        let offset = self.origin - self.lookat;
+        let offset = Rotation3::from_axis_angle(&up, deg_to_rad(yaw)) * offset;
 
-        Camera {
-            origin,
-            lower_left_corner,
-            horizontal,
-            vertical,
-            u,
-            v,
-            w,
-            lens_radius,
-            aperture_shape: Circle,
-            wavelength_sampler,
-        }
+        let elevation = offset.normalize().dot(&up).acos();
+        let min_elevation = deg_to_rad(1.0);
+        let max_elevation = deg_to_rad(179.0);
+        let pitch = deg_to_rad(pitch)
+            .min(elevation - min_elevation)
+            .max(elevation - max_elevation);
+        let right = Unit::new_normalize(self.vup.cross(&offset));
+        let offset = Rotation3::from_axis_angle(&right, pitch) * offset;
+
+        self.origin = self.lookat + offset;
+        self.update();
+    }
+
+    /// Moves the camera and the look at point sideways and up, in fractions
+    /// of the view height at the look at point.
+    pub fn pan(&mut self, right: f32, up: f32) {
+        let distance = (self.origin - self.lookat).magnitude();
+        let view_height = 2.0 * (deg_to_rad(self.vfov) / 2.0).tan() * distance;
+        let offset = (right * self.u + up * self.v) * view_height;
+        self.origin += offset;
+        self.lookat += offset;
+        self.update();
     }
-    pub fn get_ray_tri(&self, s: f32, t: f32) -> (Ray, f32) {
+
+    /// Scales the distance to the look at point, below one moves closer.
+    pub fn dolly(&mut self, factor: f32) {
+        self.origin = self.lookat + (self.origin - self.lookat) * factor;
+        self.update();
+    }
+
+    pub fn set_focus_distance(&mut self, focus_dist: f32) {
+        self.focus_dist = focus_dist;
+        self.update();
+    }
+
+    pub fn aperture(&self) -> f32 {
+        2.0 * self.lens_radius
+    }
+
+    pub fn set_aperture(&mut self, aperture: f32) {
+        self.lens_radius = aperture / 2.0;
+    }
+
+    pub fn set_shutter(&mut self, open: f32, close: f32) {
+        self.shutter_open = open;
+        self.shutter_close = close;
+    }
+
+    /// The camera motion at `time` as a world space transform.
+    pub fn motion_at(&self, time: f32) -> Option<Mat4> {
+        let motion = self.motion.as_ref()?;
+        Some(translation(self.origin) * motion.at(time) * translation(-self.origin))
+    }
+
+    pub fn get_ray_tri(&self, s: f32, t: f32, sampler: &mut dyn Sampler) -> (Ray, f32) {
         use ApertureShape::*;
+        sampler.set_dimension(LENS_DIMENSION);
         let rd = match &self.aperture_shape {
-            Circle => self.lens_radius * random_unit_in_disk(),
-            Hexagon => loop {
-                let p = self.lens_radius * random_unit_in_disk();
-                if inside_hexagon(self.lens_radius * 2.0, p.x, p.y) {
-                    break p;
-                }
-            },
+            Circle => self.lens_radius * random_unit_in_disk(sampler),
+            Hexagon => self.lens_radius * sample_hexagon(sampler.next_2d()),
         };
         let offset = self.u * rd.x + self.v * rd.y;
 
-        let (wavelength, pdf) = self.wavelength_sampler.get_wavelengths();
+        sampler.set_dimension(WAVELENGTH_DIMENSION);
+        let (wavelength, pdf) = self.wavelength_sampler.get_wavelengths(sampler);
 
-        let direction =
+        sampler.set_dimension(TIME_DIMENSION);
+        let time = self.shutter_open + (self.shutter_close - self.shutter_open) * sampler.next_f32();
+
+        let mut direction =
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset;
-        let origin = self.origin + offset;
-
-        (
-            Ray {
-                origin,
-                direction,
-                wavelength,
-            },
-            pdf,
-        )
+        let mut origin = self.origin + offset;
+        if let Some(motion) = self.motion_at(time) {
+            origin = motion.transform_point(&origin.into()).coords;
+            direction = motion.transform_vector(&direction);
+        }
+
+        (Ray::new(origin, direction, wavelength, time), pdf)
     }
 }
 
@@ -103,10 +203,17 @@ impl Default for Camera {
     }
 }
 
-const A: f32 = 0.25 * 1.73205080757;
-
-fn inside_hexagon(d: f32, x: f32, y: f32) -> bool {
-    let dx = x.abs() / d;
-    let dy = y.abs() / d;
-    (dy <= A) && (A * dx + 0.25 * dy <= 0.5 * A)
+/// A uniform point in the unit hexagon with corners on the x axis. The
+/// first number picks one of its six triangles and is reused inside it, so
+/// the lens takes two dimensions like the circle.
+fn sample_hexagon(r: Vec2) -> Vec3 {
+    let sextant = (r.x * 6.0).min(5.999_999);
+    let triangle = sextant.floor();
+    let corner = |i: f32| {
+        let angle = i * PI / 3.0;
+        Vec3::new(angle.cos(), angle.sin(), 0.0)
+    };
+    // The square root spreads the points evenly from the centre out.
+    let scale = (sextant - triangle).sqrt();
+    scale * ((1.0 - r.y) * corner(triangle) + r.y * corner(triangle + 1.0))
 }
@@ -0,0 +1,351 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::camera::Camera;
+use crate::film::{AovBuffers, Film, LightLayers, SpectralBuffer};
+use crate::filter::Filter;
+use crate::geometry::Geometry;
+use crate::sampler::{hash, SamplerKind};
+use crate::vector::Vec3;
+
+const MAGIC: &[u8; 4] = b"MXCK";
+const VERSION: u32 = 4;
+
+/// Everything needed to continue an interrupted render: the accumulated
+/// film, the number of finished passes and the fingerprint of the render.
+pub struct Checkpoint {
+    pub film: Film,
+    pub passes: u32,
+    pub fingerprint: u64,
+}
+
+/// Identifies what a checkpoint is a render of: the scene, the camera, the
+/// sampling and the filter. A scene is only known by its name and bounds,
+/// after editing its code the old checkpoint has to be deleted.
+pub fn render_fingerprint(
+    scene_name: &str,
+    world: &dyn Geometry,
+    camera: &Camera,
+    sampler: SamplerKind,
+    filter: &dyn Filter,
+    seed: u64,
+    samples: u32,
+) -> u64 {
+    let bounds = world.aabb();
+    let floats = [
+        bounds.min.as_slice(),
+        bounds.max.as_slice(),
+        camera.origin.as_slice(),
+        camera.lookat.as_slice(),
+        camera.vup.as_slice(),
+        &[
+            camera.vfov,
+            camera.aspect,
+            camera.focus_dist,
+            camera.lens_radius,
+            camera.shutter_open,
+            camera.shutter_close,
+        ],
+    ]
+    .concat();
+    let description = format!(
+        "{} {:?} {:?} {:?} {:?}",
+        scene_name, camera.aperture_shape, camera.motion, sampler, filter
+    );
+    let values = floats
+        .iter()
+        .map(|v| v.to_bits() as u64)
+        .chain(description.bytes().map(u64::from))
+        .chain([seed, samples as u64].iter().copied())
+        .collect::<Vec<u64>>();
+    hash(&values)
+}
+
+/// Writes to a temporary file first and renames it, so an interruption
+/// while saving leaves the previous checkpoint intact.
+pub fn save_checkpoint(path: &str, film: &Film, passes: u32, fingerprint: u64) -> io::Result<()> {
+    if let Some(parent) = Path::new(path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temporary_path = format!("{}.tmp", path);
+    {
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+        write_checkpoint(&mut writer, film, passes, fingerprint)?;
+        writer.flush()?;
+    }
+    fs::rename(temporary_path, path)
+}
+
+impl Checkpoint {
+    pub fn load(path: &str) -> io::Result<Checkpoint> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Checkpoint::read(&mut reader)
+    }
+
+    /// Checks that the checkpoint is of the render with `fingerprint`, with
+    /// the same resolution and output buffers as `film`, which is what a
+    /// resumed render will use.
+    pub fn matches(&self, film: &Film, fingerprint: u64) -> bool {
+        let spectral_matches = match (&self.film.spectral, &film.spectral) {
+            (Some(a), Some(b)) => a.bins == b.bins && a.range == b.range,
+            (None, None) => true,
+            _ => false,
+        };
+        let light_layers_match = match (&self.film.light_layers, &film.light_layers) {
+            (Some(a), Some(b)) => a.light_ids == b.light_ids,
+            (None, None) => true,
+            _ => false,
+        };
+        self.fingerprint == fingerprint
+            && self.film.width == film.width
+            && self.film.height == film.height
+            && spectral_matches
+            && self.film.aovs.is_some() == film.aovs.is_some()
+            && light_layers_match
+    }
+
+    fn read(r: &mut impl Read) -> io::Result<Checkpoint> {
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a checkpoint file"));
+        }
+        let version = read_u32(r)?;
+        if version != VERSION {
+            return Err(invalid_data(&format!("unsupported checkpoint version {}", version)));
+        }
+        let width = read_u64(r)? as usize;
+        let height = read_u64(r)? as usize;
+        let passes = read_u32(r)?;
+        let fingerprint = read_u64(r)?;
+        let pixels = width * height;
+
+        let mut film = Film::new(width, height);
+        film.weighted_tristimulus = read_vec3s(r, pixels)?;
+        film.weighted_squares = read_vec3s(r, pixels)?;
+        film.sample_counts = read_u32s(r, pixels)?;
+        film.weight_sums = read_f32s(r, pixels)?;
+        film.weight_squared_sums = read_f32s(r, pixels)?;
+
+        if read_u32(r)? != 0 {
+            let bins = read_u64(r)? as usize;
+            let range = read_f32s(r, 2)?;
+            let mut spectral = SpectralBuffer::new(0, bins, range[0]..range[1]);
+            spectral.data = read_f32s(r, pixels * bins)?;
+            film.spectral = Some(spectral);
+        }
+
+        if read_u32(r)? != 0 {
+            film.aovs = Some(AovBuffers {
+                normal: read_vec3s(r, pixels)?,
+                depth: read_f32s(r, pixels)?,
+                position: read_vec3s(r, pixels)?,
+                albedo: read_vec3s(r, pixels)?,
+                object_id: read_u32s(r, pixels)?,
+                material_id: read_u32s(r, pixels)?,
+                id_weights: read_f32s(r, pixels)?,
+            });
+        }
+
+        if read_u32(r)? != 0 {
+            let light_count = read_u64(r)? as usize;
+            let light_ids = read_u32s(r, light_count)?;
+            let mut light_layers = LightLayers::new(0, light_ids);
+            for layer in light_layers
+                .light_paths
+                .iter_mut()
+                .chain(light_layers.lights.iter_mut())
+            {
+                *layer = read_vec3s(r, pixels)?;
+            }
+            film.light_layers = Some(light_layers);
+        }
+
+        Ok(Checkpoint { film, passes, fingerprint })
+    }
+}
+
+fn write_checkpoint(w: &mut impl Write, film: &Film, passes: u32, fingerprint: u64) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    write_u32(w, VERSION)?;
+    write_u64(w, film.width as u64)?;
+    write_u64(w, film.height as u64)?;
+    write_u32(w, passes)?;
+    write_u64(w, fingerprint)?;
+
+    write_vec3s(w, &film.weighted_tristimulus)?;
+    write_vec3s(w, &film.weighted_squares)?;
+    write_u32s(w, &film.sample_counts)?;
+    write_f32s(w, &film.weight_sums)?;
+    write_f32s(w, &film.weight_squared_sums)?;
+
+    write_u32(w, film.spectral.is_some() as u32)?;
+    if let Some(spectral) = &film.spectral {
+        write_u64(w, spectral.bins as u64)?;
+        write_f32s(w, &[spectral.range.start, spectral.range.end])?;
+        write_f32s(w, &spectral.data)?;
+    }
+
+    write_u32(w, film.aovs.is_some() as u32)?;
+    if let Some(aovs) = &film.aovs {
+        write_vec3s(w, &aovs.normal)?;
+        write_f32s(w, &aovs.depth)?;
+        write_vec3s(w, &aovs.position)?;
+        write_vec3s(w, &aovs.albedo)?;
+        write_u32s(w, &aovs.object_id)?;
+        write_u32s(w, &aovs.material_id)?;
+        write_f32s(w, &aovs.id_weights)?;
+    }
+
+    write_u32(w, film.light_layers.is_some() as u32)?;
+    if let Some(light_layers) = &film.light_layers {
+        write_u64(w, light_layers.light_ids.len() as u64)?;
+        write_u32s(w, &light_layers.light_ids)?;
+        for layer in light_layers.light_paths.iter().chain(&light_layers.lights) {
+            write_vec3s(w, layer)?;
+        }
+    }
+    Ok(())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_u32(w: &mut impl Write, value: u32) -> io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+
+fn write_u64(w: &mut impl Write, value: u64) -> io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+
+fn write_u32s(w: &mut impl Write, values: &[u32]) -> io::Result<()> {
+    values.iter().try_for_each(|v| write_u32(w, *v))
+}
+
+fn write_f32s(w: &mut impl Write, values: &[f32]) -> io::Result<()> {
+    values.iter().try_for_each(|v| w.write_all(&v.to_le_bytes()))
+}
+
+fn write_vec3s(w: &mut impl Write, values: &[Vec3]) -> io::Result<()> {
+    values.iter().try_for_each(|v| write_f32s(w, &[v.x, v.y, v.z]))
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+    let mut bytes = [0u8; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+fn read_u32s(r: &mut impl Read, count: usize) -> io::Result<Vec<u32>> {
+    (0..count).map(|_| read_u32(r)).collect()
+}
+
+fn read_f32s(r: &mut impl Read, count: usize) -> io::Result<Vec<f32>> {
+    (0..count).map(|_| read_f32(r)).collect()
+}
+
+fn read_vec3s(r: &mut impl Read, count: usize) -> io::Result<Vec<Vec3>> {
+    (0..count)
+        .map(|_| Ok(Vec3::new(read_f32(r)?, read_f32(r)?, read_f32(r)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use smallvec::smallvec;
+
+    use super::*;
+    use crate::film::{FirstHit, PixelSample};
+    use crate::filter::BoxFilter;
+    use crate::integrator::{LightPath, PathContribution};
+    use crate::vector::Vec2;
+
+    /// A film with every output enabled and a few samples on it.
+    fn film() -> Film {
+        let (width, height) = (4, 3);
+        let mut film = Film::new(width, height);
+        film.spectral = Some(SpectralBuffer::new(width * height, 8, 400.0..720.0));
+        film.aovs = Some(AovBuffers::new(width * height));
+        film.light_layers = Some(LightLayers::new(width * height, vec![2, 5]));
+        for i in 0..20 {
+            let sample = PixelSample {
+                position: Vec2::new((i % 4) as f32 + 0.3, (i % 3) as f32 + 0.7),
+                tristimulus: Vec3::new(0.1, 0.2, 0.3) * i as f32,
+                wavelength: 400.0 + 15.0 * i as f32,
+                radiance: 0.5 * i as f32,
+                first_hit: FirstHit {
+                    normal: Vec3::new(0.0, 1.0, 0.0),
+                    depth: i as f32,
+                    position: Vec3::new(1.0, 2.0, i as f32),
+                    albedo: Vec3::repeat(0.5),
+                    object_id: i,
+                    material_id: i + 1,
+                },
+                contributions: smallvec![PathContribution {
+                    radiance: 0.5 * i as f32,
+                    light_path: LightPath::DirectDiffuse,
+                    object_id: 5,
+                }],
+            };
+            film.add_sample(&sample, &BoxFilter { radius: 0.5 });
+        }
+        film
+    }
+
+    fn checkpoint_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("maxwell_{}_{}.ckpt", name, std::process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn film_survives_a_round_trip() {
+        let path = checkpoint_path("round_trip");
+        let film = film();
+        save_checkpoint(&path, &film, 7, 42).unwrap();
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(checkpoint.passes, 7);
+        assert_eq!(checkpoint.fingerprint, 42);
+        assert_eq!(checkpoint.film, film);
+        assert!(checkpoint.matches(&film, 42));
+    }
+
+    #[test]
+    fn other_renders_are_rejected() {
+        let path = checkpoint_path("rejected");
+        save_checkpoint(&path, &film(), 7, 42).unwrap();
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!checkpoint.matches(&film(), 43));
+        assert!(!checkpoint.matches(&Film::new(4, 3), 42));
+        let mut other_layers = film();
+        other_layers.light_layers = Some(LightLayers::new(12, vec![2]));
+        assert!(!checkpoint.matches(&other_layers, 42));
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let path = checkpoint_path("not_a_checkpoint");
+        fs::write(&path, b"MXCK\x03\0\0\0").unwrap();
+        let error = Checkpoint::load(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -6,7 +6,7 @@ pub fn get_tristimulus(wavelength: f32) -> Vec3 {
     let index = indexf.floor() as isize;
     let remainder = indexf - index as f32;
 
-    if index < -1 || index > 80 {
+    if !(-1..=80).contains(&index) {
         // Wavelength is not in the visible spectrum.
         Vec3::zeros()
     } else if index == -1 {
@@ -31,7 +31,13 @@ pub fn get_tristimulus(wavelength: f32) -> Vec3 {
     }
 }
 
-fn gamma_correct(f: f32) -> f32 {
+/// The integral of the CIE Y colour matching function, the Y of an equal
+/// energy spectrum with unit radiance.
+pub fn cie_y_integral() -> f32 {
+    Y.iter().sum::<f32>() * 5.0
+}
+
+pub fn gamma_correct(f: f32) -> f32 {
     if f <= 0.0031308 {
         12.92 * f
     } else {
@@ -39,27 +45,6 @@ fn gamma_correct(f: f32) -> f32 {
     }
 }
 
-/// Converts a CIE XYZ tristimulus to an sRGB colour.
-pub fn cie_to_rgb(cie: &Vec3) -> Vec3 {
-    // Apply the sRGB matrix.
-    let r =  3.2406 * cie.x - 1.5372 * cie.y - 0.4986 * cie.z;
-    let g = -0.9689 * cie.x + 1.8758 * cie.y + 0.0415 * cie.z;
-    let b =  0.0557 * cie.x - 0.2040 * cie.y + 1.0570 * cie.z;
-
-    // Then do gamma correction.
-    Vec3::new(
-        gamma_correct(clamp(r)),
-        gamma_correct(clamp(g)),
-        gamma_correct(clamp(b))
-    )
-}
-
-fn clamp(x: f32) -> f32 {
-    if x.lt(&0.0) { 0.0 }
-    else if 1.0f32.lt(&x) { 1.0 }
-    else { x }
-}
-
 pub fn find_exposure(tristimulus_buffer: &Vec<Vec3>) -> f32 {
     let n = tristimulus_buffer.len() as f32;
 
@@ -123,11 +108,11 @@ pub const X: [f32; 81] = [
     0.842500,
     0.916300,
     0.978600,
-    1.026300,
-    1.056700,
-    1.062200,
-    1.045600,
-    1.002600,
+    1.0263,
+    1.0567,
+    1.0622,
+    1.0456,
+    1.0026,
     0.938400,
     0.854450,
     0.751400,
@@ -260,17 +245,17 @@ pub const Z: [f32; 81] = [
     0.207400,
     0.371300,
     0.645600,
-    1.039050,
-    1.385600,
-    1.622960,
-    1.747060,
-    1.782600,
-    1.772110,
-    1.744100,
-    1.669200,
-    1.528100,
-    1.287640,
-    1.041900,
+    1.03905,
+    1.3856,
+    1.62296,
+    1.74706,
+    1.7826,
+    1.77211,
+    1.7441,
+    1.6692,
+    1.5281,
+    1.28764,
+    1.0419,
     0.812950,
     0.616200,
     0.465180,
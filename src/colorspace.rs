@@ -0,0 +1,364 @@
+use nalgebra::Matrix3;
+
+use crate::color::{gamma_correct, X, Y, Z};
+use crate::material::spectrum::boltzmann;
+use crate::vector::Vec3;
+
+pub type Mat3 = Matrix3<f32>;
+
+/// CIE 1931 xy chromaticities of the white points used by the colour spaces.
+pub const WHITE_D65: (f32, f32) = (0.31270, 0.32900);
+pub const WHITE_ACES: (f32, f32) = (0.32168, 0.33767);
+pub const WHITE_E: (f32, f32) = (1.0 / 3.0, 1.0 / 3.0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    /// CIE 1931 XYZ, the space the renderer accumulates in. Written to EXR
+    /// as R, G and B channels with XYZ chromaticities, as OpenEXR expects.
+    Xyz,
+    /// Linear sRGB, which shares its primaries with Rec.709.
+    LinearSrgb,
+    Rec2020,
+    DisplayP3,
+    /// ACES AP1 primaries.
+    AcesCg,
+    /// ACES AP0 primaries.
+    Aces2065_1,
+}
+
+impl ColorSpace {
+    pub fn parse(name: &str) -> Option<Self> {
+        use ColorSpace::*;
+        match name {
+            "xyz" => Some(Xyz),
+            "linear-srgb" => Some(LinearSrgb),
+            "rec2020" => Some(Rec2020),
+            "display-p3" => Some(DisplayP3),
+            "acescg" => Some(AcesCg),
+            "aces2065-1" => Some(Aces2065_1),
+            _ => None,
+        }
+    }
+
+    /// The xy chromaticities of the red, green and blue primaries.
+    pub fn primaries(&self) -> [(f32, f32); 3] {
+        use ColorSpace::*;
+        match self {
+            Xyz => [(1.0, 0.0), (0.0, 1.0), (0.0, 0.0)],
+            LinearSrgb => [(0.640, 0.330), (0.300, 0.600), (0.150, 0.060)],
+            Rec2020 => [(0.708, 0.292), (0.170, 0.797), (0.131, 0.046)],
+            DisplayP3 => [(0.680, 0.320), (0.265, 0.690), (0.150, 0.060)],
+            AcesCg => [(0.713, 0.293), (0.165, 0.830), (0.128, 0.044)],
+            Aces2065_1 => [(0.7347, 0.2653), (0.0, 1.0), (0.0001, -0.0770)],
+        }
+    }
+
+    /// The white point of the primaries, for XYZ this is the equal energy
+    /// white required by the OpenEXR chromaticities convention.
+    pub fn white(&self) -> (f32, f32) {
+        use ColorSpace::*;
+        match self {
+            Xyz => WHITE_E,
+            LinearSrgb | Rec2020 | DisplayP3 => WHITE_D65,
+            AcesCg | Aces2065_1 => WHITE_ACES,
+        }
+    }
+
+    /// The white that neutral colours are adapted to when converting into
+    /// this space. XYZ output keeps the D65 white the renderer assumes.
+    pub fn adopted_white(&self) -> (f32, f32) {
+        match self {
+            ColorSpace::Xyz => WHITE_D65,
+            _ => self.white(),
+        }
+    }
+
+    pub fn rgb_to_xyz(&self) -> Mat3 {
+        if *self == ColorSpace::Xyz {
+            return Mat3::identity();
+        }
+        let [r, g, b] = self.primaries();
+        let primaries = Mat3::from_columns(&[xy_to_xyz(r), xy_to_xyz(g), xy_to_xyz(b)]);
+        // Scale the primaries so that RGB (1, 1, 1) maps onto the white point.
+        let scale = primaries.try_inverse().unwrap() * xy_to_xyz(self.white());
+        primaries * Mat3::from_diagonal(&scale)
+    }
+
+    pub fn xyz_to_rgb(&self) -> Mat3 {
+        self.rgb_to_xyz().try_inverse().unwrap()
+    }
+
+    /// Applies the display transfer function of this space to a linear
+    /// value. Spaces that are not meant for display are left linear.
+    pub fn encode(&self, v: f32) -> f32 {
+        use ColorSpace::*;
+        match self {
+            LinearSrgb | DisplayP3 => gamma_correct(v),
+            Rec2020 => rec2020_oetf(v),
+            Xyz | AcesCg | Aces2065_1 => v,
+        }
+    }
+
+    /// The complete transform from renderer XYZ, whose neutral is
+    /// `source_white`, into this colour space.
+    pub fn adapted_xyz_to_rgb(&self, source_white: (f32, f32), adaptation: ChromaticAdaptation) -> Mat3 {
+        self.xyz_to_rgb() * adaptation.transform(source_white, self.adopted_white())
+    }
+}
+
+/// The BT.2020 opto-electronic transfer function, a linear segment near
+/// black followed by a 0.45 power curve.
+fn rec2020_oetf(v: f32) -> f32 {
+    const ALPHA: f32 = 1.099_296_8;
+    const BETA: f32 = 0.018_053_97;
+    if v < BETA {
+        4.5 * v
+    } else {
+        ALPHA * v.powf(0.45) - (ALPHA - 1.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChromaticAdaptation {
+    Bradford,
+    /// Von Kries using the Hunt-Pointer-Estevez cone responses.
+    VonKries,
+    Cat02,
+    /// Scales XYZ directly, the crudest transform.
+    XyzScaling,
+    /// Leaves the colours unadapted.
+    None,
+}
+
+impl ChromaticAdaptation {
+    pub fn parse(name: &str) -> Option<Self> {
+        use ChromaticAdaptation::*;
+        match name {
+            "bradford" => Some(Bradford),
+            "von-kries" => Some(VonKries),
+            "cat02" => Some(Cat02),
+            "xyz-scaling" => Some(XyzScaling),
+            "none" => Some(None),
+            _ => Option::None,
+        }
+    }
+
+    fn cone_response(&self) -> Mat3 {
+        use ChromaticAdaptation::*;
+        match self {
+            Bradford => Mat3::new(
+                 0.8951,  0.2664, -0.1614,
+                -0.7502,  1.7135,  0.0367,
+                 0.0389, -0.0685,  1.0296,
+            ),
+            VonKries => Mat3::new(
+                 0.40024, 0.70760, -0.08081,
+                -0.22630, 1.16532,  0.04570,
+                 0.0,     0.0,      0.91822,
+            ),
+            Cat02 => Mat3::new(
+                 0.7328, 0.4296, -0.1624,
+                -0.7036, 1.6975,  0.0061,
+                 0.0030, 0.0136,  0.9834,
+            ),
+            XyzScaling | None => Mat3::identity(),
+        }
+    }
+
+    /// Adapts XYZ colours seen under `source_white` to `target_white`.
+    pub fn transform(&self, source_white: (f32, f32), target_white: (f32, f32)) -> Mat3 {
+        if *self == ChromaticAdaptation::None {
+            return Mat3::identity();
+        }
+        let cone_response = self.cone_response();
+        let source = cone_response * xy_to_xyz(source_white);
+        let target = cone_response * xy_to_xyz(target_white);
+        let scale = Mat3::from_diagonal(&target.component_div(&source));
+        cone_response.try_inverse().unwrap() * scale * cone_response
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Illuminant {
+    A,
+    D50,
+    D55,
+    D65,
+    D75,
+    E,
+    F2,
+    F7,
+    F11,
+}
+
+impl Illuminant {
+    pub fn parse(name: &str) -> Option<Self> {
+        use Illuminant::*;
+        match name {
+            "a" => Some(A),
+            "d50" => Some(D50),
+            "d55" => Some(D55),
+            "d65" => Some(D65),
+            "d75" => Some(D75),
+            "e" => Some(E),
+            "f2" => Some(F2),
+            "f7" => Some(F7),
+            "f11" => Some(F11),
+            _ => None,
+        }
+    }
+
+    /// CIE 1931 2° chromaticity of the illuminant.
+    pub fn white(&self) -> (f32, f32) {
+        use Illuminant::*;
+        match self {
+            A => (0.44757, 0.40745),
+            D50 => (0.34567, 0.35850),
+            D55 => (0.33242, 0.34743),
+            D65 => WHITE_D65,
+            D75 => (0.29902, 0.31485),
+            E => WHITE_E,
+            F2 => (0.37208, 0.37529),
+            F7 => (0.31292, 0.32933),
+            F11 => (0.38052, 0.37713),
+        }
+    }
+}
+
+/// The colour in the render that should come out neutral.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WhitePoint {
+    /// A black body at the given correlated colour temperature in kelvin.
+    Cct(f32),
+    Illuminant(Illuminant),
+    /// The colour of the rendered pixel at (x, y), averaged with its neighbours.
+    Pixel(usize, usize),
+}
+
+impl WhitePoint {
+    /// A temperature such as `2000`, an illuminant such as `d50` or a pixel
+    /// such as `250,300`.
+    pub fn parse(name: &str) -> Option<Self> {
+        if let Ok(temperature) = name.parse::<f32>() {
+            return Some(WhitePoint::Cct(temperature)).filter(|_| temperature > 0.0);
+        }
+        if let Some((x, y)) = name.split_once(',') {
+            return Some(WhitePoint::Pixel(x.parse().ok()?, y.parse().ok()?));
+        }
+        Illuminant::parse(name).map(WhitePoint::Illuminant)
+    }
+}
+
+pub struct WhiteBalance {
+    pub white: WhitePoint,
+    pub adaptation: ChromaticAdaptation,
+}
+
+impl WhiteBalance {
+    pub fn new(white: WhitePoint, adaptation: ChromaticAdaptation) -> Self {
+        Self { white, adaptation }
+    }
+
+    /// Resolves the white point, the buffer is only used for picked pixels.
+    pub fn source_white(&self, tristimulus_buffer: &[Vec3], width: usize) -> (f32, f32) {
+        match self.white {
+            WhitePoint::Cct(temperature) => black_body_white(temperature),
+            WhitePoint::Illuminant(illuminant) => illuminant.white(),
+            WhitePoint::Pixel(x, y) => {
+                let height = tristimulus_buffer.len() / width;
+                let mut sum = Vec3::zeros();
+                for py in y.saturating_sub(1)..(y + 2).min(height) {
+                    for px in x.saturating_sub(1)..(x + 2).min(width) {
+                        sum += tristimulus_buffer[py * width + px];
+                    }
+                }
+                if sum.x + sum.y + sum.z > 0.0 {
+                    xyz_to_xy(&sum)
+                } else {
+                    WHITE_D65
+                }
+            }
+        }
+    }
+
+    /// The transform from renderer XYZ into white balanced `color_space`.
+    pub fn xyz_to_rgb(&self, color_space: ColorSpace, tristimulus_buffer: &[Vec3], width: usize) -> Mat3 {
+        color_space.adapted_xyz_to_rgb(self.source_white(tristimulus_buffer, width), self.adaptation)
+    }
+}
+
+impl Default for WhiteBalance {
+    fn default() -> Self {
+        WhiteBalance::new(WhitePoint::Illuminant(Illuminant::D65), ChromaticAdaptation::Bradford)
+    }
+}
+
+/// Converts an xy chromaticity into XYZ with a luminance of one.
+pub fn xy_to_xyz(xy: (f32, f32)) -> Vec3 {
+    let (x, y) = xy;
+    Vec3::new(x / y, 1.0, (1.0 - x - y) / y)
+}
+
+pub fn xyz_to_xy(xyz: &Vec3) -> (f32, f32) {
+    let sum = xyz.x + xyz.y + xyz.z;
+    (xyz.x / sum, xyz.y / sum)
+}
+
+/// The chromaticity of a black body, integrated against the CIE 1931
+/// colour matching functions.
+pub fn black_body_white(temperature: f32) -> (f32, f32) {
+    let xyz = (0..X.len()).fold(Vec3::zeros(), |acc, i| {
+        let wavelength = 380.0 + 5.0 * i as f32;
+        acc + boltzmann(wavelength, temperature) * Vec3::new(X[i], Y[i], Z[i])
+    });
+    xyz_to_xy(&xyz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOR_SPACES: [ColorSpace; 6] = [
+        ColorSpace::Xyz,
+        ColorSpace::LinearSrgb,
+        ColorSpace::Rec2020,
+        ColorSpace::DisplayP3,
+        ColorSpace::AcesCg,
+        ColorSpace::Aces2065_1,
+    ];
+
+    #[test]
+    fn rgb_white_maps_to_the_white_point() {
+        for color_space in COLOR_SPACES.iter() {
+            let white = color_space.rgb_to_xyz() * Vec3::new(1.0, 1.0, 1.0);
+            let expected = xy_to_xyz(color_space.white());
+            assert!((white - expected).norm() < 1.0e-4, "{:?} maps white to {}", color_space, white);
+        }
+    }
+
+    #[test]
+    fn adaptation_maps_the_source_white_to_the_target_white() {
+        let source = Illuminant::A.white();
+        for adaptation in [
+            ChromaticAdaptation::Bradford,
+            ChromaticAdaptation::VonKries,
+            ChromaticAdaptation::Cat02,
+            ChromaticAdaptation::XyzScaling,
+        ]
+        .iter()
+        {
+            let adapted = adaptation.transform(source, WHITE_D65) * xy_to_xyz(source);
+            assert!((adapted - xy_to_xyz(WHITE_D65)).norm() < 1.0e-4, "{:?} gave {}", adaptation, adapted);
+        }
+    }
+
+    #[test]
+    fn white_points_parse() {
+        assert_eq!(WhitePoint::parse("2000"), Some(WhitePoint::Cct(2000.0)));
+        assert_eq!(WhitePoint::parse("d50"), Some(WhitePoint::Illuminant(Illuminant::D50)));
+        assert_eq!(WhitePoint::parse("250,300"), Some(WhitePoint::Pixel(250, 300)));
+        assert_eq!(WhitePoint::parse("-5"), None);
+        assert_eq!(WhitePoint::parse("250,x"), None);
+        assert_eq!(WhitePoint::parse("d60"), None);
+    }
+}
@@ -1,5 +1,4 @@
-pub const GOLDEN_RATIO: f32 = 1.6180339887498948482045868343656381177203091798057628;
-pub const PLANCKS_CONSTANT: f32 = 6.62606957e-34;
+pub const PLANCKS_CONSTANT: f32 = 6.6260697e-34;
 pub const BOLTZMANNS_CONSTANT: f32 = 1.3806488e-23;
 pub const SPEED_OF_LIGHT: f32 = 299792458.0;
-pub const WIENS_CONSTANT: f32 = 2.897772126e-3;
\ No newline at end of file
+pub const WIENS_CONSTANT: f32 = 2.8977722e-3;
\ No newline at end of file
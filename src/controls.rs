@@ -0,0 +1,248 @@
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window};
+
+use crate::camera::Camera;
+use crate::colorspace::{ChromaticAdaptation, WHITE_E};
+use crate::film::Film;
+use crate::tonemap::{DisplayTransform, Exposure};
+use crate::vector::{Vec2, Vec3};
+
+/// Printed at startup.
+pub const HELP: &str = "Drag to orbit, right drag to pan, scroll to dolly, shift drag for a \
+region of interest. [ ] focus distance, , . aperture, - = exposure, space pauses, \
+V cycles the preview, Home resets the camera, Escape or S stops.";
+
+/// What the preview window shows, the AOV views need the AOV output.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PreviewView {
+    Beauty,
+    Albedo,
+    Normal,
+    Depth,
+}
+
+impl PreviewView {
+    fn next(self, has_aovs: bool) -> Self {
+        use PreviewView::*;
+        match self {
+            Beauty if has_aovs => Albedo,
+            Albedo => Normal,
+            Normal => Depth,
+            _ => Beauty,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        use PreviewView::*;
+        match self {
+            Beauty => "beauty",
+            Albedo => "albedo",
+            Normal => "normal",
+            Depth => "depth",
+        }
+    }
+
+    /// Display encoded RGB of the view, `beauty` is the XYZ buffer to show,
+    /// which may be denoised.
+    pub fn rgb(self, film: &Film, beauty: &Vec<Vec3>, display: &DisplayTransform) -> Vec<Vec3> {
+        use PreviewView::*;
+        if self == Beauty {
+            return display.apply(beauty, film.width);
+        }
+        let aovs = match film.aov_means() {
+            Some(aovs) => aovs,
+            None => return display.apply(beauty, film.width),
+        };
+        let encode = |rgb: Vec3| rgb.map(|v| display.color_space.encode(v.clamp(0.0, 1.0)));
+        match self {
+            Albedo => {
+                let from_albedo = display
+                    .color_space
+                    .adapted_xyz_to_rgb(WHITE_E, ChromaticAdaptation::Bradford);
+                aovs.albedo.iter().map(|albedo| encode(from_albedo * albedo)).collect()
+            }
+            Normal => aovs
+                .normal
+                .iter()
+                .map(|normal| encode(normal * 0.5 + Vec3::repeat(0.5)))
+                .collect(),
+            _ => {
+                let max_depth = aovs.depth.iter().cloned().fold(0.0, f32::max);
+                aovs.depth
+                    .iter()
+                    .map(|depth| encode(Vec3::repeat(1.0 - depth / max_depth.max(1e-6))))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Camera changes requested in the preview window. They are collected
+/// while a pass renders and applied between passes.
+pub struct CameraInput {
+    /// Yaw and pitch in degrees.
+    pub orbit: Vec2,
+    /// In fractions of the view height.
+    pub pan: Vec2,
+    /// The changes of the distance to the look at point, the focus distance
+    /// and the aperture are in stops.
+    pub dolly: f32,
+    pub focus: f32,
+    pub aperture: f32,
+    pub reset: bool,
+}
+
+impl Default for CameraInput {
+    fn default() -> Self {
+        CameraInput {
+            orbit: Vec2::zeros(),
+            pan: Vec2::zeros(),
+            dolly: 0.0,
+            focus: 0.0,
+            aperture: 0.0,
+            reset: false,
+        }
+    }
+}
+
+impl CameraInput {
+    pub fn is_empty(&self) -> bool {
+        self.orbit == Vec2::zeros()
+            && self.pan == Vec2::zeros()
+            && self.dolly == 0.0
+            && self.focus == 0.0
+            && self.aperture == 0.0
+            && !self.reset
+    }
+
+    pub fn apply(&self, camera: &mut Camera, home: &CameraHome) {
+        if self.reset {
+            home.restore(camera);
+        }
+        if self.orbit != Vec2::zeros() {
+            camera.orbit(self.orbit.x, self.orbit.y);
+        }
+        if self.pan != Vec2::zeros() {
+            camera.pan(self.pan.x, self.pan.y);
+        }
+        if self.dolly != 0.0 {
+            camera.dolly(2.0f32.powf(self.dolly));
+        }
+        if self.focus != 0.0 {
+            camera.set_focus_distance(camera.focus_dist * 2.0f32.powf(self.focus));
+        }
+        if self.aperture != 0.0 {
+            // Opening a pinhole starts from a small aperture.
+            let aperture = camera.aperture().max(1e-3);
+            camera.set_aperture(aperture * 2.0f32.powf(self.aperture));
+        }
+    }
+}
+
+/// The camera of the scene, restored with Home.
+pub struct CameraHome {
+    origin: Vec3,
+    lookat: Vec3,
+    focus_dist: f32,
+    aperture: f32,
+}
+
+impl CameraHome {
+    pub fn new(camera: &Camera) -> Self {
+        CameraHome {
+            origin: camera.origin,
+            lookat: camera.lookat,
+            focus_dist: camera.focus_dist,
+            aperture: camera.aperture(),
+        }
+    }
+
+    fn restore(&self, camera: &mut Camera) {
+        camera.origin = self.origin;
+        camera.lookat = self.lookat;
+        camera.focus_dist = self.focus_dist;
+        camera.set_aperture(self.aperture);
+        camera.update();
+    }
+}
+
+/// Keyboard and mouse state of the preview window.
+pub struct Controls {
+    pub paused: bool,
+    pub view: PreviewView,
+    pub camera: CameraInput,
+    last_mouse: Option<(f32, f32)>,
+    /// Left drags started with shift select the region of interest instead.
+    orbiting: bool,
+}
+
+impl Controls {
+    pub fn new() -> Self {
+        Controls {
+            paused: false,
+            view: PreviewView::Beauty,
+            camera: CameraInput::default(),
+            last_mouse: None,
+            orbiting: false,
+        }
+    }
+
+    /// Reads the input since the last call. Display changes take effect
+    /// immediately, camera changes are added to `camera`.
+    pub fn update(&mut self, win: &Window, exposure: &mut Exposure, has_aovs: bool) {
+        let shift = win.is_key_down(Key::LeftShift) || win.is_key_down(Key::RightShift);
+        let left = win.get_mouse_down(MouseButton::Left);
+        let right = win.get_mouse_down(MouseButton::Right);
+        let mouse = win.get_unscaled_mouse_pos(MouseMode::Clamp);
+        let (_, window_height) = win.get_size();
+
+        if left && !self.orbiting && self.last_mouse.is_none() {
+            self.orbiting = !shift;
+        } else if !left {
+            self.orbiting = false;
+        }
+        if let (Some(last), Some(mouse)) = (self.last_mouse, mouse) {
+            let delta = Vec2::new(mouse.0 - last.0, mouse.1 - last.1) / window_height as f32;
+            if self.orbiting {
+                self.camera.orbit += Vec2::new(-delta.x, delta.y) * 180.0;
+            } else if right {
+                self.camera.pan += Vec2::new(-delta.x, delta.y);
+            }
+        }
+        self.last_mouse = if left || right { mouse } else { None };
+
+        if let Some((_, scroll)) = win.get_scroll_wheel().filter(|(_, y)| *y != 0.0) {
+            self.camera.dolly -= scroll.signum() * 0.1;
+        }
+
+        let pressed = |key| win.is_key_pressed(key, KeyRepeat::Yes);
+        if pressed(Key::LeftBracket) {
+            self.camera.focus -= 0.1;
+        }
+        if pressed(Key::RightBracket) {
+            self.camera.focus += 0.1;
+        }
+        if pressed(Key::Comma) {
+            self.camera.aperture -= 0.5;
+        }
+        if pressed(Key::Period) {
+            self.camera.aperture += 0.5;
+        }
+        if win.is_key_pressed(Key::Home, KeyRepeat::No) {
+            self.camera.reset = true;
+        }
+        if pressed(Key::Minus) {
+            exposure.adjust(-0.5);
+        }
+        if pressed(Key::Equal) {
+            exposure.adjust(0.5);
+        }
+        if win.is_key_pressed(Key::Space, KeyRepeat::No) {
+            self.paused = !self.paused;
+            println!("{}", if self.paused { "Paused" } else { "Resumed" });
+        }
+        if win.is_key_pressed(Key::V, KeyRepeat::No) {
+            self.view = self.view.next(has_aovs);
+            println!("Showing the {}", self.view.name());
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use rayon::prelude::*;
+
+use crate::film::AovBuffers;
+use crate::vector::Vec3;
+
+/// Joint non-local means filter for the accumulated XYZ buffer. Colour
+/// differences are normalised by the per-pixel variance so converged
+/// pixels are left alone, and the albedo, normal and depth outputs keep
+/// edges and texture that the noisy colour alone can not distinguish.
+pub struct Denoiser {
+    /// Half size of the search window in pixels.
+    pub radius: usize,
+    /// Half size of the compared patches, zero compares single pixels which
+    /// turns the filter into a cross bilateral filter.
+    pub patch_radius: usize,
+    /// Filter strength, larger values blur more.
+    pub k: f32,
+    pub sigma_albedo: f32,
+    pub sigma_normal: f32,
+    /// Relative to the depth of the centre pixel.
+    pub sigma_depth: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            radius: 7,
+            patch_radius: 1,
+            k: 0.45,
+            sigma_albedo: 0.1,
+            sigma_normal: 0.3,
+            sigma_depth: 0.05,
+        }
+    }
+}
+
+impl Denoiser {
+    /// `variance` is the variance of the pixel means, not of the samples.
+    pub fn denoise(
+        &self,
+        tristimulus_buffer: &[Vec3],
+        variance: &[Vec3],
+        guides: Option<&AovBuffers>,
+        width: usize,
+        height: usize,
+    ) -> Vec<Vec3> {
+        let epsilon = 1.0e-10;
+        let k_squared = self.k * self.k;
+        let radius = self.radius as isize;
+        let patch_radius = self.patch_radius as isize;
+
+        let clamped = |x: isize, y: isize| -> usize {
+            let x = x.max(0).min(width as isize - 1) as usize;
+            let y = y.max(0).min(height as isize - 1) as usize;
+            y * width + x
+        };
+
+        let colour_weight = |p: usize, q: usize| -> f32 {
+            let (px, py) = ((p % width) as isize, (p / width) as isize);
+            let (qx, qy) = ((q % width) as isize, (q / width) as isize);
+            let mut distance = 0.0;
+            for dy in -patch_radius..=patch_radius {
+                for dx in -patch_radius..=patch_radius {
+                    let i = clamped(px + dx, py + dy);
+                    let j = clamped(qx + dx, qy + dy);
+                    let (u_i, u_j) = (tristimulus_buffer[i], tristimulus_buffer[j]);
+                    let (var_i, var_j) = (variance[i], variance[j]);
+                    for c in 0..3 {
+                        let difference = (u_i[c] - u_j[c]).powi(2) - (var_i[c] + var_i[c].min(var_j[c]));
+                        distance += difference / (epsilon + k_squared * (var_i[c] + var_j[c]));
+                    }
+                }
+            }
+            let patch_size = (2 * patch_radius + 1).pow(2) as f32;
+            (-(distance / (3.0 * patch_size)).max(0.0)).exp()
+        };
+
+        let feature_weight = |p: usize, q: usize| -> f32 {
+            match guides {
+                Some(aovs) => {
+                    let albedo = (aovs.albedo[p] - aovs.albedo[q]).magnitude_squared()
+                        / (self.sigma_albedo * self.sigma_albedo);
+                    let normal = (aovs.normal[p] - aovs.normal[q]).magnitude_squared()
+                        / (self.sigma_normal * self.sigma_normal);
+                    let sigma_depth = self.sigma_depth * aovs.depth[p].max(epsilon);
+                    let depth = (aovs.depth[p] - aovs.depth[q]).powi(2) / (sigma_depth * sigma_depth);
+                    (-0.5 * (albedo + normal + depth)).exp()
+                }
+                None => 1.0,
+            }
+        };
+
+        (0..height)
+            .into_par_iter()
+            .flat_map(|y| {
+                (0..width)
+                    .map(|x| {
+                        let p = y * width + x;
+                        let mut sum = Vec3::zeros();
+                        let mut weight_sum = 0.0;
+                        for qy in (y as isize - radius).max(0)..(y as isize + radius + 1).min(height as isize) {
+                            for qx in (x as isize - radius).max(0)..(x as isize + radius + 1).min(width as isize) {
+                                let q = qy as usize * width + qx as usize;
+                                let weight = colour_weight(p, q).min(feature_weight(p, q));
+                                sum += weight * tristimulus_buffer[q];
+                                weight_sum += weight;
+                            }
+                        }
+                        if weight_sum > 0.0 {
+                            sum / weight_sum
+                        } else {
+                            tristimulus_buffer[p]
+                        }
+                    })
+                    .collect::<Vec<Vec3>>()
+            })
+            .collect()
+    }
+}
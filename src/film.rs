@@ -0,0 +1,554 @@
+use smallvec::SmallVec;
+use std::collections::HashSet;
+use std::ops::{Mul, Range};
+
+use crate::color::get_tristimulus;
+use crate::filter::Filter;
+use crate::integrator::{LightPath, PathContribution};
+use crate::tile::Rect;
+use crate::vector::{Vec2, Vec3};
+
+/// The result of tracing a single camera ray through a pixel.
+pub struct PixelSample {
+    /// Where on the film the ray went through, in pixels.
+    pub position: Vec2,
+    pub tristimulus: Vec3,
+    pub wavelength: f32,
+    /// Spectral radiance along the ray, already divided by the wavelength pdf.
+    pub radiance: f32,
+    pub first_hit: FirstHit,
+    /// The radiance split per emitter hit, also divided by the wavelength pdf.
+    pub contributions: SmallVec<[PathContribution; 4]>,
+}
+
+/// What the camera ray saw first, all zero when it hit nothing.
+#[derive(Clone)]
+pub struct FirstHit {
+    pub normal: Vec3,
+    /// Distance from the camera to the hit point.
+    pub depth: f32,
+    pub position: Vec3,
+    /// Reflectance as the XYZ of a white lit surface, so grey has Y = reflectance.
+    pub albedo: Vec3,
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+impl Default for FirstHit {
+    fn default() -> Self {
+        FirstHit {
+            normal: Vec3::zeros(),
+            depth: 0.0,
+            position: Vec3::zeros(),
+            albedo: Vec3::zeros(),
+            object_id: 0,
+            material_id: 0,
+        }
+    }
+}
+
+/// Arbitrary output variables, filled from the first hit of every camera
+/// ray. On the film the continuous outputs hold weighted sums, see
+/// `Film::aov_means`.
+#[derive(Debug, PartialEq)]
+pub struct AovBuffers {
+    pub normal: Vec<Vec3>,
+    pub depth: Vec<f32>,
+    pub position: Vec<Vec3>,
+    pub albedo: Vec<Vec3>,
+    pub object_id: Vec<u32>,
+    pub material_id: Vec<u32>,
+    /// Filter weight of the sample the ids were taken from.
+    pub id_weights: Vec<f32>,
+}
+
+impl AovBuffers {
+    pub fn new(pixels: usize) -> Self {
+        Self {
+            normal: vec![Vec3::zeros(); pixels],
+            depth: vec![0.0; pixels],
+            position: vec![Vec3::zeros(); pixels],
+            albedo: vec![Vec3::zeros(); pixels],
+            object_id: vec![0; pixels],
+            material_id: vec![0; pixels],
+            id_weights: vec![0.0; pixels],
+        }
+    }
+
+    /// Sums the continuous outputs weighted by the filter. Ids can not be
+    /// averaged, they come from the sample with the largest weight, the one
+    /// closest to the pixel centre.
+    pub fn add_sample(&mut self, pixel: usize, sample: &PixelSample, weight: f32) {
+        let hit = &sample.first_hit;
+        self.normal[pixel] += hit.normal * weight;
+        self.depth[pixel] += hit.depth * weight;
+        self.position[pixel] += hit.position * weight;
+        self.albedo[pixel] += hit.albedo * weight;
+        if weight > self.id_weights[pixel] {
+            self.object_id[pixel] = hit.object_id;
+            self.material_id[pixel] = hit.material_id;
+            self.id_weights[pixel] = weight;
+        }
+    }
+
+    fn means(&self, weight_sums: &[f32]) -> Self {
+        Self {
+            normal: means(&self.normal, weight_sums),
+            depth: means(&self.depth, weight_sums),
+            position: means(&self.position, weight_sums),
+            albedo: means(&self.albedo, weight_sums),
+            object_id: self.object_id.clone(),
+            material_id: self.material_id.clone(),
+            id_weights: self.id_weights.clone(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.normal.fill(Vec3::zeros());
+        self.depth.fill(0.0);
+        self.position.fill(Vec3::zeros());
+        self.albedo.fill(Vec3::zeros());
+        self.object_id.fill(0);
+        self.material_id.fill(0);
+        self.id_weights.fill(0.0);
+    }
+
+    /// Adds a pixel of another buffer, keeping the ids of the larger weight.
+    fn add_pixel(&mut self, pixel: usize, other: &Self, other_pixel: usize) {
+        self.normal[pixel] += other.normal[other_pixel];
+        self.depth[pixel] += other.depth[other_pixel];
+        self.position[pixel] += other.position[other_pixel];
+        self.albedo[pixel] += other.albedo[other_pixel];
+        if other.id_weights[other_pixel] > self.id_weights[pixel] {
+            self.object_id[pixel] = other.object_id[other_pixel];
+            self.material_id[pixel] = other.material_id[other_pixel];
+            self.id_weights[pixel] = other.id_weights[other_pixel];
+        }
+    }
+}
+
+/// Divides weighted sums by the weight sums of their pixels. Pixels whose
+/// weights cancel out, which filters with negative lobes allow, stay black.
+fn means<T: Copy + Mul<f32, Output = T>>(sums: &[T], weight_sums: &[f32]) -> Vec<T> {
+    sums.iter()
+        .zip(weight_sums)
+        .map(|(sum, weight_sum)| *sum * inverse_weight(*weight_sum))
+        .collect()
+}
+
+fn inverse_weight(weight_sum: f32) -> f32 {
+    if weight_sum > 0.0 {
+        1.0 / weight_sum
+    } else {
+        0.0
+    }
+}
+
+/// All per-pixel accumulation buffers of a render. Samples are splatted
+/// onto the pixels around them, weighted by the reconstruction filter, and
+/// every buffer sums the weighted samples of its pixels. The means are only
+/// taken when reading the film, as the weights of filters with negative
+/// lobes can sum to zero. Pixels can receive different numbers of samples,
+/// so every pixel keeps its own weights.
+#[derive(Debug, PartialEq)]
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    /// Sum of the weighted XYZ of every pixel, `tristimulus` has the means.
+    pub weighted_tristimulus: Vec<Vec3>,
+    /// Sum of the weighted squared XYZ, per channel.
+    pub weighted_squares: Vec<Vec3>,
+    /// Samples taken inside every pixel.
+    pub sample_counts: Vec<u32>,
+    /// Sum of the filter weights of all samples splatted onto every pixel.
+    pub weight_sums: Vec<f32>,
+    pub weight_squared_sums: Vec<f32>,
+    pub spectral: Option<SpectralBuffer>,
+    pub aovs: Option<AovBuffers>,
+    pub light_layers: Option<LightLayers>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        let pixels = width * height;
+        Self {
+            width,
+            height,
+            weighted_tristimulus: vec![Vec3::zeros(); pixels],
+            weighted_squares: vec![Vec3::zeros(); pixels],
+            sample_counts: vec![0; pixels],
+            weight_sums: vec![0.0; pixels],
+            weight_squared_sums: vec![0.0; pixels],
+            spectral: None,
+            aovs: None,
+            light_layers: None,
+        }
+    }
+
+    /// An empty film of another size with the same outputs enabled.
+    pub fn with_outputs_of(other: &Film, width: usize, height: usize) -> Self {
+        let pixels = width * height;
+        let mut film = Film::new(width, height);
+        film.spectral = other
+            .spectral
+            .as_ref()
+            .map(|spectral| SpectralBuffer::new(pixels, spectral.bins, spectral.range.clone()));
+        film.aovs = other.aovs.as_ref().map(|_| AovBuffers::new(pixels));
+        film.light_layers = other
+            .light_layers
+            .as_ref()
+            .map(|layers| LightLayers::new(pixels, layers.light_ids.clone()));
+        film
+    }
+
+    /// Discards all samples and keeps the enabled outputs, e.g. after the
+    /// camera moved. The buffers are zeroed in place.
+    pub fn clear(&mut self) {
+        self.weighted_tristimulus.fill(Vec3::zeros());
+        self.weighted_squares.fill(Vec3::zeros());
+        self.sample_counts.fill(0);
+        self.weight_sums.fill(0.0);
+        self.weight_squared_sums.fill(0.0);
+        if let Some(spectral) = &mut self.spectral {
+            spectral.data.fill(0.0);
+        }
+        if let Some(aovs) = &mut self.aovs {
+            aovs.clear();
+        }
+        if let Some(light_layers) = &mut self.light_layers {
+            light_layers.clear();
+        }
+    }
+
+    /// Counts the sample in the pixel it lies in and splats it.
+    pub fn add_sample(&mut self, sample: &PixelSample, filter: &dyn Filter) {
+        let x = (sample.position.x as usize).min(self.width - 1);
+        let y = (sample.position.y as usize).min(self.height - 1);
+        self.sample_counts[y * self.width + x] += 1;
+        self.splat(sample, filter);
+    }
+
+    /// Adds the sums of a tile film onto the pixels of its region. Regions
+    /// of neighbouring tiles overlap by the filter radius, so the tiles have
+    /// to be added in the same order on every pass for the film to stay
+    /// deterministic.
+    pub fn add_tile(&mut self, tile: &TileFilm) {
+        let source = &tile.film;
+        for (i, (x, y)) in tile.region.pixels().enumerate() {
+            let pixel = y * self.width + x;
+            self.weighted_tristimulus[pixel] += source.weighted_tristimulus[i];
+            self.weighted_squares[pixel] += source.weighted_squares[i];
+            self.sample_counts[pixel] += source.sample_counts[i];
+            self.weight_sums[pixel] += source.weight_sums[i];
+            self.weight_squared_sums[pixel] += source.weight_squared_sums[i];
+            if let (Some(spectral), Some(source)) = (&mut self.spectral, &source.spectral) {
+                spectral.add_pixel(pixel, source, i);
+            }
+            if let (Some(aovs), Some(source)) = (&mut self.aovs, &source.aovs) {
+                aovs.add_pixel(pixel, source, i);
+            }
+            if let (Some(light_layers), Some(source)) = (&mut self.light_layers, &source.light_layers) {
+                light_layers.add_pixel(pixel, source, i);
+            }
+        }
+    }
+
+    /// Adds the sample to every pixel whose centre is within the filter
+    /// radius of the sample position.
+    pub fn splat(&mut self, sample: &PixelSample, filter: &dyn Filter) {
+        let radius = filter.radius();
+        let position = sample.position;
+        let x0 = (position.x - radius - 0.5).ceil().max(0.0) as usize;
+        let y0 = (position.y - radius - 0.5).ceil().max(0.0) as usize;
+        let x1 = ((position.x + radius - 0.5).floor() as isize).min(self.width as isize - 1);
+        let y1 = ((position.y + radius - 0.5).floor() as isize).min(self.height as isize - 1);
+
+        for y in y0 as isize..=y1 {
+            for x in x0 as isize..=x1 {
+                let weight = filter.evaluate(
+                    x as f32 + 0.5 - position.x,
+                    y as f32 + 0.5 - position.y,
+                );
+                if weight != 0.0 {
+                    self.add_weighted(y as usize * self.width + x as usize, sample, weight);
+                }
+            }
+        }
+    }
+
+    fn add_weighted(&mut self, pixel: usize, sample: &PixelSample, weight: f32) {
+        self.weight_sums[pixel] += weight;
+        self.weight_squared_sums[pixel] += weight * weight;
+        self.weighted_tristimulus[pixel] += sample.tristimulus * weight;
+        self.weighted_squares[pixel] +=
+            sample.tristimulus.component_mul(&sample.tristimulus) * weight;
+
+        if let Some(spectral) = &mut self.spectral {
+            spectral.add_sample(pixel, sample, weight);
+        }
+        if let Some(aovs) = &mut self.aovs {
+            aovs.add_sample(pixel, sample, weight);
+        }
+        if let Some(light_layers) = &mut self.light_layers {
+            light_layers.add_sample(pixel, sample, weight);
+        }
+    }
+
+    /// Weighted mean XYZ of every pixel.
+    pub fn tristimulus(&self) -> Vec<Vec3> {
+        means(&self.weighted_tristimulus, &self.weight_sums)
+    }
+
+    pub fn pixel_tristimulus(&self, pixel: usize) -> Vec3 {
+        self.weighted_tristimulus[pixel] * inverse_weight(self.weight_sums[pixel])
+    }
+
+    /// The weighted means of the AOVs.
+    pub fn aov_means(&self) -> Option<AovBuffers> {
+        self.aovs.as_ref().map(|aovs| aovs.means(&self.weight_sums))
+    }
+
+    /// The weighted means of the light layers.
+    pub fn light_layer_means(&self) -> Option<LightLayers> {
+        self.light_layers
+            .as_ref()
+            .map(|layers| layers.means(&self.weight_sums))
+    }
+
+    /// The weighted mean spectral radiance.
+    pub fn spectral_means(&self) -> Option<SpectralBuffer> {
+        self.spectral
+            .as_ref()
+            .map(|spectral| spectral.means(&self.weight_sums))
+    }
+
+    /// The variance of a pixel mean, not of its individual samples. Uses the
+    /// effective number of samples of the filter weights, which is the
+    /// sample count for a box filter.
+    pub fn pixel_variance_of_mean(&self, pixel: usize) -> Vec3 {
+        let weight_sum = self.weight_sums[pixel];
+        let weight_squared_sum = self.weight_squared_sums[pixel];
+        if weight_sum <= 0.0 || weight_squared_sum <= 0.0 {
+            return Vec3::zeros();
+        }
+        let effective_samples = weight_sum * weight_sum / weight_squared_sum;
+        if effective_samples <= 1.0 {
+            return Vec3::zeros();
+        }
+        let mean = self.pixel_tristimulus(pixel);
+        let variance = self.weighted_squares[pixel] / weight_sum - mean.component_mul(&mean);
+        variance.map(|v| v.max(0.0)) / (effective_samples - 1.0)
+    }
+
+    pub fn variance_of_mean(&self) -> Vec<Vec3> {
+        (0..self.weight_sums.len())
+            .map(|pixel| self.pixel_variance_of_mean(pixel))
+            .collect()
+    }
+
+    /// Standard error of the pixel luminance relative to the luminance itself.
+    pub fn relative_error(&self, pixel: usize) -> f32 {
+        let error = self.pixel_variance_of_mean(pixel).y.sqrt();
+        error / self.pixel_tristimulus(pixel).y.max(1.0e-4)
+    }
+}
+
+/// The pixels a tile of samples reaches, the tile grown by the filter
+/// radius, with a film of their own. A worker thread renders a pass of the
+/// tile into it without touching the shared film, which adds it afterwards.
+/// Kept from pass to pass while the tiles stay the same.
+pub struct TileFilm {
+    pub tile: Rect,
+    /// The grown tile, clipped to the image.
+    pub region: Rect,
+    /// Covers only `region`, its pixel (0, 0) is the region's corner.
+    pub film: Film,
+}
+
+impl TileFilm {
+    pub fn new(film: &Film, tile: Rect, filter: &dyn Filter) -> Self {
+        let padding = (filter.radius() + 0.5).ceil() as usize;
+        let region = Rect {
+            x0: tile.x0.saturating_sub(padding),
+            y0: tile.y0.saturating_sub(padding),
+            x1: (tile.x1 + padding).min(film.width),
+            y1: (tile.y1 + padding).min(film.height),
+        };
+        Self {
+            tile,
+            region,
+            film: Film::with_outputs_of(film, region.width(), region.height()),
+        }
+    }
+
+    /// Takes a sample positioned on the full image.
+    pub fn add_sample(&mut self, mut sample: PixelSample, filter: &dyn Filter) {
+        sample.position -= Vec2::new(self.region.x0 as f32, self.region.y0 as f32);
+        self.film.add_sample(&sample, filter);
+    }
+}
+
+/// Splits the tristimulus buffer by light path expression and by emitter.
+/// Both sets of layers sum to the beauty buffer.
+#[derive(Debug, PartialEq)]
+pub struct LightLayers {
+    /// Object ids of the emitters with their own layer, each only once. The
+    /// layer after them collects all other emitters.
+    pub light_ids: Vec<u32>,
+    /// Indexed by `LightPath::index`.
+    pub light_paths: Vec<Vec<Vec3>>,
+    pub lights: Vec<Vec<Vec3>>,
+}
+
+impl LightLayers {
+    /// Untagged geometry reports object id 0, so lights only get a layer
+    /// of their own when they are tagged.
+    pub fn new(pixels: usize, mut light_ids: Vec<u32>) -> Self {
+        assert!(
+            !light_ids.contains(&0),
+            "lights need a nonzero object id for their own layer"
+        );
+        let mut seen = HashSet::new();
+        light_ids.retain(|id| seen.insert(*id));
+        let light_paths = vec![vec![Vec3::zeros(); pixels]; LightPath::ALL.len()];
+        let lights = vec![vec![Vec3::zeros(); pixels]; light_ids.len() + 1];
+        Self {
+            light_ids,
+            light_paths,
+            lights,
+        }
+    }
+
+    /// Only the layers of the contributions change, the others receive a
+    /// zero.
+    pub fn add_sample(&mut self, pixel: usize, sample: &PixelSample, weight: f32) {
+        let tristimulus = get_tristimulus(sample.wavelength);
+        for contribution in &sample.contributions {
+            let value = contribution.radiance * tristimulus * weight;
+            self.light_paths[contribution.light_path.index()][pixel] += value;
+            let light = self
+                .light_ids
+                .iter()
+                .position(|id| *id == contribution.object_id)
+                .unwrap_or(self.light_ids.len());
+            self.lights[light][pixel] += value;
+        }
+    }
+
+    fn means(&self, weight_sums: &[f32]) -> Self {
+        let layer_means = |layers: &Vec<Vec<Vec3>>| {
+            layers.iter().map(|layer| means(layer, weight_sums)).collect()
+        };
+        Self {
+            light_ids: self.light_ids.clone(),
+            light_paths: layer_means(&self.light_paths),
+            lights: layer_means(&self.lights),
+        }
+    }
+
+    fn clear(&mut self) {
+        for layer in self.light_paths.iter_mut().chain(&mut self.lights) {
+            layer.fill(Vec3::zeros());
+        }
+    }
+
+    fn add_pixel(&mut self, pixel: usize, other: &Self, other_pixel: usize) {
+        let layers = self.light_paths.iter_mut().chain(&mut self.lights);
+        let other_layers = other.light_paths.iter().chain(&other.lights);
+        for (layer, other_layer) in layers.zip(other_layers) {
+            layer[pixel] += other_layer[other_pixel];
+        }
+    }
+
+    /// Layer names paired with their buffers, as written to the EXR.
+    pub fn named_layers(&self) -> Vec<(String, &Vec<Vec3>)> {
+        let light_paths = LightPath::ALL
+            .iter()
+            .zip(&self.light_paths)
+            .map(|(light_path, layer)| (format!("lpe.{}", light_path.name()), layer));
+        let lights = self.lights.iter().enumerate().map(|(i, layer)| {
+            let name = match self.light_ids.get(i) {
+                Some(id) => format!("light.{}", id),
+                None => String::from("light.other"),
+            };
+            (name, layer)
+        });
+        light_paths.chain(lights).collect()
+    }
+}
+
+/// Accumulates the spectral radiance of every pixel in a number of equally
+/// sized wavelength bins, as weighted sums on the film.
+#[derive(Debug, PartialEq)]
+pub struct SpectralBuffer {
+    pub bins: usize,
+    pub range: Range<f32>,
+    /// Pixel major storage, the bins of a pixel are contiguous.
+    pub data: Vec<f32>,
+}
+
+impl SpectralBuffer {
+    pub fn new(pixels: usize, bins: usize, range: Range<f32>) -> Self {
+        Self {
+            bins,
+            range,
+            data: vec![0.0; pixels * bins],
+        }
+    }
+
+    pub fn bin_width(&self) -> f32 {
+        (self.range.end - self.range.start) / self.bins as f32
+    }
+
+    pub fn bin_center(&self, bin: usize) -> f32 {
+        self.range.start + (bin as f32 + 0.5) * self.bin_width()
+    }
+
+    pub fn bin_index(&self, wavelength: f32) -> Option<usize> {
+        if wavelength < self.range.start || wavelength >= self.range.end {
+            None
+        } else {
+            Some((((wavelength - self.range.start) / self.bin_width()) as usize).min(self.bins - 1))
+        }
+    }
+
+    /// Every bin receives the sample, the ones the wavelength does not fall
+    /// into a zero one, which leaves their sums as they are.
+    pub fn add_sample(&mut self, pixel: usize, sample: &PixelSample, weight: f32) {
+        if let Some(bin) = self.bin_index(sample.wavelength) {
+            if !sample.radiance.is_nan() {
+                self.data[pixel * self.bins + bin] += sample.radiance / self.bin_width() * weight;
+            }
+        }
+    }
+
+    fn means(&self, weight_sums: &[f32]) -> Self {
+        let data = self
+            .data
+            .chunks(self.bins)
+            .zip(weight_sums)
+            .flat_map(|(bins, weight_sum)| {
+                let inverse = inverse_weight(*weight_sum);
+                bins.iter().map(move |v| v * inverse)
+            })
+            .collect();
+        Self {
+            bins: self.bins,
+            range: self.range.clone(),
+            data,
+        }
+    }
+
+    fn add_pixel(&mut self, pixel: usize, other: &Self, other_pixel: usize) {
+        let bins = &mut self.data[pixel * self.bins..(pixel + 1) * self.bins];
+        let other_bins = &other.data[other_pixel * other.bins..(other_pixel + 1) * other.bins];
+        for (bin, other_bin) in bins.iter_mut().zip(other_bins) {
+            *bin += other_bin;
+        }
+    }
+
+    /// Returns a single wavelength band as an image plane.
+    pub fn band(&self, bin: usize) -> Vec<f32> {
+        self.data.iter().skip(bin).step_by(self.bins).cloned().collect()
+    }
+}
@@ -0,0 +1,223 @@
+use std::f32::consts::PI;
+use std::fmt::Debug;
+
+/// Pixel reconstruction filter. Every sample is splatted onto all pixels
+/// within `radius` of it, weighted by the filter at its offset from the
+/// pixel centre. All filters here are separable.
+pub trait Filter: Sync + Send + Debug {
+    /// Half width of the square support, in pixels.
+    fn radius(&self) -> f32;
+    /// Weight of a sample at offset (x, y) from the pixel centre.
+    fn evaluate(&self, x: f32, y: f32) -> f32;
+}
+
+/// The filter called `name`: `box`, `tent`, `gaussian`, `mitchell` or
+/// `blackman-harris`.
+pub fn find(name: &str) -> Option<Box<dyn Filter>> {
+    match name {
+        "box" => Some(Box::new(BoxFilter { radius: 0.5 })),
+        "tent" => Some(Box::new(TentFilter { radius: 1.0 })),
+        "gaussian" => Some(Box::new(GaussianFilter::default())),
+        "mitchell" => Some(Box::new(MitchellFilter::default())),
+        "blackman-harris" => Some(Box::new(BlackmanHarrisFilter { radius: 1.5 })),
+        _ => None,
+    }
+}
+
+/// Equal weight over the support, a radius of 0.5 keeps every sample in
+/// its own pixel.
+#[derive(Debug)]
+pub struct BoxFilter {
+    pub radius: f32,
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+    /// Half open like the pixels themselves, so a sample on the edge between
+    /// two pixels only counts in the one it lies in.
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        let inside = |offset: f32| -self.radius < offset && offset <= self.radius;
+        if inside(x) && inside(y) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Linear falloff to zero at the radius.
+#[derive(Debug)]
+pub struct TentFilter {
+    pub radius: f32,
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        (self.radius - x.abs()).max(0.0) * (self.radius - y.abs()).max(0.0)
+    }
+}
+
+/// Gaussian shifted down to reach zero at the radius.
+#[derive(Debug)]
+pub struct GaussianFilter {
+    pub radius: f32,
+    pub sigma: f32,
+}
+
+impl Default for GaussianFilter {
+    fn default() -> Self {
+        GaussianFilter {
+            radius: 1.5,
+            sigma: 0.5,
+        }
+    }
+}
+
+impl GaussianFilter {
+    fn gaussian(&self, x: f32) -> f32 {
+        let g = |x: f32| (-x * x / (2.0 * self.sigma * self.sigma)).exp();
+        (g(x) - g(self.radius)).max(0.0)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+/// Mitchell-Netravali cubic, sharper than the Gaussian thanks to its small
+/// negative lobes. B = C = 1/3 is the recommended trade off between
+/// blurring and ringing.
+#[derive(Debug)]
+pub struct MitchellFilter {
+    pub radius: f32,
+    pub b: f32,
+    pub c: f32,
+}
+
+impl Default for MitchellFilter {
+    fn default() -> Self {
+        MitchellFilter {
+            radius: 2.0,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }
+    }
+}
+
+impl MitchellFilter {
+    /// The cubic is defined on [-2, 2], scaled to the radius.
+    fn mitchell(&self, x: f32) -> f32 {
+        let x = (2.0 * x / self.radius).abs();
+        let (b, c) = (self.b, self.c);
+        let value = if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            (-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)
+        } else {
+            (12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b)
+        };
+        value / 6.0
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.mitchell(x) * self.mitchell(y)
+    }
+}
+
+/// Four term Blackman-Harris window, close to a Gaussian but with a
+/// compact support and very low side lobes.
+#[derive(Debug)]
+pub struct BlackmanHarrisFilter {
+    pub radius: f32,
+}
+
+impl BlackmanHarrisFilter {
+    fn window(&self, x: f32) -> f32 {
+        if x.abs() > self.radius {
+            return 0.0;
+        }
+        let t = 2.0 * PI * (x / (2.0 * self.radius) + 0.5);
+        0.35875 - 0.48829 * t.cos() + 0.14128 * (2.0 * t).cos() - 0.01168 * (3.0 * t).cos()
+    }
+}
+
+impl Filter for BlackmanHarrisFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.window(x) * self.window(y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smallvec::SmallVec;
+
+    use super::*;
+    use crate::film::{Film, FirstHit, PixelSample};
+    use crate::vector::{Vec2, Vec3};
+
+    const NAMES: [&str; 5] = ["box", "tent", "gaussian", "mitchell", "blackman-harris"];
+
+    #[test]
+    fn filters_are_zero_past_their_radius() {
+        for name in NAMES.iter() {
+            let filter = find(name).unwrap();
+            let r = filter.radius();
+            assert!(filter.evaluate(0.0, 0.0) > 0.0, "{} is zero at its centre", name);
+            for outside in [r + 1.0e-3, r + 0.5, 2.0 * r + 1.0].iter() {
+                for inside in [0.0, 0.5 * r, -0.5 * r].iter() {
+                    for (x, y) in [(*outside, *inside), (-*outside, *inside), (*inside, *outside), (*inside, -*outside)].iter() {
+                        assert_eq!(filter.evaluate(*x, *y), 0.0, "{} at ({}, {})", name, x, y);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn splatted_constant_image_is_unchanged() {
+        let color = Vec3::new(0.25, 1.0, 3.0);
+        for name in NAMES.iter() {
+            let filter = find(name).unwrap();
+            let mut film = Film::new(6, 5);
+            // Eight by eight stratified samples per pixel.
+            for y in 0..5 * 8 {
+                for x in 0..6 * 8 {
+                    let sample = PixelSample {
+                        position: Vec2::new((x as f32 + 0.5) / 8.0, (y as f32 + 0.5) / 8.0),
+                        tristimulus: color,
+                        wavelength: 550.0,
+                        radiance: 1.0,
+                        first_hit: FirstHit::default(),
+                        contributions: SmallVec::new(),
+                    };
+                    film.add_sample(&sample, &*filter);
+                }
+            }
+            for (pixel, tristimulus) in film.tristimulus().iter().enumerate() {
+                assert!(film.weight_sums[pixel] > 0.0);
+                assert!((tristimulus - color).norm() < 1.0e-4, "{} gave {} at {}", name, tristimulus, pixel);
+            }
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use std::f32;
 use crate::ray::Ray;
-use crate::vector::Vec3;
+use nalgebra::Point3;
 
+use crate::vector::{Mat4, Vec3};
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, Debug)]
 pub struct AABB {
     pub min: Vec3,
@@ -20,6 +23,31 @@ impl AABB {
         t_min.max(t_small.max()) < t_max.min(t_big.min())        
     }
 
+    /// The slab test with the reciprocal of the ray direction precomputed,
+    /// for testing many boxes against the same ray. Returns where the ray
+    /// enters the box.
+    pub fn hit_inverse(
+        &self,
+        origin: &Vec3,
+        inv_direction: &Vec3,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<f32> {
+        let mut t_near = t_min;
+        let mut t_far = t_max;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - origin[axis]) * inv_direction[axis];
+            let t1 = (self.max[axis] - origin[axis]) * inv_direction[axis];
+            t_near = t0.min(t1).max(t_near);
+            t_far = t0.max(t1).min(t_far);
+        }
+        if t_near <= t_far {
+            Some(t_near)
+        } else {
+            None
+        }
+    }
+
     pub fn zero() -> Self {
         AABB {
             min: Vec3::zeros(),
@@ -29,6 +57,48 @@ impl AABB {
     pub fn is_inside(&self, point: Vec3) -> bool {
         point.zip_fold(&self.min, true, |acc, a, b| acc & (a >= b)) & point.zip_fold(&self.min, true, |acc, a, b| acc & (a <= b))
     }
+    /// Contains nothing, the start for growing a box around points or boxes.
+    pub fn empty() -> Self {
+        AABB {
+            min: Vec3::new(f32::MAX, f32::MAX, f32::MAX),
+            max: Vec3::new(f32::MIN, f32::MIN, f32::MIN),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+    pub fn grow(&self, point: Vec3) -> Self {
+        AABB {
+            min: self.min.inf(&point),
+            max: self.max.sup(&point),
+        }
+    }
+    /// One of the eight corners, bit 0, 1 and 2 of `index` pick the max
+    /// side along x, y and z.
+    pub fn corner(&self, index: usize) -> Vec3 {
+        Vec3::new(
+            if index & 1 == 0 { self.min.x } else { self.max.x },
+            if index & 2 == 0 { self.min.y } else { self.max.y },
+            if index & 4 == 0 { self.min.z } else { self.max.z },
+        )
+    }
+    /// The box around all eight transformed corners.
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        (0..8).fold(AABB::empty(), |bbox, corner| {
+            let point = Point3::from(self.corner(corner));
+            bbox.grow(matrix.transform_point(&point).coords)
+        })
+    }
     pub fn max() -> Self {
         AABB {
             min: Vec3::new(f32::MIN, f32::MIN, f32::MIN),
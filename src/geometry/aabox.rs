@@ -1,4 +1,3 @@
-use rand::{thread_rng, Rng};
 
 use crate::geometry::{
     aabb::AABB,
@@ -7,7 +6,7 @@ use crate::geometry::{
 };
 use crate::material::{Material, EmptyMaterial};
 use crate::ray::Ray;
-use crate::vector::{Vec2, Vec3};
+use crate::vector::Vec3;
 #[derive(Clone)]
 pub struct AABox {
     pub box_min: Vec3,
@@ -52,22 +51,13 @@ impl AABox {
             )
             .boxed(),
         );
-        sides.push(
-            AARect {
-                xy0: p.xz() - half_scale.xz(),
-                xy1: p.xz() + half_scale.xz(),
-                k: p.y + half_scale.y,
-                material: empty_material.clone(),
-                rect_type: XZ,
-            }
-            .boxed(),
-        );
+        // Rects in XZ face down, the top is flipped to face out.
         sides.push(
             FlipNormals::new(
                 AARect {
                     xy0: p.xz() - half_scale.xz(),
                     xy1: p.xz() + half_scale.xz(),
-                    k: p.y - half_scale.y,
+                    k: p.y + half_scale.y,
                     material: empty_material.clone(),
                     rect_type: XZ,
                 }
@@ -75,6 +65,16 @@ impl AABox {
             )
             .boxed(),
         );
+        sides.push(
+            AARect {
+                xy0: p.xz() - half_scale.xz(),
+                xy1: p.xz() + half_scale.xz(),
+                k: p.y - half_scale.y,
+                material: empty_material.clone(),
+                rect_type: XZ,
+            }
+            .boxed(),
+        );
         sides.push(
             AARect {
                 xy0: p.yz() - half_scale.yz(),
@@ -109,9 +109,9 @@ impl AABox {
 }
 
 impl Geometry for AABox {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         if let Some(mut hit) = self.sides.hit(ray, t_min, t_max) {
-            hit.material = self.material.clone();
+            hit.material = &*self.material;
             Some(hit)
         } else {
             None
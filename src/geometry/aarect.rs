@@ -1,8 +1,8 @@
-use rand::{Rng, thread_rng};
 
-use crate::geometry::{Geometry, HitRecord, aabb::{AABB}};
+use crate::geometry::{Geometry, HitRecord, Primitive, aabb::{AABB}};
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vector::{Vec2, Vec3};
 
 #[derive(Clone)]
@@ -29,38 +29,8 @@ impl AARect {
 }
 
 impl Geometry for AARect {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
-        use AARectType::*;
-        let t = match &self.rect_type {
-            XY => (self.k - ray.origin.z) / ray.direction.z,
-            XZ => (self.k - ray.origin.y) / ray.direction.y,
-            YZ => (self.k - ray.origin.x) / ray.direction.x,
-        };
-        if t < tmin || t > tmax {
-            return None;
-        }
-        let xy = match &self.rect_type {
-            XY => ray.origin.xy() + t * ray.direction.xy(),
-            XZ => ray.origin.xz() + t * ray.direction.xz(),
-            YZ => ray.origin.yz() + t * ray.direction.yz(),
-        };
-        if xy.x < self.xy0.x || xy.x > self.xy1.x || xy.y < self.xy0.y || xy.y > self.xy1.y {
-            return None;
-        }
-        let uv = (xy - self.xy0).component_div(&(self.xy1 - self.xy0));
-        let p = ray.at(t);
-        let normal = match &self.rect_type {
-            XY => Vec3::new(0.0, 0.0, 1.0),
-            XZ => Vec3::new(0.0, -1.0, 0.0),
-            YZ => Vec3::new(1.0, 0.0, 0.0),
-        };
-        Some(HitRecord {
-            t,
-            p,
-            normal,
-            material: self.material.clone(),
-            uv
-        })
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        hit_rect(&self.rect_type, [self.xy0, self.xy1], self.k, &*self.material, ray, tmin, tmax)
     }
 
     fn aabb(&self) -> AABB {
@@ -74,8 +44,8 @@ impl Geometry for AARect {
         }
     }
 
-    fn pdf(&self, origin: &Vec3, direction: &Vec3) -> f32 {
-        let ray = &Ray::new(*origin, *direction, 0.0);
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        let ray = &Ray::new(*origin, *direction, 0.0, time);
         if let Some(hit) = &self.hit(ray, 0.001, f32::MAX) {
             let area = (self.xy1.x - self.xy0.x) * (self.xy1.y - self.xy0.y);
             let distance_squared = (ray.at(hit.t) - ray.origin).magnitude_squared();
@@ -84,13 +54,65 @@ impl Geometry for AARect {
         } else { 0.0 }        
     }
 
-    fn sample_direction(&self, origin: &Vec3) -> Vec3 {
+    fn sample_direction(&self, origin: &Vec3, _time: f32, sampler: &mut dyn Sampler) -> Vec3 {
         use AARectType::*;
-        let mut rng = thread_rng();
+        let r = sampler.next_2d();
+        let x = self.xy0.x + r.x * (self.xy1.x - self.xy0.x);
+        let y = self.xy0.y + r.y * (self.xy1.y - self.xy0.y);
         match &self.rect_type {
-            XY => Vec3::new(rng.gen_range(self.xy0.x, self.xy1.x), rng.gen_range(self.xy0.y, self.xy1.y), self.k) - origin,
-            XZ => Vec3::new(rng.gen_range(self.xy0.x, self.xy1.x), self.k, rng.gen_range(self.xy0.y, self.xy1.y)) - origin,
-            YZ => Vec3::new(self.k, rng.gen_range(self.xy0.x, self.xy1.x), rng.gen_range(self.xy0.y, self.xy1.y)) - origin,
+            XY => Vec3::new(x, y, self.k) - origin,
+            XZ => Vec3::new(x, self.k, y) - origin,
+            YZ => Vec3::new(self.k, x, y) - origin,
         }
     }
-}
\ No newline at end of file
+
+    fn primitive(&self) -> Option<Primitive<'_>> {
+        Some(Primitive::Rect(self))
+    }
+}
+
+/// Shared with the BVH leaves, which store rectangles without their box.
+/// `bounds` are the corners `xy0` and `xy1`.
+pub fn hit_rect<'a>(
+    rect_type: &AARectType,
+    [xy0, xy1]: [Vec2; 2],
+    k: f32,
+    material: &'a dyn Material,
+    ray: &Ray,
+    tmin: f32,
+    tmax: f32,
+) -> Option<HitRecord<'a>> {
+    use AARectType::*;
+    let t = match rect_type {
+        XY => (k - ray.origin.z) / ray.direction.z,
+        XZ => (k - ray.origin.y) / ray.direction.y,
+        YZ => (k - ray.origin.x) / ray.direction.x,
+    };
+    if t < tmin || t > tmax {
+        return None;
+    }
+    let xy = match rect_type {
+        XY => ray.origin.xy() + t * ray.direction.xy(),
+        XZ => ray.origin.xz() + t * ray.direction.xz(),
+        YZ => ray.origin.yz() + t * ray.direction.yz(),
+    };
+    if xy.x < xy0.x || xy.x > xy1.x || xy.y < xy0.y || xy.y > xy1.y {
+        return None;
+    }
+    let uv = (xy - xy0).component_div(&(xy1 - xy0));
+    let p = ray.at(t);
+    let normal = match rect_type {
+        XY => Vec3::new(0.0, 0.0, 1.0),
+        XZ => Vec3::new(0.0, -1.0, 0.0),
+        YZ => Vec3::new(1.0, 0.0, 0.0),
+    };
+    Some(HitRecord {
+        t,
+        p,
+        normal,
+        material,
+        uv,
+        object_id: 0,
+        material_id: 0,
+    })
+}
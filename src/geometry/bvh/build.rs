@@ -0,0 +1,204 @@
+use crate::geometry::{Geometry, aabb::{surrounding_box, AABB}};
+use crate::geometry::bvh::BvhOptions;
+use crate::vector::Vec3;
+
+/// Subtrees with fewer objects are built on the current thread.
+const PARALLEL_THRESHOLD: usize = 128;
+
+/// The tree made by the builder, converted into one of the BVH layouts.
+pub enum BuildNode {
+    /// Indices of the objects passed to `build_tree`.
+    Leaf { bbox: AABB, objects: Vec<usize> },
+    Interior {
+        bbox: AABB,
+        axis: usize,
+        left: Box<BuildNode>,
+        right: Box<BuildNode>,
+    },
+}
+
+impl BuildNode {
+    pub fn bbox(&self) -> AABB {
+        match self {
+            BuildNode::Leaf { bbox, .. } | BuildNode::Interior { bbox, .. } => *bbox,
+        }
+    }
+}
+
+/// An object with its bounds, computed once before building.
+struct Primitive {
+    index: usize,
+    bbox: AABB,
+    centroid: Vec3,
+}
+
+#[derive(Clone, Copy)]
+struct Bin {
+    bbox: AABB,
+    count: usize,
+}
+
+/// Objects with their centroid in a bin below `bin` go left.
+struct Split {
+    axis: usize,
+    bin: usize,
+    bins: usize,
+}
+
+impl Split {
+    fn is_left(&self, centroid: Vec3, centroid_bounds: &AABB) -> bool {
+        bin_index(centroid, centroid_bounds, self.axis, self.bins) < self.bin
+    }
+}
+
+/// Builds a tree with binned surface area heuristic splits, see Wald,
+/// "On fast Construction of SAH-based Bounding Volume Hierarchies", 2007.
+pub fn build_tree(objects: &[Box<dyn Geometry>], options: &BvhOptions) -> BuildNode {
+    assert!(!objects.is_empty(), "can not build a BVH without objects");
+    let primitives = objects
+        .iter()
+        .enumerate()
+        .map(|(index, object)| {
+            let bb = object.aabb();
+            // Some geometry returns boxes with min and max swapped on an axis.
+            let bbox = AABB {
+                min: bb.min.inf(&bb.max),
+                max: bb.min.sup(&bb.max),
+            };
+            Primitive {
+                index,
+                bbox,
+                centroid: bbox.centroid(),
+            }
+        })
+        .collect();
+    build_node(primitives, options)
+}
+
+fn build_node(mut primitives: Vec<Primitive>, options: &BvhOptions) -> BuildNode {
+    let count = primitives.len();
+    let bbox = primitives
+        .iter()
+        .fold(AABB::empty(), |bbox, p| surrounding_box(bbox, p.bbox));
+    let leaf = |primitives: Vec<Primitive>| BuildNode::Leaf {
+        bbox,
+        objects: primitives.iter().map(|p| p.index).collect(),
+    };
+    if count == 1 {
+        return leaf(primitives);
+    }
+
+    let centroid_bounds = primitives
+        .iter()
+        .fold(AABB::empty(), |bounds, p| bounds.grow(p.centroid));
+    let extent = centroid_bounds.max - centroid_bounds.min;
+    let axis = extent.imax();
+
+    let leaf_cost = count as f32 * options.intersection_cost;
+    let split = if extent[axis] > 0.0 {
+        find_split(&primitives, &bbox, &centroid_bounds, options)
+    } else {
+        None
+    };
+
+    let (axis, mut right) = match split {
+        Some((_, cost)) if count <= options.max_leaf_size && cost >= leaf_cost => {
+            return leaf(primitives);
+        }
+        Some((split, _)) => {
+            let (left, right): (Vec<Primitive>, Vec<Primitive>) = primitives
+                .into_iter()
+                .partition(|p| split.is_left(p.centroid, &centroid_bounds));
+            primitives = left;
+            (split.axis, right)
+        }
+        None if count <= options.max_leaf_size => return leaf(primitives),
+        // All centroids coincide, any split is as good as another.
+        None => {
+            let right = primitives.split_off(count / 2);
+            (axis, right)
+        }
+    };
+    if primitives.is_empty() || right.is_empty() {
+        let mut all = primitives;
+        all.append(&mut right);
+        all.sort_unstable_by(|a, b| a.centroid[axis].partial_cmp(&b.centroid[axis]).unwrap());
+        right = all.split_off(count / 2);
+        primitives = all;
+    }
+
+    let (left, right) = if count > PARALLEL_THRESHOLD {
+        rayon::join(
+            || build_node(primitives, options),
+            || build_node(right, options),
+        )
+    } else {
+        (build_node(primitives, options), build_node(right, options))
+    };
+    BuildNode::Interior {
+        bbox,
+        axis,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+/// The cheapest split between bins on any axis, with its cost.
+fn find_split(
+    primitives: &[Primitive],
+    bbox: &AABB,
+    centroid_bounds: &AABB,
+    options: &BvhOptions,
+) -> Option<(Split, f32)> {
+    let parent_area = bbox.surface_area().max(f32::MIN_POSITIVE);
+    let mut best: Option<(Split, f32)> = None;
+
+    for axis in 0..3 {
+        let extent = centroid_bounds.max[axis] - centroid_bounds.min[axis];
+        if extent <= 0.0 {
+            continue;
+        }
+        let mut bins = vec![Bin { bbox: AABB::empty(), count: 0 }; options.bins];
+        for p in primitives {
+            let bin = &mut bins[bin_index(p.centroid, centroid_bounds, axis, options.bins)];
+            bin.bbox = surrounding_box(bin.bbox, p.bbox);
+            bin.count += 1;
+        }
+
+        // Areas and counts of everything right of every boundary, then
+        // sweep from the left.
+        let mut right_area = vec![0.0; options.bins];
+        let mut right_count = vec![0; options.bins];
+        let mut right = Bin { bbox: AABB::empty(), count: 0 };
+        for i in (1..options.bins).rev() {
+            right.bbox = surrounding_box(right.bbox, bins[i].bbox);
+            right.count += bins[i].count;
+            right_area[i] = right.bbox.surface_area();
+            right_count[i] = right.count;
+        }
+        let mut left = Bin { bbox: AABB::empty(), count: 0 };
+        for i in 1..options.bins {
+            left.bbox = surrounding_box(left.bbox, bins[i - 1].bbox);
+            left.count += bins[i - 1].count;
+            if left.count == 0 || right_count[i] == 0 {
+                continue;
+            }
+            let cost = options.traversal_cost
+                + options.intersection_cost
+                    * (left.bbox.surface_area() * left.count as f32
+                        + right_area[i] * right_count[i] as f32)
+                    / parent_area;
+            if best.as_ref().is_none_or(|(_, best_cost)| cost < *best_cost) {
+                best = Some((Split { axis, bin: i, bins: options.bins }, cost));
+            }
+        }
+    }
+    best
+}
+
+fn bin_index(centroid: Vec3, centroid_bounds: &AABB, axis: usize, bins: usize) -> usize {
+    let min = centroid_bounds.min[axis];
+    let extent = centroid_bounds.max[axis] - min;
+    let bin = ((centroid[axis] - min) / extent * bins as f32) as usize;
+    bin.min(bins - 1)
+}
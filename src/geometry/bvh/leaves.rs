@@ -0,0 +1,94 @@
+use std::ops::Range;
+
+use crate::geometry::{
+    aarect::{hit_rect, AARectType},
+    sphere::hit_sphere,
+    Geometry, HitRecord, Primitive,
+};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector::{Vec2, Vec3};
+
+/// The objects of all leaves of a flattened BVH. Spheres and axis aligned
+/// rectangles, the bulk of most scenes, are kept by value as structures of
+/// arrays and intersected without a virtual call, other objects stay boxed.
+#[derive(Clone, Default)]
+pub struct Leaves {
+    leaves: Vec<Leaf>,
+    sphere_centers: Vec<Vec3>,
+    sphere_radii: Vec<f32>,
+    sphere_materials: Vec<Box<dyn Material>>,
+    rect_types: Vec<AARectType>,
+    rect_bounds: Vec<[Vec2; 2]>,
+    rect_ks: Vec<f32>,
+    rect_materials: Vec<Box<dyn Material>>,
+    others: Vec<Box<dyn Geometry>>,
+}
+
+/// Every kind of object of a leaf is contiguous in its arrays.
+#[derive(Clone)]
+struct Leaf {
+    spheres: Range<usize>,
+    rects: Range<usize>,
+    others: Range<usize>,
+}
+
+impl Leaves {
+    /// Adds the objects of a leaf and returns its index.
+    pub fn push(&mut self, objects: Vec<Box<dyn Geometry>>) -> u32 {
+        let (spheres, rects, others) = (self.sphere_radii.len(), self.rect_ks.len(), self.others.len());
+        for object in objects {
+            match object.primitive() {
+                Some(Primitive::Sphere(sphere)) => {
+                    self.sphere_centers.push(sphere.center);
+                    self.sphere_radii.push(sphere.radius);
+                    self.sphere_materials.push(sphere.material.clone());
+                }
+                Some(Primitive::Rect(rect)) => {
+                    self.rect_types.push(rect.rect_type.clone());
+                    self.rect_bounds.push([rect.xy0, rect.xy1]);
+                    self.rect_ks.push(rect.k);
+                    self.rect_materials.push(rect.material.clone());
+                }
+                None => self.others.push(object),
+            }
+        }
+        self.leaves.push(Leaf {
+            spheres: spheres..self.sphere_radii.len(),
+            rects: rects..self.rect_ks.len(),
+            others: others..self.others.len(),
+        });
+        (self.leaves.len() - 1) as u32
+    }
+
+    /// The closest hit with an object of the leaf.
+    pub fn hit(&self, leaf: u32, ray: &Ray, t_min: f32, mut t_max: f32) -> Option<HitRecord<'_>> {
+        let leaf = &self.leaves[leaf as usize];
+        let mut closest = None;
+        for i in leaf.spheres.clone() {
+            let material = &*self.sphere_materials[i];
+            if let Some(hit) =
+                hit_sphere(self.sphere_centers[i], self.sphere_radii[i], material, ray, t_min, t_max)
+            {
+                t_max = hit.t;
+                closest = Some(hit);
+            }
+        }
+        for i in leaf.rects.clone() {
+            let material = &*self.rect_materials[i];
+            if let Some(hit) =
+                hit_rect(&self.rect_types[i], self.rect_bounds[i], self.rect_ks[i], material, ray, t_min, t_max)
+            {
+                t_max = hit.t;
+                closest = Some(hit);
+            }
+        }
+        for object in &self.others[leaf.others.clone()] {
+            if let Some(hit) = object.hit(ray, t_min, t_max) {
+                t_max = hit.t;
+                closest = Some(hit);
+            }
+        }
+        closest
+    }
+}
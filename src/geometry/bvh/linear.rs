@@ -0,0 +1,109 @@
+use crate::geometry::{HitRecord, Geometry, aabb::AABB};
+use crate::geometry::bvh::{build::{build_tree, BuildNode}, leaves::Leaves, take_objects, BvhOptions};
+use crate::ray::Ray;
+use crate::vector::Vec3;
+use smallvec::SmallVec;
+
+/// A binary BVH stored in one array in depth first order, the left child of
+/// an interior node directly follows it. The objects are reordered so every
+/// leaf covers a contiguous range of them. See Pharr et al., "Physically
+/// Based Rendering", 4.3.4.
+/// Spheres and rectangles are stored by value in the leaves, see `Leaves`.
+#[derive(Clone)]
+pub struct LinearBvh {
+    nodes: Vec<LinearNode>,
+    leaves: Leaves,
+}
+
+/// 32 bytes, two nodes share a cache line.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct LinearNode {
+    bbox: AABB,
+    /// The index of a leaf, or the right child of an interior node.
+    offset: u32,
+    /// Number of objects in a leaf, zero for interior nodes.
+    count: u16,
+}
+
+impl LinearBvh {
+    pub fn build(objects: Vec<Box<dyn Geometry>>, options: &BvhOptions) -> Self {
+        assert!(options.max_leaf_size <= u16::MAX as usize);
+        let tree = build_tree(&objects, options);
+        let mut bvh = LinearBvh {
+            nodes: Vec::new(),
+            leaves: Leaves::default(),
+        };
+        let mut objects = objects.into_iter().map(Some).collect::<Vec<_>>();
+        bvh.flatten(tree, &mut objects);
+        bvh
+    }
+
+    fn flatten(&mut self, node: BuildNode, objects: &mut [Option<Box<dyn Geometry>>]) -> u32 {
+        let index = self.nodes.len();
+        match node {
+            BuildNode::Leaf { bbox, objects: indices } => {
+                self.nodes.push(LinearNode {
+                    bbox,
+                    offset: self.leaves.push(take_objects(&indices, objects)),
+                    count: indices.len() as u16,
+                });
+            }
+            BuildNode::Interior { bbox, left, right, .. } => {
+                self.nodes.push(LinearNode {
+                    bbox,
+                    offset: 0,
+                    count: 0,
+                });
+                self.flatten(*left, objects);
+                self.nodes[index].offset = self.flatten(*right, objects);
+            }
+        }
+        index as u32
+    }
+}
+
+impl Geometry for LinearBvh {
+    fn hit(&self, ray: &Ray, t_min: f32, mut t_max: f32) -> Option<HitRecord<'_>> {
+        let inv_direction = Vec3::repeat(1.0).component_div(&ray.direction);
+        let enter = |index: u32, t_max: f32| {
+            self.nodes[index as usize]
+                .bbox
+                .hit_inverse(&ray.origin, &inv_direction, t_min, t_max)
+                .map(|t_near| (index, t_near))
+        };
+        let mut closest = None;
+        // Nodes whose box the ray enters, with the entry distance.
+        let mut stack: SmallVec<[(u32, f32); 64]> = SmallVec::new();
+        stack.extend(enter(0, t_max));
+        while let Some((index, t_near)) = stack.pop() {
+            if t_near > t_max {
+                continue;
+            }
+            let node = &self.nodes[index as usize];
+            if node.count > 0 {
+                if let Some(hit) = self.leaves.hit(node.offset, ray, t_min, t_max) {
+                    t_max = hit.t;
+                    closest = Some(hit);
+                }
+                continue;
+            }
+            // The nearer child is pushed last so it is popped first.
+            match (enter(index + 1, t_max), enter(node.offset, t_max)) {
+                (Some(left), Some(right)) if left.1 <= right.1 => {
+                    stack.extend_from_slice(&[right, left])
+                }
+                (Some(left), Some(right)) => stack.extend_from_slice(&[left, right]),
+                (left, right) => stack.extend(left.or(right)),
+            }
+        }
+        closest
+    }
+
+    fn aabb(&self) -> AABB {
+        self.nodes[0].bbox
+    }
+    fn is_inside(&self, point: Vec3, _time: f32) -> bool {
+        self.aabb().is_inside(point)
+    }
+}
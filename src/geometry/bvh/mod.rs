@@ -0,0 +1,115 @@
+mod build;
+mod leaves;
+pub mod linear;
+pub mod qbvh;
+
+pub use linear::LinearBvh;
+pub use qbvh::Qbvh;
+
+use crate::geometry::{HitRecord, Geometry, HittableList, aabb::AABB};
+use crate::ray::Ray;
+use crate::vector::Vec3;
+use build::{build_tree, BuildNode};
+
+/// Settings of the surface area heuristic builder shared by all BVH
+/// layouts. The costs are relative, only their ratio matters.
+#[derive(Clone, Copy, Debug)]
+pub struct BvhOptions {
+    /// Nodes with more objects are always split.
+    pub max_leaf_size: usize,
+    /// Number of centroid bins per axis that split candidates are taken from.
+    pub bins: usize,
+    pub traversal_cost: f32,
+    pub intersection_cost: f32,
+}
+
+impl Default for BvhOptions {
+    fn default() -> Self {
+        BvhOptions {
+            max_leaf_size: 4,
+            bins: 16,
+            traversal_cost: 0.125,
+            intersection_cost: 1.0,
+        }
+    }
+}
+
+/// A tree of boxed nodes. `LinearBvh` and `Qbvh` are faster to traverse,
+/// this one can be nested freely as it is a plain `Geometry`.
+#[derive(Clone)]
+pub struct BVHNode {
+    left: Box<dyn Geometry>,
+    right: Box<dyn Geometry>,
+    bbox: AABB,
+    /// The split axis, the child on the side the ray comes from is tested
+    /// first so the second one can often be culled by the closer hit.
+    axis: usize,
+}
+
+impl BVHNode {
+    pub fn build(objects: Vec<Box<dyn Geometry>>, options: &BvhOptions) -> Box<dyn Geometry> {
+        let tree = build_tree(&objects, options);
+        let mut objects = objects.into_iter().map(Some).collect::<Vec<_>>();
+        Self::from_build_node(tree, &mut objects)
+    }
+
+    fn from_build_node(
+        node: BuildNode,
+        objects: &mut [Option<Box<dyn Geometry>>],
+    ) -> Box<dyn Geometry> {
+        match node {
+            BuildNode::Leaf { objects: indices, .. } => {
+                let mut leaf = take_objects(&indices, objects);
+                if leaf.len() == 1 {
+                    leaf.remove(0)
+                } else {
+                    Box::new(HittableList { objects: leaf })
+                }
+            }
+            BuildNode::Interior { bbox, axis, left, right } => Box::new(BVHNode {
+                left: Self::from_build_node(*left, objects),
+                right: Self::from_build_node(*right, objects),
+                bbox,
+                axis,
+            }),
+        }
+    }
+}
+
+/// Moves the objects of a leaf out of the builder input.
+fn take_objects(
+    indices: &[usize],
+    objects: &mut [Option<Box<dyn Geometry>>],
+) -> Vec<Box<dyn Geometry>> {
+    indices
+        .iter()
+        .map(|i| objects[*i].take().expect("object in two leaves"))
+        .collect()
+}
+
+impl Geometry for BVHNode {
+    fn hit(&self, ray: &Ray, t_min: f32, mut t_max: f32) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(ray, t_min, t_max) {
+            return None;
+        }
+        let (near, far) = if ray.direction[self.axis] < 0.0 {
+            (&self.right, &self.left)
+        } else {
+            (&self.left, &self.right)
+        };
+
+        let near_hit = near.hit(ray, t_min, t_max);
+        if let Some(h) = &near_hit {
+            t_max = h.t;
+        }
+        // Only returns hits closer than the near one.
+        far.hit(ray, t_min, t_max).or(near_hit)
+    }
+
+    fn aabb(&self) -> AABB {
+        self.bbox
+    }
+    fn is_inside(&self, point: Vec3, _time: f32) -> bool {
+        self.bbox.is_inside(point)
+    }
+}
@@ -0,0 +1,216 @@
+use crate::geometry::{HitRecord, Geometry, aabb::AABB};
+use crate::geometry::bvh::{build::{build_tree, BuildNode}, leaves::Leaves, take_objects, BvhOptions};
+use crate::ray::Ray;
+use crate::vector::Vec3;
+use smallvec::SmallVec;
+
+/// Marks a child slot that is not used.
+const EMPTY: u32 = u32::MAX;
+
+/// A BVH with four children per node, whose boxes are tested against a ray
+/// at once with SIMD. Made by pulling the grandchildren of the binary tree
+/// up into their parent, see Dammertz et al., "Shallow Bounding Volume
+/// Hierarchies for Fast SIMD Ray Tracing of Incoherent Rays", 2008.
+/// Spheres and rectangles are stored by value in the leaves, see `Leaves`.
+#[derive(Clone)]
+pub struct Qbvh {
+    nodes: Vec<QbvhNode>,
+    leaves: Leaves,
+    bbox: AABB,
+}
+
+/// The child boxes are stored as a structure of arrays, one lane per child.
+#[derive(Clone, Copy)]
+#[repr(C, align(16))]
+struct QbvhNode {
+    min: [[f32; 4]; 3],
+    max: [[f32; 4]; 3],
+    /// A node index, or a leaf index when the count is set.
+    children: [u32; 4],
+    counts: [u16; 4],
+    /// Bit `i` is set when child `i` is used.
+    lanes: u8,
+}
+
+impl QbvhNode {
+    fn empty() -> Self {
+        QbvhNode {
+            min: [[0.0; 4]; 3],
+            max: [[0.0; 4]; 3],
+            children: [EMPTY; 4],
+            counts: [0; 4],
+            lanes: 0,
+        }
+    }
+
+    /// Returns a bit mask of the children the ray enters, and the distance
+    /// at which it enters them.
+    #[cfg(target_arch = "x86_64")]
+    fn intersect(
+        &self,
+        origin: &Vec3,
+        inv_direction: &Vec3,
+        t_min: f32,
+        t_max: f32,
+    ) -> (u32, [f32; 4]) {
+        use std::arch::x86_64::*;
+        // SSE is part of every x86_64 target.
+        unsafe {
+            let mut t_near = _mm_set1_ps(t_min);
+            let mut t_far = _mm_set1_ps(t_max);
+            for axis in 0..3 {
+                let o = _mm_set1_ps(origin[axis]);
+                let inv_d = _mm_set1_ps(inv_direction[axis]);
+                let t0 = _mm_mul_ps(_mm_sub_ps(_mm_loadu_ps(self.min[axis].as_ptr()), o), inv_d);
+                let t1 = _mm_mul_ps(_mm_sub_ps(_mm_loadu_ps(self.max[axis].as_ptr()), o), inv_d);
+                // The second operand is returned for NaN, which a ray parallel
+                // to and in the plane of a slab gives.
+                t_near = _mm_max_ps(_mm_min_ps(t0, t1), t_near);
+                t_far = _mm_min_ps(_mm_max_ps(t0, t1), t_far);
+            }
+            let mask = _mm_movemask_ps(_mm_cmple_ps(t_near, t_far)) as u32;
+            let mut near = [0.0; 4];
+            _mm_storeu_ps(near.as_mut_ptr(), t_near);
+            (mask & self.lanes as u32, near)
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn intersect(
+        &self,
+        origin: &Vec3,
+        inv_direction: &Vec3,
+        t_min: f32,
+        t_max: f32,
+    ) -> (u32, [f32; 4]) {
+        let mut mask = 0;
+        let mut near = [0.0; 4];
+        for lane in 0..4 {
+            let mut t_near = t_min;
+            let mut t_far = t_max;
+            for axis in 0..3 {
+                let t0 = (self.min[axis][lane] - origin[axis]) * inv_direction[axis];
+                let t1 = (self.max[axis][lane] - origin[axis]) * inv_direction[axis];
+                t_near = t0.min(t1).max(t_near);
+                t_far = t0.max(t1).min(t_far);
+            }
+            if t_near <= t_far {
+                mask |= 1 << lane;
+            }
+            near[lane] = t_near;
+        }
+        (mask & self.lanes as u32, near)
+    }
+}
+
+impl Qbvh {
+    pub fn build(objects: Vec<Box<dyn Geometry>>, options: &BvhOptions) -> Self {
+        assert!(options.max_leaf_size <= u16::MAX as usize);
+        let tree = build_tree(&objects, options);
+        let mut bvh = Qbvh {
+            nodes: Vec::new(),
+            leaves: Leaves::default(),
+            bbox: tree.bbox(),
+        };
+        let mut objects = objects.into_iter().map(Some).collect::<Vec<_>>();
+        bvh.flatten(vec![tree], &mut objects);
+        bvh
+    }
+
+    /// Adds a node for up to four subtrees, the interior ones with the
+    /// largest surface area are opened up while there is room.
+    fn flatten(
+        &mut self,
+        mut children: Vec<BuildNode>,
+        objects: &mut [Option<Box<dyn Geometry>>],
+    ) -> u32 {
+        while children.len() < 4 {
+            let largest = children
+                .iter()
+                .enumerate()
+                .filter(|(_, child)| matches!(child, BuildNode::Interior { .. }))
+                .max_by(|(_, a), (_, b)| {
+                    a.bbox().surface_area().partial_cmp(&b.bbox().surface_area()).unwrap()
+                })
+                .map(|(i, _)| i);
+            match largest.map(|i| children.swap_remove(i)) {
+                Some(BuildNode::Interior { left, right, .. }) => {
+                    children.push(*left);
+                    children.push(*right);
+                }
+                _ => break,
+            }
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(QbvhNode::empty());
+        for (lane, child) in children.into_iter().enumerate() {
+            let bbox = child.bbox();
+            let (child_index, count) = match child {
+                BuildNode::Leaf { objects: indices, .. } => {
+                    let leaf = self.leaves.push(take_objects(&indices, objects));
+                    (leaf, indices.len() as u16)
+                }
+                interior => (self.flatten(vec![interior], objects), 0),
+            };
+            let node = &mut self.nodes[index];
+            for axis in 0..3 {
+                node.min[axis][lane] = bbox.min[axis];
+                node.max[axis][lane] = bbox.max[axis];
+            }
+            node.children[lane] = child_index;
+            node.counts[lane] = count;
+            node.lanes |= 1 << lane;
+        }
+        index as u32
+    }
+}
+
+impl Geometry for Qbvh {
+    fn hit(&self, ray: &Ray, t_min: f32, mut t_max: f32) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(ray, t_min, t_max) {
+            return None;
+        }
+        let inv_direction = Vec3::repeat(1.0).component_div(&ray.direction);
+        let mut closest = None;
+        // Node or leaf, object count and entry distance.
+        let mut stack: SmallVec<[(u32, u16, f32); 64]> = smallvec![(0, 0, t_min)];
+        while let Some((index, count, t_near)) = stack.pop() {
+            if t_near > t_max {
+                continue;
+            }
+            if count > 0 {
+                if let Some(hit) = self.leaves.hit(index, ray, t_min, t_max) {
+                    t_max = hit.t;
+                    closest = Some(hit);
+                }
+                continue;
+            }
+
+            let node = &self.nodes[index as usize];
+            let (mask, near) = node.intersect(&ray.origin, &inv_direction, t_min, t_max);
+            // Pushes the children far to near, so the nearest is popped first.
+            let mut hits = [(0, 0, 0.0); 4];
+            let mut count = 0;
+            for lane in (0..4).filter(|lane| mask & (1 << lane) != 0) {
+                let child = (node.children[lane], node.counts[lane], near[lane]);
+                let mut i = count;
+                while i > 0 && hits[i - 1].2 < child.2 {
+                    hits[i] = hits[i - 1];
+                    i -= 1;
+                }
+                hits[i] = child;
+                count += 1;
+            }
+            stack.extend_from_slice(&hits[..count]);
+        }
+        closest
+    }
+
+    fn aabb(&self) -> AABB {
+        self.bbox
+    }
+    fn is_inside(&self, point: Vec3, _time: f32) -> bool {
+        self.bbox.is_inside(point)
+    }
+}
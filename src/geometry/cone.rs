@@ -0,0 +1,99 @@
+use std::f32::consts::PI;
+
+use crate::geometry::{
+    area_pdf,
+    disk::disk_aabb,
+    solve_quadratic, Geometry, HitRecord,
+    aabb::{surrounding_box, AABB},
+};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vector::{Vec2, Vec3};
+
+/// The open side of a cone standing on `center` along y, narrowing from
+/// `radius` to `top_radius`. A top radius of zero makes a pointed cone,
+/// anything else a truncated one such as a lamp shade.
+#[derive(Clone)]
+pub struct Cone {
+    pub center: Vec3,
+    pub radius: f32,
+    pub top_radius: f32,
+    pub height: f32,
+    pub material: Box<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(center: Vec3, radius: f32, height: f32, material: Box<dyn Material>) -> Self {
+        Cone { center, radius, top_radius: 0.0, height, material }
+    }
+
+    /// Change of radius per unit of height.
+    fn slope(&self) -> f32 {
+        (self.top_radius - self.radius) / self.height
+    }
+
+    pub fn area(&self) -> f32 {
+        let slant = ((self.radius - self.top_radius).powi(2) + self.height * self.height).sqrt();
+        PI * (self.radius + self.top_radius) * slant
+    }
+
+    pub fn sample_point(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let r = sampler.next_2d();
+        // The circumference grows linearly with the radius, so the squared
+        // radius is uniform over the area.
+        let (r0, r1) = (self.radius, self.top_radius);
+        let radius = (r0 * r0 + r.x * (r1 * r1 - r0 * r0)).sqrt();
+        let y = if (r1 - r0).abs() > 1.0e-6 {
+            (radius - r0) / (r1 - r0) * self.height
+        } else {
+            r.x * self.height
+        };
+        let phi = 2.0 * PI * r.y;
+        self.center + Vec3::new(radius * phi.cos(), y, radius * phi.sin())
+    }
+}
+
+impl Geometry for Cone {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let o = ray.origin - self.center;
+        let d = ray.direction;
+        let k = self.slope();
+        // x² + z² = (radius + k y)²
+        let radius_at_origin = self.radius + k * o.y;
+        let a = d.x * d.x + d.z * d.z - k * k * d.y * d.y;
+        let half_b = o.x * d.x + o.z * d.z - k * d.y * radius_at_origin;
+        let c = o.x * o.x + o.z * o.z - radius_at_origin * radius_at_origin;
+        let (t0, t1) = solve_quadratic(a, half_b, c)?;
+
+        for &t in &[t0, t1] {
+            let p = o + t * d;
+            if t > tmin && t < tmax && p.y >= 0.0 && p.y <= self.height {
+                let radius = self.radius + k * p.y;
+                let normal = Vec3::new(p.x, -k * radius, p.z).normalize();
+                let u = (p.z.atan2(p.x) + PI) / (2.0 * PI);
+                return Some(HitRecord {
+                    t,
+                    p: ray.at(t),
+                    normal,
+                    material: &*self.material,
+                    uv: Vec2::new(u, p.y / self.height),
+                    object_id: 0,
+                    material_id: 0,
+                });
+            }
+        }
+        None
+    }
+
+    fn aabb(&self) -> AABB {
+        let top = self.center + Vec3::new(0.0, self.height, 0.0);
+        surrounding_box(disk_aabb(self.center, self.radius), disk_aabb(top, self.top_radius))
+    }
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        area_pdf(self, self.area(), origin, direction, time)
+    }
+    fn sample_direction(&self, origin: &Vec3, _time: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        self.sample_point(sampler) - origin
+    }
+}
@@ -0,0 +1,189 @@
+use crate::geometry::{Geometry, HitRecord, aabb::{surrounding_box, AABB}};
+use crate::ray::Ray;
+use crate::vector::Vec3;
+
+/// Distance stepped past a surface before looking for the next one along
+/// the ray.
+const EPSILON: f32 = 1.0e-4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// The left child with the right one cut away.
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// Constructive solid geometry over two closed children with outward
+/// normals, which can be `Csg` nodes themselves. The ray is followed from
+/// surface to surface of both children, and whether it is inside each is
+/// read from the normals, until it crosses the boundary of the result.
+/// Every surface keeps the material of its child.
+#[derive(Clone)]
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub left: Box<dyn Geometry>,
+    pub right: Box<dyn Geometry>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Box<dyn Geometry>, right: Box<dyn Geometry>) -> Self {
+        Csg { operation, left, right }
+    }
+
+    pub fn union(left: Box<dyn Geometry>, right: Box<dyn Geometry>) -> Self {
+        Self::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: Box<dyn Geometry>, right: Box<dyn Geometry>) -> Self {
+        Self::new(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference(left: Box<dyn Geometry>, right: Box<dyn Geometry>) -> Self {
+        Self::new(CsgOperation::Difference, left, right)
+    }
+
+    pub fn boxed(self) -> Box<dyn Geometry> {
+        Box::from(self)
+    }
+}
+
+/// A closed surface is left at a hit where the normal faces along the ray.
+fn exits(hit: &HitRecord, direction: &Vec3) -> bool {
+    hit.normal.dot(direction) > 0.0
+}
+
+impl Geometry for Csg {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        // Children are searched beyond `tmax`, an exit past it still tells
+        // that the ray starts inside.
+        let mut left_hit = self.left.hit(ray, tmin, f32::MAX);
+        let mut right_hit = self.right.hit(ray, tmin, f32::MAX);
+        let mut in_left = left_hit.as_ref().is_some_and(|hit| exits(hit, &ray.direction));
+        let mut in_right = right_hit.as_ref().is_some_and(|hit| exits(hit, &ray.direction));
+        // `t` is in units of the direction, which need not be normalised.
+        let step = EPSILON / ray.direction.magnitude();
+
+        loop {
+            let left_is_closer = match (&left_hit, &right_hit) {
+                (Some(left), Some(right)) => left.t <= right.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None,
+            };
+            let was_inside = self.operation.contains(in_left, in_right);
+            let hit = if left_is_closer {
+                let hit = left_hit.take().unwrap();
+                in_left = !exits(&hit, &ray.direction);
+                left_hit = self.left.hit(ray, hit.t + step, f32::MAX);
+                hit
+            } else {
+                let hit = right_hit.take().unwrap();
+                in_right = !exits(&hit, &ray.direction);
+                right_hit = self.right.hit(ray, hit.t + step, f32::MAX);
+                hit
+            };
+            if hit.t >= tmax {
+                return None;
+            }
+            if self.operation.contains(in_left, in_right) != was_inside {
+                let mut hit = hit;
+                // The cut away part faces the other way on the result.
+                if self.operation == CsgOperation::Difference && !left_is_closer {
+                    hit.normal = -hit.normal;
+                }
+                return Some(hit);
+            }
+        }
+    }
+
+    fn aabb(&self) -> AABB {
+        let (left, right) = (self.left.aabb(), self.right.aabb());
+        match self.operation {
+            CsgOperation::Union => surrounding_box(left, right),
+            CsgOperation::Intersection => AABB {
+                min: left.min.sup(&right.min),
+                max: left.max.inf(&right.max),
+            },
+            CsgOperation::Difference => left,
+        }
+    }
+
+    fn is_inside(&self, point: Vec3, time: f32) -> bool {
+        self.operation
+            .contains(self.left.is_inside(point, time), self.right.is_inside(point, time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::sphere::Sphere;
+    use crate::material::{color::Reflectance, lambertian::Lambertian};
+
+    /// Unit spheres centred at z = -0.5 and z = 0.5.
+    fn spheres(operation: CsgOperation) -> Csg {
+        let sphere = |z: f32| -> Box<dyn Geometry> {
+            Box::new(Sphere {
+                center: Vec3::new(0.0, 0.0, z),
+                radius: 1.0,
+                material: Box::new(Lambertian { reflectance: Reflectance::Uniform(0.5) }),
+            })
+        };
+        Csg::new(operation, sphere(-0.5), sphere(0.5))
+    }
+
+    /// The entry and exit `t` of a ray along z from z = -5, with a direction
+    /// of length two, checking that the normals face out of the result.
+    fn entry_and_exit(csg: &Csg) -> (f32, f32) {
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 2.0), 550.0, 0.0);
+        let entry = csg.hit(&ray, 0.0, f32::MAX).unwrap();
+        assert!(entry.normal.dot(&ray.direction) < 0.0);
+        let exit = csg.hit(&ray, entry.t + 1.0e-3, f32::MAX).unwrap();
+        assert!(exit.normal.dot(&ray.direction) > 0.0);
+        assert!(csg.hit(&ray, exit.t + 1.0e-3, f32::MAX).is_none());
+        (entry.t, exit.t)
+    }
+
+    fn assert_close(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1.0e-4 && (actual.1 - expected.1).abs() < 1.0e-4,
+            "{:?} instead of {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn union_skips_the_inner_surfaces() {
+        assert_close(entry_and_exit(&spheres(CsgOperation::Union)), (1.75, 3.25));
+    }
+
+    #[test]
+    fn intersection_is_the_overlap() {
+        assert_close(entry_and_exit(&spheres(CsgOperation::Intersection)), (2.25, 2.75));
+    }
+
+    #[test]
+    fn difference_exits_through_the_cut() {
+        assert_close(entry_and_exit(&spheres(CsgOperation::Difference)), (1.75, 2.25));
+    }
+
+    #[test]
+    fn rays_past_the_overlap_miss_the_intersection() {
+        let csg = spheres(CsgOperation::Intersection);
+        let ray = Ray::new(Vec3::new(0.0, 0.95, -5.0), Vec3::new(0.0, 0.0, 1.0), 550.0, 0.0);
+        assert!(csg.hit(&ray, 0.0, f32::MAX).is_none());
+        assert!(spheres(CsgOperation::Union).hit(&ray, 0.0, f32::MAX).is_some());
+    }
+}
@@ -0,0 +1,119 @@
+use std::f32::consts::PI;
+
+use crate::geometry::{
+    area_pdf,
+    disk::{disk_aabb, hit_disk, sample_disk},
+    solve_quadratic, Geometry, HitRecord,
+    aabb::{surrounding_box, AABB},
+};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vector::{Vec2, Vec3};
+
+/// A cylinder standing on `center` along y. Closed cylinders have a cap at
+/// both ends, open ones are tubes.
+#[derive(Clone)]
+pub struct Cylinder {
+    pub center: Vec3,
+    pub radius: f32,
+    pub height: f32,
+    pub closed: bool,
+    pub material: Box<dyn Material>,
+}
+
+impl Cylinder {
+    fn side_area(&self) -> f32 {
+        2.0 * PI * self.radius * self.height
+    }
+
+    fn cap_area(&self) -> f32 {
+        PI * self.radius * self.radius
+    }
+
+    pub fn area(&self) -> f32 {
+        if self.closed {
+            self.side_area() + 2.0 * self.cap_area()
+        } else {
+            self.side_area()
+        }
+    }
+
+    pub fn sample_point(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let top = self.center + Vec3::new(0.0, self.height, 0.0);
+        if self.closed {
+            // Picks the side or a cap by their area.
+            let choice = sampler.next_f32() * self.area();
+            if choice < self.cap_area() {
+                return sample_disk(self.center, self.radius, 0.0, sampler);
+            } else if choice < 2.0 * self.cap_area() {
+                return sample_disk(top, self.radius, 0.0, sampler);
+            }
+        }
+        let r = sampler.next_2d();
+        let phi = 2.0 * PI * r.x;
+        self.center + Vec3::new(self.radius * phi.cos(), r.y * self.height, self.radius * phi.sin())
+    }
+}
+
+impl Geometry for Cylinder {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let o = ray.origin - self.center;
+        let d = ray.direction;
+        let a = d.x * d.x + d.z * d.z;
+        let half_b = o.x * d.x + o.z * d.z;
+        let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+
+        let mut closest: Option<(f32, Vec3, Vec2)> = None;
+        let mut tmax = tmax;
+        if let Some((t0, t1)) = solve_quadratic(a, half_b, c) {
+            for &t in &[t0, t1] {
+                let y = o.y + t * d.y;
+                if t > tmin && t < tmax && y >= 0.0 && y <= self.height {
+                    let p = o + t * d;
+                    let normal = Vec3::new(p.x, 0.0, p.z) / self.radius;
+                    let u = (p.z.atan2(p.x) + PI) / (2.0 * PI);
+                    closest = Some((t, normal, Vec2::new(u, y / self.height)));
+                    tmax = t;
+                    break;
+                }
+            }
+        }
+        if self.closed {
+            let top = self.center + Vec3::new(0.0, self.height, 0.0);
+            let caps = [(self.center, -1.0), (top, 1.0)];
+            for &(cap, side) in &caps {
+                if let Some((t, uv)) = hit_disk(cap, self.radius, 0.0, ray, tmin, tmax) {
+                    closest = Some((t, Vec3::new(0.0, side, 0.0), uv));
+                    tmax = t;
+                }
+            }
+        }
+
+        let (t, normal, uv) = closest?;
+        Some(HitRecord {
+            t,
+            p: ray.at(t),
+            normal,
+            material: &*self.material,
+            uv,
+            object_id: 0,
+            material_id: 0,
+        })
+    }
+
+    fn aabb(&self) -> AABB {
+        let top = self.center + Vec3::new(0.0, self.height, 0.0);
+        surrounding_box(disk_aabb(self.center, self.radius), disk_aabb(top, self.radius))
+    }
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        area_pdf(self, self.area(), origin, direction, time)
+    }
+    fn sample_direction(&self, origin: &Vec3, _time: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        self.sample_point(sampler) - origin
+    }
+    fn is_inside(&self, point: Vec3, _time: f32) -> bool {
+        let p = point - self.center;
+        self.closed && p.y > 0.0 && p.y < self.height && p.x * p.x + p.z * p.z < self.radius * self.radius
+    }
+}
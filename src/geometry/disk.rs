@@ -0,0 +1,88 @@
+use std::f32::consts::PI;
+
+use crate::geometry::{area_pdf, Geometry, HitRecord, aabb::AABB};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vector::{Vec2, Vec3};
+
+/// A disk facing up along y, with a hole of `inner_radius` for rings. Use a
+/// `Transform` to orient it.
+#[derive(Clone)]
+pub struct Disk {
+    pub center: Vec3,
+    pub radius: f32,
+    pub inner_radius: f32,
+    pub material: Box<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, radius: f32, material: Box<dyn Material>) -> Self {
+        Disk { center, radius, inner_radius: 0.0, material }
+    }
+
+    pub fn area(&self) -> f32 {
+        PI * (self.radius * self.radius - self.inner_radius * self.inner_radius)
+    }
+
+    pub fn sample_point(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        sample_disk(self.center, self.radius, self.inner_radius, sampler)
+    }
+}
+
+impl Geometry for Disk {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let (t, uv) = hit_disk(self.center, self.radius, self.inner_radius, ray, tmin, tmax)?;
+        Some(HitRecord {
+            t,
+            p: ray.at(t),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            material: &*self.material,
+            uv,
+            object_id: 0,
+            material_id: 0,
+        })
+    }
+
+    fn aabb(&self) -> AABB {
+        disk_aabb(self.center, self.radius)
+    }
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        area_pdf(self, self.area(), origin, direction, time)
+    }
+    fn sample_direction(&self, origin: &Vec3, _time: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        self.sample_point(sampler) - origin
+    }
+}
+
+/// The distance to the plane of a disk facing along y and the uv of the
+/// hit, u around the centre and v from the rim inwards.
+pub fn hit_disk(center: Vec3, radius: f32, inner_radius: f32, ray: &Ray, tmin: f32, tmax: f32) -> Option<(f32, Vec2)> {
+    let t = (center.y - ray.origin.y) / ray.direction.y;
+    if !(t > tmin && t < tmax) {
+        return None;
+    }
+    let offset = ray.at(t) - center;
+    let distance_squared = offset.x * offset.x + offset.z * offset.z;
+    if distance_squared > radius * radius || distance_squared < inner_radius * inner_radius {
+        return None;
+    }
+    let phi = offset.z.atan2(offset.x);
+    let u = (phi + PI) / (2.0 * PI);
+    let v = (radius - distance_squared.sqrt()) / (radius - inner_radius);
+    Some((t, Vec2::new(u, v)))
+}
+
+pub fn disk_aabb(center: Vec3, radius: f32) -> AABB {
+    let half_size = Vec3::new(radius, 0.0001, radius);
+    AABB { min: center - half_size, max: center + half_size }
+}
+
+/// A point uniformly distributed over the area of the ring.
+pub fn sample_disk(center: Vec3, radius: f32, inner_radius: f32, sampler: &mut dyn Sampler) -> Vec3 {
+    let r = sampler.next_2d();
+    let inner_squared = inner_radius * inner_radius;
+    let distance = (inner_squared + r.x * (radius * radius - inner_squared)).sqrt();
+    let phi = 2.0 * PI * r.y;
+    center + Vec3::new(distance * phi.cos(), 0.0, distance * phi.sin())
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use crate::geometry::{aabb::AABB, transform::Affine, Geometry, HitRecord};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vector::{Mat4, Vec3};
+
+/// A placed copy of shared geometry, usually a prebuilt BVH. Every copy
+/// only holds the `Arc` and its transform, so thousands of copies of one
+/// mesh take little memory. A BVH built over the instances makes a two
+/// level BVH.
+#[derive(Clone)]
+pub struct Instance {
+    pub object: Arc<dyn Geometry>,
+    pub transform: Affine,
+    bbox: AABB,
+}
+
+impl Instance {
+    /// `to_world` has to be an invertible affine transform.
+    pub fn new(object: Arc<dyn Geometry>, to_world: Mat4) -> Self {
+        let bbox = object.aabb().transformed(&to_world);
+        Instance {
+            object,
+            transform: Affine::new(to_world),
+            bbox,
+        }
+    }
+}
+
+impl Geometry for Instance {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let object_ray = self.transform.ray_to_object(ray);
+        let hit = self.object.hit(&object_ray, tmin, tmax)?;
+        Some(self.transform.hit_to_world(hit))
+    }
+
+    fn aabb(&self) -> AABB {
+        self.bbox
+    }
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        self.transform.pdf(&*self.object, origin, direction, time)
+    }
+    fn sample_direction(&self, origin: &Vec3, time: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        self.transform.sample_direction(&*self.object, origin, time, sampler)
+    }
+    fn is_inside(&self, point: Vec3, time: f32) -> bool {
+        self.object.is_inside(self.transform.point_to_object(&point), time)
+    }
+}
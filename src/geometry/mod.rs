@@ -4,7 +4,16 @@ pub mod aabb;
 pub mod aarect;
 pub mod aabox;
 pub mod transform;
+pub mod instance;
 pub mod volume;
+pub mod disk;
+pub mod cylinder;
+pub mod cone;
+pub mod paraboloid;
+pub mod torus;
+pub mod quad;
+pub mod csg;
+pub mod sdf;
 
 use dyn_clone::DynClone;
 
@@ -13,28 +22,92 @@ use crate::vector::{Vec2, Vec3};
 use crate::material::Material;
 use crate::geometry::aabb::{AABB, surrounding_box};
 use crate::pdf::{Pdf, MixturePdf, GeometryPdf};
+use crate::sampler::Sampler;
 
 pub trait Geometry: Sync + Send + DynClone{
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>>;
     fn aabb(&self) -> AABB;
-    fn pdf(&self, _origin: &Vec3, _direction: &Vec3) -> f32 {
+    /// Solid angle density of `sample_direction` at `time`, for moving
+    /// geometry.
+    fn pdf(&self, _origin: &Vec3, _direction: &Vec3, _time: f32) -> f32 {
         0.0
     }
-    fn sample_direction(&self, _origin: &Vec3) -> Vec3 {
+    fn sample_direction(&self, _origin: &Vec3, _time: f32, _sampler: &mut dyn Sampler) -> Vec3 {
         Vec3::new(1.0, 0.0, 0.0)
     }
-    fn is_inside(&self, point: Vec3) -> bool {
+    /// At `time`, for moving geometry.
+    fn is_inside(&self, _point: Vec3, _time: f32) -> bool {
         false
     }
+    fn object_id(&self) -> u32 {
+        0
+    }
+    /// The plain shape behind this geometry, which the flattened BVHs store
+    /// by value in their leaves.
+    fn primitive(&self) -> Option<Primitive<'_>> {
+        None
+    }
 }
 dyn_clone::clone_trait_object!(Geometry);
 
-pub struct HitRecord {
+pub enum Primitive<'a> {
+    Sphere(&'a sphere::Sphere),
+    Rect(&'a aarect::AARect),
+}
+
+/// Solid angle density of the direction towards a point sampled uniformly
+/// over the `area` of `geometry`, for area lights. Points hidden behind the
+/// first hit are sampled as well, so the densities of every point of the
+/// surface along the direction add up.
+pub fn area_pdf(geometry: &dyn Geometry, area: f32, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+    let ray = Ray::new(*origin, *direction, 0.0, time);
+    let length = direction.magnitude();
+    let mut pdf = 0.0;
+    let mut tmin = 0.001;
+    while let Some(hit) = geometry.hit(&ray, tmin, f32::MAX) {
+        let distance_squared = (hit.p - origin).magnitude_squared();
+        let cosine = (direction.dot(&hit.normal) / length).abs();
+        pdf += distance_squared / (cosine * area);
+        // Steps a fixed distance past the hit, whatever the ray's length.
+        let next = hit.t + 1.0e-4 / length;
+        if next <= tmin {
+            break;
+        }
+        tmin = next;
+    }
+    pdf
+}
+
+/// Roots of `a t² + 2 half_b t + c`, the smaller first.
+pub fn solve_quadratic(a: f32, half_b: f32, c: f32) -> Option<(f32, f32)> {
+    if a.abs() < 1.0e-12 {
+        if half_b == 0.0 {
+            return None;
+        }
+        let t = -c / (2.0 * half_b);
+        return Some((t, t));
+    }
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    // Avoids the cancellation of -half_b ± root.
+    let q = -(half_b + half_b.signum() * discriminant.sqrt());
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+/// Borrows the material of the geometry that was hit, so taking a hit
+/// does not allocate.
+pub struct HitRecord<'a> {
     pub t: f32,
     pub p: Vec3,
     pub normal: Vec3,
-    pub material: Box<dyn Material>,
-    pub uv: Vec2
+    pub material: &'a dyn Material,
+    pub uv: Vec2,
+    /// Set by `Tagged`, zero for untagged geometry.
+    pub object_id: u32,
+    pub material_id: u32,
 }
 
 #[derive(Default, Clone)]
@@ -46,9 +119,9 @@ impl HittableList {
     pub fn push(&mut self, geom: Box<dyn Geometry>) {
         self.objects.push(geom);
     }
-    pub fn generate_mixture_pdf(&self, hit_position: Vec3) -> MixturePdf<Vec3> {
+    pub fn generate_mixture_pdf(&self, hit_position: Vec3, time: f32) -> MixturePdf<'_, Vec3> {
         let pdfs = self.objects.iter().map(|object| {
-            let geom_pdf: Box<dyn Pdf<Vec3>> = Box::new(GeometryPdf { origin: hit_position, geometry: object });
+            let geom_pdf: Box<dyn Pdf<Vec3>> = Box::new(GeometryPdf { origin: hit_position, time, geometry: &**object });
             geom_pdf
         }).collect::<Vec<Box<dyn Pdf<Vec3>>>>();
         MixturePdf::new_uniform(pdfs)
@@ -56,8 +129,8 @@ impl HittableList {
 }
 
 impl Geometry for HittableList {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
-        let mut hit_closest: Option<HitRecord> = None;
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let mut hit_closest: Option<HitRecord<'_>> = None;
         let mut closest_so_far = tmax;
         for hittable_obj in self.objects.iter() {
             if let Some(hit) = hittable_obj.hit(ray, tmin, closest_so_far) {
@@ -65,7 +138,7 @@ impl Geometry for HittableList {
                 hit_closest = Some(hit);
             }
         }
-        return hit_closest;
+        hit_closest
     }
     fn aabb(&self) -> AABB {
         if !&self.objects.is_empty() {
@@ -83,7 +156,7 @@ impl Geometry for HittableList {
             AABB::zero()
         }
     }
-    fn is_inside(&self, point: Vec3) -> bool {
+    fn is_inside(&self, point: Vec3, _time: f32) -> bool {
         self.aabb().is_inside(point)
     }
 }
@@ -105,7 +178,7 @@ impl FlipNormals {
 }
 
 impl Geometry for FlipNormals {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
         if let Some(mut hit_rec) = self.object.hit(ray, tmin, tmax) {
             hit_rec.normal = -hit_rec.normal;
             Some(hit_rec)
@@ -116,4 +189,53 @@ impl Geometry for FlipNormals {
     fn aabb(&self) -> AABB {
         self.object.aabb()
     }
+}
+
+/// Stamps an object and material id onto every hit of the wrapped geometry,
+/// these end up in the id output layer.
+#[derive(Clone)]
+pub struct Tagged {
+    pub object: Box<dyn Geometry>,
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+impl Tagged {
+    pub fn new(object: Box<dyn Geometry>, object_id: u32, material_id: u32) -> Self {
+        Self {
+            object,
+            object_id,
+            material_id,
+        }
+    }
+    pub fn boxed(self) -> Box<Self> {
+        Box::from(self)
+    }
+}
+
+impl Geometry for Tagged {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        if let Some(mut hit_rec) = self.object.hit(ray, tmin, tmax) {
+            hit_rec.object_id = self.object_id;
+            hit_rec.material_id = self.material_id;
+            Some(hit_rec)
+        } else {
+            None
+        }
+    }
+    fn aabb(&self) -> AABB {
+        self.object.aabb()
+    }
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        self.object.pdf(origin, direction, time)
+    }
+    fn sample_direction(&self, origin: &Vec3, time: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        self.object.sample_direction(origin, time, sampler)
+    }
+    fn is_inside(&self, point: Vec3, time: f32) -> bool {
+        self.object.is_inside(point, time)
+    }
+    fn object_id(&self) -> u32 {
+        self.object_id
+    }
 }
\ No newline at end of file
@@ -0,0 +1,92 @@
+use std::f32::consts::PI;
+
+use crate::geometry::{area_pdf, disk::disk_aabb, solve_quadratic, Geometry, HitRecord, aabb::AABB};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vector::{Vec2, Vec3};
+
+/// An open paraboloid with its vertex on `center`, opening up along y to
+/// `radius` at `height`, like a reflector dish.
+#[derive(Clone)]
+pub struct Paraboloid {
+    pub center: Vec3,
+    pub radius: f32,
+    pub height: f32,
+    pub material: Box<dyn Material>,
+}
+
+impl Paraboloid {
+    /// y = curvature · (x² + z²)
+    fn curvature(&self) -> f32 {
+        self.height / (self.radius * self.radius)
+    }
+
+    /// From the squared slope of the surface, 4 k² ρ² at distance ρ.
+    fn alpha(&self) -> f32 {
+        4.0 * self.curvature() * self.curvature()
+    }
+
+    pub fn area(&self) -> f32 {
+        let alpha = self.alpha();
+        2.0 * PI * ((1.0 + alpha * self.radius * self.radius).powf(1.5) - 1.0) / (3.0 * alpha)
+    }
+
+    pub fn sample_point(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let r = sampler.next_2d();
+        // Inverts the area within a distance from the axis.
+        let alpha = self.alpha();
+        let rim = (1.0 + alpha * self.radius * self.radius).powf(1.5);
+        let distance_squared = ((1.0 + r.x * (rim - 1.0)).powf(2.0 / 3.0) - 1.0) / alpha;
+        let distance = distance_squared.max(0.0).sqrt();
+        let phi = 2.0 * PI * r.y;
+        self.center
+            + Vec3::new(
+                distance * phi.cos(),
+                self.curvature() * distance_squared,
+                distance * phi.sin(),
+            )
+    }
+}
+
+impl Geometry for Paraboloid {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let o = ray.origin - self.center;
+        let d = ray.direction;
+        let k = self.curvature();
+        let a = k * (d.x * d.x + d.z * d.z);
+        let half_b = k * (o.x * d.x + o.z * d.z) - 0.5 * d.y;
+        let c = k * (o.x * o.x + o.z * o.z) - o.y;
+        let (t0, t1) = solve_quadratic(a, half_b, c)?;
+
+        for &t in &[t0, t1] {
+            let p = o + t * d;
+            if t > tmin && t < tmax && p.y <= self.height {
+                // Faces away from the focus.
+                let normal = Vec3::new(2.0 * k * p.x, -1.0, 2.0 * k * p.z).normalize();
+                let u = (p.z.atan2(p.x) + PI) / (2.0 * PI);
+                return Some(HitRecord {
+                    t,
+                    p: ray.at(t),
+                    normal,
+                    material: &*self.material,
+                    uv: Vec2::new(u, p.y / self.height),
+                    object_id: 0,
+                    material_id: 0,
+                });
+            }
+        }
+        None
+    }
+
+    fn aabb(&self) -> AABB {
+        let top = disk_aabb(self.center + Vec3::new(0.0, self.height, 0.0), self.radius);
+        AABB { min: Vec3::new(top.min.x, self.center.y, top.min.z), max: top.max }
+    }
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        area_pdf(self, self.area(), origin, direction, time)
+    }
+    fn sample_direction(&self, origin: &Vec3, _time: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        self.sample_point(sampler) - origin
+    }
+}
@@ -0,0 +1,192 @@
+use std::f32::consts::PI;
+
+use crate::geometry::{area_pdf, Geometry, HitRecord, aabb::AABB};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vector::{Vec2, Vec3};
+
+/// Below this solid angle sampling the spherical triangles loses precision,
+/// the area is sampled instead.
+const MIN_SOLID_ANGLE: f32 = 3.0e-4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuadSampling {
+    /// Points uniform over the area, cheap but noisy for large or close
+    /// lights.
+    Area,
+    /// Directions uniform over the solid angle the quad covers.
+    SolidAngle,
+}
+
+/// A parallelogram spanned by the edges `u` and `v` from `corner`, in any
+/// orientation. The normal is `u × v`.
+#[derive(Clone)]
+pub struct Quad {
+    pub corner: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Box<dyn Material>,
+    pub sampling: QuadSampling,
+}
+
+impl Quad {
+    pub fn new(corner: Vec3, u: Vec3, v: Vec3, material: Box<dyn Material>) -> Self {
+        Quad { corner, u, v, material, sampling: QuadSampling::SolidAngle }
+    }
+
+    pub fn boxed(self) -> Box<dyn Geometry> {
+        Box::from(self)
+    }
+
+    pub fn area(&self) -> f32 {
+        self.u.cross(&self.v).magnitude()
+    }
+
+    pub fn sample_point(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let r = sampler.next_2d();
+        self.corner + r.x * self.u + r.y * self.v
+    }
+
+    /// The two triangles of the quad as seen from `origin`, unit vectors to
+    /// their corners with their solid angles.
+    fn spherical_triangles(&self, origin: &Vec3) -> [([Vec3; 3], f32); 2] {
+        let corners = [
+            self.corner,
+            self.corner + self.u,
+            self.corner + self.u + self.v,
+            self.corner + self.v,
+        ]
+        .iter()
+        .map(|corner| (corner - origin).normalize())
+        .collect::<Vec<Vec3>>();
+        let first = [corners[0], corners[1], corners[2]];
+        let second = [corners[0], corners[2], corners[3]];
+        [
+            (first, triangle_solid_angle(&first)),
+            (second, triangle_solid_angle(&second)),
+        ]
+    }
+}
+
+impl Geometry for Quad {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let n = self.u.cross(&self.v);
+        let denominator = n.dot(&ray.direction);
+        if denominator.abs() < 1.0e-8 {
+            return None;
+        }
+        let t = n.dot(&(self.corner - ray.origin)) / denominator;
+        if !(t > tmin && t < tmax) {
+            return None;
+        }
+        let p = ray.at(t);
+        // Coordinates of the hit along the edges.
+        let offset = p - self.corner;
+        let w = n / n.magnitude_squared();
+        let alpha = w.dot(&offset.cross(&self.v));
+        let beta = w.dot(&self.u.cross(&offset));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some(HitRecord {
+            t,
+            p,
+            normal: n.normalize(),
+            material: &*self.material,
+            uv: Vec2::new(alpha, beta),
+            object_id: 0,
+            material_id: 0,
+        })
+    }
+
+    fn aabb(&self) -> AABB {
+        let corners = [self.corner + self.u, self.corner + self.v, self.corner + self.u + self.v];
+        let (min, max) = corners
+            .iter()
+            .fold((self.corner, self.corner), |(min, max), c| (min.inf(c), max.sup(c)));
+        let padding = Vec3::repeat(0.0001);
+        AABB { min: min - padding, max: max + padding }
+    }
+
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        if self.sampling == QuadSampling::SolidAngle {
+            let [(_, first), (_, second)] = self.spherical_triangles(origin);
+            let solid_angle = first + second;
+            if solid_angle > MIN_SOLID_ANGLE {
+                let ray = Ray::new(*origin, *direction, 0.0, time);
+                return match self.hit(&ray, 0.001, f32::MAX) {
+                    Some(_) => 1.0 / solid_angle,
+                    None => 0.0,
+                };
+            }
+        }
+        area_pdf(self, self.area(), origin, direction, time)
+    }
+
+    fn sample_direction(&self, origin: &Vec3, _time: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        if self.sampling == QuadSampling::SolidAngle {
+            let [(first, first_angle), (second, second_angle)] = self.spherical_triangles(origin);
+            let solid_angle = first_angle + second_angle;
+            if solid_angle > MIN_SOLID_ANGLE {
+                let triangle = if sampler.next_f32() * solid_angle < first_angle {
+                    &first
+                } else {
+                    &second
+                };
+                return sample_spherical_triangle(triangle, sampler.next_2d());
+            }
+        }
+        self.sample_point(sampler) - origin
+    }
+}
+
+/// After Van Oosterom and Strackee, "The Solid Angle of a Plane Triangle",
+/// 1983.
+fn triangle_solid_angle([a, b, c]: &[Vec3; 3]) -> f32 {
+    let numerator = a.dot(&b.cross(c)).abs();
+    let denominator = 1.0 + a.dot(b) + b.dot(c) + c.dot(a);
+    2.0 * numerator.atan2(denominator)
+}
+
+/// A direction uniform over the solid angle of the spherical triangle,
+/// after Arvo, "Stratified Sampling of Spherical Triangles", 1995.
+fn sample_spherical_triangle([a, b, c]: &[Vec3; 3], r: Vec2) -> Vec3 {
+    let (n_ab, n_bc, n_ca) = (a.cross(b), b.cross(c), c.cross(a));
+    if n_ab.magnitude_squared() == 0.0 || n_bc.magnitude_squared() == 0.0 || n_ca.magnitude_squared() == 0.0 {
+        return *a;
+    }
+    let (n_ab, n_bc, n_ca) = (n_ab.normalize(), n_bc.normalize(), n_ca.normalize());
+    // Interior angles of the triangle at a, b and c.
+    let alpha = angle_between(&n_ab, &-n_ca);
+    let beta = angle_between(&n_bc, &-n_ab);
+    let gamma = angle_between(&n_ca, &-n_bc);
+
+    // Picks the sub-triangle with the sampled fraction of the area, which
+    // fixes the point c' on the edge ac.
+    let area_pi = PI + r.x * (alpha + beta + gamma - PI);
+    let (sin_alpha, cos_alpha) = alpha.sin_cos();
+    let sin_phi = area_pi.sin() * cos_alpha - area_pi.cos() * sin_alpha;
+    let cos_phi = area_pi.cos() * cos_alpha + area_pi.sin() * sin_alpha;
+    let k1 = cos_phi + cos_alpha;
+    let k2 = sin_phi - sin_alpha * a.dot(b);
+    let cos_b = ((k2 + (k2 * cos_phi - k1 * sin_phi) * cos_alpha)
+        / ((k2 * sin_phi + k1 * cos_phi) * sin_alpha))
+        .clamp(-1.0, 1.0);
+    let sin_b = (1.0 - cos_b * cos_b).max(0.0).sqrt();
+    let c_prime = cos_b * a + sin_b * (c - c.dot(a) * a).normalize();
+
+    // Then a point on the arc from b to c'.
+    let cos_theta = 1.0 - r.y * (1.0 - c_prime.dot(b));
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    cos_theta * b + sin_theta * (c_prime - c_prime.dot(b) * b).normalize()
+}
+
+/// Numerically stable angle between two unit vectors.
+fn angle_between(v1: &Vec3, v2: &Vec3) -> f32 {
+    if v1.dot(v2) < 0.0 {
+        PI - 2.0 * ((v1 + v2).magnitude() / 2.0).min(1.0).asin()
+    } else {
+        2.0 * ((v2 - v1).magnitude() / 2.0).min(1.0).asin()
+    }
+}
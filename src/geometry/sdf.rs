@@ -0,0 +1,355 @@
+use dyn_clone::DynClone;
+
+use crate::geometry::{Geometry, HitRecord, aabb::AABB};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector::{Vec2, Vec3};
+
+/// A signed distance field, negative inside. Fields that are not exact
+/// distances should never overestimate them, or the sphere tracing steps
+/// through the surface.
+pub trait Sdf: Sync + Send + DynClone {
+    fn distance(&self, point: Vec3) -> f32;
+}
+dyn_clone::clone_trait_object!(Sdf);
+
+impl<F: Fn(Vec3) -> f32 + Sync + Send + Clone> Sdf for F {
+    fn distance(&self, point: Vec3) -> f32 {
+        self(point)
+    }
+}
+
+/// Composable distance fields, built with the methods below, e.g.
+/// `SdfExpr::sphere(1.0).smooth_union(SdfExpr::cuboid(Vec3::repeat(0.5)), 0.2).twist(1.0)`.
+#[derive(Clone, Debug)]
+pub enum SdfExpr {
+    Sphere { radius: f32 },
+    /// Centred on the origin.
+    Cuboid { half_size: Vec3 },
+    /// In the xz plane.
+    Torus { major_radius: f32, minor_radius: f32 },
+    Translate { offset: Vec3, expr: Box<SdfExpr> },
+    /// Uniform, `factor` is above zero.
+    Scale { factor: f32, expr: Box<SdfExpr> },
+    Union(Box<SdfExpr>, Box<SdfExpr>),
+    Intersection(Box<SdfExpr>, Box<SdfExpr>),
+    Difference(Box<SdfExpr>, Box<SdfExpr>),
+    /// Blends the surfaces within `k` of each other, `k` is above zero.
+    SmoothUnion { a: Box<SdfExpr>, b: Box<SdfExpr>, k: f32 },
+    /// Repeats space with the given period along every axis, a period of
+    /// zero leaves the axis alone.
+    Repeat { period: Vec3, expr: Box<SdfExpr> },
+    /// Turns space around y by `rate` radians per unit of height. Not an
+    /// exact distance, use a `step_scale` below one.
+    Twist { rate: f32, expr: Box<SdfExpr> },
+}
+
+impl SdfExpr {
+    pub fn sphere(radius: f32) -> Self {
+        SdfExpr::Sphere { radius }
+    }
+    pub fn cuboid(half_size: Vec3) -> Self {
+        SdfExpr::Cuboid { half_size }
+    }
+    pub fn torus(major_radius: f32, minor_radius: f32) -> Self {
+        SdfExpr::Torus { major_radius, minor_radius }
+    }
+    pub fn translate(self, offset: Vec3) -> Self {
+        SdfExpr::Translate { offset, expr: Box::new(self) }
+    }
+    pub fn scale(self, factor: f32) -> Self {
+        assert!(factor > 0.0, "scale factor {} is not above zero", factor);
+        SdfExpr::Scale { factor, expr: Box::new(self) }
+    }
+    pub fn union(self, other: SdfExpr) -> Self {
+        SdfExpr::Union(Box::new(self), Box::new(other))
+    }
+    pub fn intersection(self, other: SdfExpr) -> Self {
+        SdfExpr::Intersection(Box::new(self), Box::new(other))
+    }
+    pub fn difference(self, other: SdfExpr) -> Self {
+        SdfExpr::Difference(Box::new(self), Box::new(other))
+    }
+    pub fn smooth_union(self, other: SdfExpr, k: f32) -> Self {
+        assert!(k > 0.0, "smoothing distance {} is not above zero", k);
+        SdfExpr::SmoothUnion { a: Box::new(self), b: Box::new(other), k }
+    }
+    pub fn repeat(self, period: Vec3) -> Self {
+        SdfExpr::Repeat { period, expr: Box::new(self) }
+    }
+    pub fn twist(self, rate: f32) -> Self {
+        SdfExpr::Twist { rate, expr: Box::new(self) }
+    }
+}
+
+impl Sdf for SdfExpr {
+    fn distance(&self, p: Vec3) -> f32 {
+        use SdfExpr::*;
+        match self {
+            Sphere { radius } => p.magnitude() - radius,
+            Cuboid { half_size } => {
+                let q = p.abs() - half_size;
+                q.sup(&Vec3::zeros()).magnitude() + q.max().min(0.0)
+            }
+            Torus { major_radius, minor_radius } => {
+                let ring = Vec2::new(p.xz().magnitude() - major_radius, p.y);
+                ring.magnitude() - minor_radius
+            }
+            Translate { offset, expr } => expr.distance(p - offset),
+            Scale { factor, expr } => expr.distance(p / *factor) * factor,
+            Union(a, b) => a.distance(p).min(b.distance(p)),
+            Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Difference(a, b) => a.distance(p).max(-b.distance(p)),
+            SmoothUnion { a, b, k } => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+                b + (a - b) * h - k * h * (1.0 - h)
+            }
+            Repeat { period, expr } => {
+                let q = p.zip_map(period, |x, period| {
+                    if period > 0.0 {
+                        x - period * (x / period).round()
+                    } else {
+                        x
+                    }
+                });
+                expr.distance(q)
+            }
+            Twist { rate, expr } => {
+                let (sin, cos) = (rate * p.y).sin_cos();
+                expr.distance(Vec3::new(cos * p.x + sin * p.z, p.y, -sin * p.x + cos * p.z))
+            }
+        }
+    }
+}
+
+/// Distance estimate of the Mandelbulb of the given `power`, 8 for the
+/// classic one, after Quilez.
+pub fn mandelbulb(p: Vec3, power: f32, iterations: u32) -> f32 {
+    let mut z = p;
+    let mut dr = 1.0;
+    let mut r = z.magnitude();
+    for _ in 0..iterations {
+        if r > 2.0 {
+            break;
+        }
+        let theta = (z.y / r).acos() * power;
+        let phi = z.z.atan2(z.x) * power;
+        dr = power * r.powf(power - 1.0) * dr + 1.0;
+        let zr = r.powf(power);
+        z = zr * Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()) + p;
+        r = z.magnitude();
+    }
+    0.5 * r.ln() * r / dr
+}
+
+/// A surface given by a distance field, intersected by sphere tracing
+/// inside `bbox`. The field has to be below zero inside and the box has to
+/// hold the whole surface, repeated fields are cut off by it.
+#[derive(Clone)]
+pub struct SdfGeometry {
+    pub sdf: Box<dyn Sdf>,
+    pub bbox: AABB,
+    pub material: Box<dyn Material>,
+    pub max_steps: u32,
+    /// Distance at which the surface counts as hit.
+    pub epsilon: f32,
+    /// Fraction of the distance stepped, below one for fields that
+    /// overestimate.
+    pub step_scale: f32,
+}
+
+impl SdfGeometry {
+    pub fn new(sdf: impl Sdf + 'static, bbox: AABB, material: Box<dyn Material>) -> Self {
+        SdfGeometry {
+            sdf: Box::new(sdf),
+            bbox,
+            material,
+            max_steps: 256,
+            epsilon: 1.0e-4,
+            step_scale: 1.0,
+        }
+    }
+
+    /// The gradient from four samples on a tetrahedron.
+    fn normal(&self, p: Vec3) -> Vec3 {
+        let h = self.epsilon;
+        let k = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ];
+        k.iter()
+            .map(|k| k * self.sdf.distance(p + k * h))
+            .fold(Vec3::zeros(), |sum, v| sum + v)
+            .normalize()
+    }
+}
+
+impl Geometry for SdfGeometry {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let length = ray.direction.magnitude();
+        let direction = ray.direction / length;
+        let inv_direction = Vec3::repeat(1.0).component_div(&direction);
+        // Marches in units of distance, `t` of the ray is scaled back at the end.
+        let start = self
+            .bbox
+            .hit_inverse(&ray.origin, &inv_direction, tmin * length, tmax * length)?;
+        let end = (0..3)
+            .map(|axis| {
+                let t0 = (self.bbox.min[axis] - ray.origin[axis]) * inv_direction[axis];
+                let t1 = (self.bbox.max[axis] - ray.origin[axis]) * inv_direction[axis];
+                t0.max(t1)
+            })
+            .fold(tmax * length, f32::min);
+
+        // Rays leaving the surface start within `epsilon` of it, where it
+        // would be hit again. They step out of that band first and then
+        // march on the side they end up on, the inside for refraction.
+        // Rays entering the box start at its edge instead, where a surface
+        // touching the box is a hit.
+        let leaving = start == tmin * length;
+        let mut side = 0.0;
+        let mut t = start;
+        for _ in 0..self.max_steps {
+            if t > end {
+                return None;
+            }
+            let p = ray.origin + t * direction;
+            let field = self.sdf.distance(p);
+            if side == 0.0 {
+                if leaving && field.abs() < self.epsilon {
+                    t += self.epsilon;
+                    continue;
+                }
+                side = field.signum();
+            }
+            let distance = side * field;
+            if distance < self.epsilon {
+                return Some(HitRecord {
+                    t: t / length,
+                    p,
+                    normal: self.normal(p),
+                    material: &*self.material,
+                    // Distance fields have no parameterisation.
+                    uv: Vec2::zeros(),
+                    object_id: 0,
+                    material_id: 0,
+                });
+            }
+            t += distance * self.step_scale;
+        }
+        None
+    }
+
+    fn aabb(&self) -> AABB {
+        self.bbox
+    }
+    fn is_inside(&self, point: Vec3, _time: f32) -> bool {
+        let in_box = point.zip_fold(&self.bbox.min, true, |acc, a, b| acc & (a >= b))
+            & point.zip_fold(&self.bbox.max, true, |acc, a, b| acc & (a <= b));
+        in_box && self.sdf.distance(point) < 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{color::Reflectance, lambertian::Lambertian};
+
+    fn assert_distance(expr: &SdfExpr, point: Vec3, expected: f32) {
+        let distance = expr.distance(point);
+        assert!((distance - expected).abs() < 1.0e-5, "{:?} at {} is {} instead of {}", expr, point, distance, expected);
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let sphere = SdfExpr::sphere(1.0);
+        assert_distance(&sphere, Vec3::new(3.0, 0.0, 0.0), 2.0);
+        assert_distance(&sphere, Vec3::zeros(), -1.0);
+        let cuboid = SdfExpr::cuboid(Vec3::new(1.0, 2.0, 3.0));
+        assert_distance(&cuboid, Vec3::new(2.0, 0.0, 0.0), 1.0);
+        assert_distance(&cuboid, Vec3::new(2.0, 3.0, 0.0), 2.0f32.sqrt());
+        assert_distance(&cuboid, Vec3::new(0.5, 0.0, 0.0), -0.5);
+        let torus = SdfExpr::torus(2.0, 0.5);
+        assert_distance(&torus, Vec3::new(2.0, 0.0, 0.0), -0.5);
+        assert_distance(&torus, Vec3::new(0.0, 0.0, 0.0), 1.5);
+    }
+
+    #[test]
+    fn transformed_distances() {
+        let moved = SdfExpr::sphere(1.0).translate(Vec3::new(0.0, 2.0, 0.0));
+        assert_distance(&moved, Vec3::new(0.0, 5.0, 0.0), 2.0);
+        // Scaling scales the distances too, so they stay exact.
+        let scaled = SdfExpr::sphere(1.0).scale(3.0);
+        assert_distance(&scaled, Vec3::new(5.0, 0.0, 0.0), 2.0);
+        assert_distance(&scaled, Vec3::zeros(), -3.0);
+        let repeated = SdfExpr::sphere(0.5).repeat(Vec3::new(4.0, 0.0, 0.0));
+        assert_distance(&repeated, Vec3::new(8.0, 1.0, 0.0), 0.5);
+        assert_distance(&repeated, Vec3::new(0.0, 0.0, 4.0), 3.5);
+    }
+
+    #[test]
+    fn boolean_distances() {
+        // Unit spheres at x = -0.5 and x = 0.5.
+        let a = || SdfExpr::sphere(1.0).translate(Vec3::new(-0.5, 0.0, 0.0));
+        let b = || SdfExpr::sphere(1.0).translate(Vec3::new(0.5, 0.0, 0.0));
+        let union = a().union(b());
+        assert_distance(&union, Vec3::new(3.0, 0.0, 0.0), 1.5);
+        assert_distance(&union, Vec3::new(-3.0, 0.0, 0.0), 1.5);
+        let intersection = a().intersection(b());
+        assert_distance(&intersection, Vec3::new(3.0, 0.0, 0.0), 2.5);
+        assert_distance(&intersection, Vec3::zeros(), -0.5);
+        let difference = a().difference(b());
+        assert_distance(&difference, Vec3::new(-3.0, 0.0, 0.0), 1.5);
+        // Inside the cut away sphere, half a unit from its surface.
+        assert_distance(&difference, Vec3::new(0.0, 0.0, 0.0), 0.5);
+        assert_distance(&difference, Vec3::new(-1.0, 0.0, 0.0), -0.5);
+    }
+
+    #[test]
+    fn smooth_union_blends_only_near_both_surfaces() {
+        let a = || SdfExpr::sphere(1.0).translate(Vec3::new(-2.0, 0.0, 0.0));
+        let b = || SdfExpr::sphere(1.0).translate(Vec3::new(2.0, 0.0, 0.0));
+        let smooth = a().smooth_union(b(), 0.5);
+        // Far from the other sphere it is the plain union.
+        assert_distance(&smooth, Vec3::new(-4.0, 0.0, 0.0), 1.0);
+        // Halfway both are one away, the blend takes off k / 4.
+        assert_distance(&smooth, Vec3::zeros(), 1.0 - 0.125);
+    }
+
+    #[test]
+    #[should_panic]
+    fn smooth_union_needs_a_positive_k() {
+        SdfExpr::sphere(1.0).smooth_union(SdfExpr::sphere(2.0), 0.0);
+    }
+
+    fn unit_sphere() -> SdfGeometry {
+        SdfGeometry::new(
+            SdfExpr::sphere(1.0),
+            AABB { min: Vec3::repeat(-1.0), max: Vec3::repeat(1.0) },
+            Box::new(Lambertian { reflectance: Reflectance::Uniform(0.5) }),
+        )
+    }
+
+    #[test]
+    fn surface_touching_the_box_is_hit() {
+        let ray = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0), 550.0, 0.0);
+        let sphere = unit_sphere();
+        let hit = sphere.hit(&ray, 0.001, f32::MAX).unwrap();
+        assert!((hit.t - 2.0).abs() < 1.0e-3, "hit at {}", hit.t);
+        assert!(hit.normal.x < 0.0);
+    }
+
+    #[test]
+    fn rays_leaving_the_surface_do_not_hit_it_again() {
+        let sphere = unit_sphere();
+        let outward = Ray::new(Vec3::new(0.0, 0.6, 0.8), Vec3::new(0.0, 0.6, 0.8), 550.0, 0.0);
+        assert!(sphere.hit(&outward, 0.0, f32::MAX).is_none());
+        // Refracted into the sphere, it is hit on the far side.
+        let inward = Ray::new(Vec3::new(0.0, 0.6, 0.8), Vec3::new(0.0, -0.6, -0.8), 550.0, 0.0);
+        let hit = sphere.hit(&inward, 0.0, f32::MAX).unwrap();
+        assert!((hit.t - 2.0).abs() < 1.0e-3, "hit at {}", hit.t);
+    }
+}
@@ -1,9 +1,10 @@
 use std::f32::consts::PI;
 
-use crate::geometry::{Geometry, HitRecord, aabb::{AABB}};
+use crate::geometry::{Geometry, HitRecord, Primitive, aabb::{AABB, surrounding_box}};
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vector::{Vec2, Vec3, onb_local, random_to_sphere, clamp};
+use crate::sampler::Sampler;
 
 #[derive(Clone)]
 pub struct Sphere {
@@ -13,68 +14,131 @@ pub struct Sphere {
 }
 
 impl Geometry for Sphere {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
-        let oc = ray.origin - self.center;
-        let a = ray.direction.magnitude_squared();
-        let half_b = oc.dot(&ray.direction);
-        let c = oc.magnitude_squared() - self.radius * self.radius;
-        let discriminant = half_b * half_b - a * c;
-        if discriminant > 0.0 {
-            let root = discriminant.sqrt();
-            let mut t = (-half_b - root) / a;
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center, self.radius, &*self.material, ray, tmin, tmax)
+    }
+
+    fn aabb(&self) -> AABB {
+        sphere_aabb(self.center, self.radius)
+    }
+
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        let ray = Ray::new(*origin, *direction, 0.0, time);
+        if self.hit(&ray, 0.001, f32::MAX).is_some() {
+            sphere_pdf(self.center, self.radius, origin)
+        } else { 0.0 }
+    }
+    fn sample_direction(&self, origin: &Vec3, _time: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        sample_sphere(self.center, self.radius, origin, sampler)
+    }
+    fn is_inside(&self, point: Vec3, _time: f32) -> bool {
+        (self.center - point).magnitude() < (self.radius - 0.001)
+    }
+    fn primitive(&self) -> Option<Primitive<'_>> {
+        Some(Primitive::Sphere(self))
+    }
+}
+
+/// A sphere moving in a straight line from `center0` at `time0` to
+/// `center1` at `time1`, it stays put before and after. With equal times
+/// it jumps at that time.
+#[derive(Clone)]
+pub struct MovingSphere {
+    pub center0: Vec3,
+    pub center1: Vec3,
+    pub time0: f32,
+    pub time1: f32,
+    pub radius: f32,
+    pub material: Box<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn center(&self, time: f32) -> Vec3 {
+        if self.time1 <= self.time0 {
+            return if time < self.time0 { self.center0 } else { self.center1 };
+        }
+        let s = clamp((time - self.time0) / (self.time1 - self.time0));
+        self.center0.lerp(&self.center1, s)
+    }
+}
+
+impl Geometry for MovingSphere {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center(ray.time), self.radius, &*self.material, ray, tmin, tmax)
+    }
+
+    fn aabb(&self) -> AABB {
+        surrounding_box(
+            sphere_aabb(self.center0, self.radius),
+            sphere_aabb(self.center1, self.radius),
+        )
+    }
+
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        let ray = Ray::new(*origin, *direction, 0.0, time);
+        if self.hit(&ray, 0.001, f32::MAX).is_some() {
+            sphere_pdf(self.center(time), self.radius, origin)
+        } else { 0.0 }
+    }
+    fn sample_direction(&self, origin: &Vec3, time: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        sample_sphere(self.center(time), self.radius, origin, sampler)
+    }
+    fn is_inside(&self, point: Vec3, time: f32) -> bool {
+        (self.center(time) - point).magnitude() < (self.radius - 0.001)
+    }
+}
+
+pub fn hit_sphere<'a>(
+    center: Vec3,
+    radius: f32,
+    material: &'a dyn Material,
+    ray: &Ray,
+    tmin: f32,
+    tmax: f32,
+) -> Option<HitRecord<'a>> {
+    let oc = ray.origin - center;
+    let a = ray.direction.magnitude_squared();
+    let half_b = oc.dot(&ray.direction);
+    let c = oc.magnitude_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant > 0.0 {
+        let root = discriminant.sqrt();
+        for &t in &[(-half_b - root) / a, (-half_b + root) / a] {
             if t < tmax && t > tmin {
                 let p = ray.at(t);
-                let outward_normal = (p - self.center) / self.radius;
+                let outward_normal = (p - center) / radius;
                 let uv = get_sphere_uv(outward_normal);
                 return Some(HitRecord {
                     t,
                     p,
                     normal: outward_normal,
-                    material: self.material.clone(),
-                    uv
-                });
-            }
-            t = (-half_b + root) / a;
-            if t < tmax && t > tmin {
-                let p = ray.at(t);                
-                let outward_normal = (p - self.center) / self.radius;
-                let uv = get_sphere_uv(outward_normal);
-                return Some(HitRecord {
-                    t,
-                    p,
-                    normal: outward_normal,
-                    material: self.material.clone(),
-                    uv
+                    material,
+                    uv,
+                    object_id: 0,
+                    material_id: 0,
                 });
             }
         }
-        None
     }
+    None
+}
 
-    fn aabb(&self) -> AABB {
-        let half_size = Vec3::new(self.radius, self.radius, self.radius);
-        let min = self.center - half_size;
-        let max = self.center + half_size;
-        AABB { min, max }
-    }
+fn sphere_aabb(center: Vec3, radius: f32) -> AABB {
+    let half_size = Vec3::new(radius, radius, radius);
+    AABB { min: center - half_size, max: center + half_size }
+}
 
-    fn pdf(&self, origin: &Vec3, direction: &Vec3) -> f32 {
-        let ray = Ray::new(*origin, *direction, 0.0);
-        if let Some(hit) = &self.hit(&ray, 0.001, f32::MAX) {
-            let cos_theta_max = (1.0 - self.radius * self.radius / (self.center - origin).magnitude_squared()).sqrt();
-            // println!("cos_theta_max: {}", cos_theta_max);
-            let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
-            1.0 / solid_angle
-        } else { 0.0 }
-    }
-    fn sample_direction(&self, origin: &Vec3) -> Vec3 {        
-        let direction = self.center - origin;
-        let distance_squared = direction.magnitude_squared();
-        onb_local(&direction.normalize(), &random_to_sphere(self.radius, distance_squared))
-    }
-    fn is_inside(&self, point: Vec3) -> bool {
-        (self.center - point).magnitude() < (self.radius - 0.001)
-    } 
+/// One over the solid angle of the sphere seen from `origin`.
+fn sphere_pdf(center: Vec3, radius: f32, origin: &Vec3) -> f32 {
+    let cos_theta_max = (1.0 - radius * radius / (center - origin).magnitude_squared()).sqrt();
+    let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+    1.0 / solid_angle
+}
+
+fn sample_sphere(center: Vec3, radius: f32, origin: &Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+    let direction = center - origin;
+    let distance_squared = direction.magnitude_squared();
+    onb_local(&direction.normalize(), &random_to_sphere(radius, distance_squared, sampler))
 }
 
 fn get_sphere_uv(p: Vec3) -> Vec2 {
@@ -0,0 +1,258 @@
+use smallvec::SmallVec;
+use std::f32::consts::PI;
+
+use crate::geometry::{area_pdf, Geometry, HitRecord, aabb::AABB};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vector::{Vec2, Vec3};
+
+/// A ring around `center` in the xz plane. `major_radius` is the distance
+/// from the centre to the middle of the tube, `minor_radius` the radius of
+/// the tube.
+#[derive(Clone)]
+pub struct Torus {
+    pub center: Vec3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+    pub material: Box<dyn Material>,
+}
+
+impl Torus {
+    pub fn area(&self) -> f32 {
+        4.0 * PI * PI * self.major_radius * self.minor_radius
+    }
+
+    pub fn sample_point(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let (major, minor) = (self.major_radius, self.minor_radius);
+        let r = sampler.next_2d();
+        let phi = 2.0 * PI * r.x;
+        let theta = sample_tube_angle(major, minor, r.y);
+        let distance = major + minor * theta.cos();
+        self.center + Vec3::new(distance * phi.cos(), minor * theta.sin(), distance * phi.sin())
+    }
+}
+
+/// The outside of the tube has more area than the inside, the angle around
+/// it has the density (major + minor cos θ) / (2π major). Inverts its CDF
+/// with Newton's method, kept inside a bracket that bisects when a step
+/// leaves it, so a sample takes a single dimension.
+fn sample_tube_angle(major: f32, minor: f32, u: f32) -> f32 {
+    let target = 2.0 * PI * major * u;
+    let (mut low, mut high) = (0.0, 2.0 * PI);
+    let mut theta = 2.0 * PI * u;
+    for _ in 0..16 {
+        let value = major * theta + minor * theta.sin() - target;
+        if value > 0.0 {
+            high = theta;
+        } else {
+            low = theta;
+        }
+        theta -= value / (major + minor * theta.cos());
+        if !(low..=high).contains(&theta) {
+            theta = 0.5 * (low + high);
+        }
+    }
+    theta
+}
+
+impl Geometry for Torus {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let (major, minor) = (self.major_radius as f64, self.minor_radius as f64);
+        let length = ray.direction.magnitude() as f64;
+        let d = ray.direction.map(f64::from) / length;
+        let o = (ray.origin - self.center).map(f64::from);
+
+        // Starts close to the torus, the quartic loses precision far away.
+        let bound = major + minor;
+        let closest = -o.dot(&d);
+        if (o + closest * d).magnitude_squared() > bound * bound {
+            return None;
+        }
+        let shift = (closest - bound).max(0.0);
+        let o = o + shift * d;
+
+        let e = o.magnitude_squared() + major * major - minor * minor;
+        let f = o.dot(&d);
+        let four_major_squared = 4.0 * major * major;
+        let roots = solve_quartic(
+            4.0 * f,
+            4.0 * f * f + 2.0 * e - four_major_squared * (d.x * d.x + d.z * d.z),
+            4.0 * f * e - 2.0 * four_major_squared * (o.x * d.x + o.z * d.z),
+            e * e - four_major_squared * (o.x * o.x + o.z * o.z),
+        );
+        let t = roots
+            .iter()
+            .map(|root| ((root + shift) / length) as f32)
+            .filter(|t| *t > tmin && *t < tmax)
+            .fold(None, |closest: Option<f32>, t| Some(closest.map_or(t, |c| c.min(t))))?;
+
+        let p = ray.origin + t * ray.direction - self.center;
+        let distance = (p.x * p.x + p.z * p.z).sqrt();
+        let ring = Vec3::new(p.x, 0.0, p.z) * (self.major_radius / distance);
+        let normal = (p - ring).normalize();
+        let u = (p.z.atan2(p.x) + PI) / (2.0 * PI);
+        let v = (p.y.atan2(distance - self.major_radius) + PI) / (2.0 * PI);
+        Some(HitRecord {
+            t,
+            p: ray.at(t),
+            normal,
+            material: &*self.material,
+            uv: Vec2::new(u, v),
+            object_id: 0,
+            material_id: 0,
+        })
+    }
+
+    fn aabb(&self) -> AABB {
+        let outer = self.major_radius + self.minor_radius;
+        let half_size = Vec3::new(outer, self.minor_radius, outer);
+        AABB { min: self.center - half_size, max: self.center + half_size }
+    }
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        area_pdf(self, self.area(), origin, direction, time)
+    }
+    fn sample_direction(&self, origin: &Vec3, _time: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        self.sample_point(sampler) - origin
+    }
+    fn is_inside(&self, point: Vec3, _time: f32) -> bool {
+        let p = point - self.center;
+        let ring_distance = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+        ring_distance * ring_distance + p.y * p.y < self.minor_radius * self.minor_radius
+    }
+}
+
+const EPSILON: f64 = 1.0e-9;
+
+/// Real roots of x⁴ + a x³ + b x² + c x + d with Ferrari's method, after
+/// Schwarze, "Cubic and Quartic Roots", Graphics Gems, 1990. Every root is
+/// polished with Newton's method.
+fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> SmallVec<[f64; 4]> {
+    // Substitutes x = y - a / 4 for y⁴ + p y² + q y + r.
+    let a_squared = a * a;
+    let p = -3.0 / 8.0 * a_squared + b;
+    let q = a_squared * a / 8.0 - a * b / 2.0 + c;
+    let r = -3.0 / 256.0 * a_squared * a_squared + a_squared * b / 16.0 - a * c / 4.0 + d;
+
+    let mut roots: SmallVec<[f64; 4]> = SmallVec::new();
+    if r.abs() < EPSILON {
+        // y (y³ + p y + q) = 0
+        roots.push(0.0);
+        roots.extend(solve_cubic(0.0, p, q));
+    } else {
+        // Any real root of the resolvent cubic splits the quartic into two
+        // quadratics.
+        let z = solve_cubic(-p / 2.0, -r, r * p / 2.0 - q * q / 8.0)[0];
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if u.abs() < EPSILON { 0.0 } else if u > 0.0 { u.sqrt() } else { return roots };
+        let v = if v.abs() < EPSILON { 0.0 } else if v > 0.0 { v.sqrt() } else { return roots };
+        let v = if q < 0.0 { -v } else { v };
+        roots.extend(solve_monic_quadratic(v, z - u));
+        roots.extend(solve_monic_quadratic(-v, z + u));
+    }
+
+    for root in roots.iter_mut() {
+        let mut x = *root - a / 4.0;
+        for _ in 0..2 {
+            let value = (((x + a) * x + b) * x + c) * x + d;
+            let slope = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+            if slope.abs() > EPSILON {
+                x -= value / slope;
+            }
+        }
+        *root = x;
+    }
+    roots
+}
+
+/// Real roots of x³ + a x² + b x + c, at least one.
+fn solve_cubic(a: f64, b: f64, c: f64) -> SmallVec<[f64; 3]> {
+    // Substitutes x = y - a / 3 for y³ + 3 p y + 2 q.
+    let a_squared = a * a;
+    let p = (-a_squared / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * a_squared - a * b / 3.0 + c) / 2.0;
+    let p_cubed = p * p * p;
+    let discriminant = q * q + p_cubed;
+
+    let mut roots: SmallVec<[f64; 3]> = if discriminant.abs() < EPSILON {
+        if q.abs() < EPSILON {
+            smallvec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            smallvec![2.0 * u, -u]
+        }
+    } else if discriminant < 0.0 {
+        // Three real roots.
+        let phi = (-q / (-p_cubed).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        let third = std::f64::consts::PI / 3.0;
+        smallvec![t * phi.cos(), -t * (phi + third).cos(), -t * (phi - third).cos()]
+    } else {
+        let root = discriminant.sqrt();
+        smallvec![(root - q).cbrt() - (root + q).cbrt()]
+    };
+    for root in roots.iter_mut() {
+        *root -= a / 3.0;
+    }
+    roots
+}
+
+/// Real roots of x² + p x + q.
+fn solve_monic_quadratic(p: f64, q: f64) -> SmallVec<[f64; 2]> {
+    let discriminant = p * p / 4.0 - q;
+    if discriminant.abs() < EPSILON {
+        smallvec![-p / 2.0]
+    } else if discriminant < 0.0 {
+        SmallVec::new()
+    } else {
+        let root = discriminant.sqrt();
+        smallvec![-p / 2.0 + root, -p / 2.0 - root]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(mut roots: Vec<f64>, expected: &[f64]) {
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(roots.len(), expected.len(), "roots {:?}, expected {:?}", roots, expected);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1.0e-6, "roots {:?}, expected {:?}", roots, expected);
+        }
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(solve_quartic(-10.0, 35.0, -50.0, 24.0).to_vec(), &[1.0, 2.0, 3.0, 4.0]);
+        // x (x + 1)(x - 1)(x - 2)
+        assert_roots(solve_quartic(-2.0, -1.0, 2.0, 0.0).to_vec(), &[-1.0, 0.0, 1.0, 2.0]);
+        // (x² + 1)(x - 2)(x + 3)
+        assert_roots(solve_quartic(1.0, -5.0, 1.0, -6.0).to_vec(), &[-3.0, 2.0]);
+        // (x² + 1)(x² + 4)
+        assert_roots(solve_quartic(0.0, 5.0, 0.0, 4.0).to_vec(), &[]);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (x - 1)(x + 2)(x - 3)
+        assert_roots(solve_cubic(-2.0, -5.0, 6.0).to_vec(), &[-2.0, 1.0, 3.0]);
+        // (x - 2)(x² + 1)
+        assert_roots(solve_cubic(-2.0, 1.0, -2.0).to_vec(), &[2.0]);
+        // (x - 1)² (x + 2)
+        assert_roots(solve_cubic(0.0, -3.0, 2.0).to_vec(), &[-2.0, 1.0]);
+    }
+
+    #[test]
+    fn tube_angle_inverts_its_cdf() {
+        let (major, minor) = (1.0, 0.4);
+        for i in 0..=16 {
+            let u = i as f32 / 16.0;
+            let theta = sample_tube_angle(major, minor, u);
+            let cdf = (major * theta + minor * theta.sin()) / (2.0 * PI * major);
+            assert!((cdf - u).abs() < 1.0e-5, "u {} gives cdf {}", u, cdf);
+        }
+    }
+}
@@ -1,71 +1,429 @@
-use nalgebra::Rotation3;
+use nalgebra::{Matrix3, Point3, Rotation3, UnitQuaternion, U3};
 
-use crate::geometry::{aabb::AABB, Geometry, HitRecord};
+use crate::geometry::{aabb::{surrounding_box, AABB}, Geometry, HitRecord};
 use crate::ray::Ray;
-use crate::vector::{Vec2, Vec3, deg_to_rad};
+use crate::sampler::Sampler;
+use crate::vector::{Mat4, Vec3, deg_to_rad};
 
+/// Matrices are composed by multiplying them, the rightmost is applied
+/// first, e.g. `translation(p) * rotation(r) * scale(s)`.
+pub fn translation(offset: Vec3) -> Mat4 {
+    Mat4::new_translation(&offset)
+}
+
+/// Euler angles in degrees, roll, pitch and yaw around x, y and z.
+pub fn rotation(rotation_deg: Vec3) -> Mat4 {
+    Rotation3::from_euler_angles(
+        deg_to_rad(rotation_deg.x),
+        deg_to_rad(rotation_deg.y),
+        deg_to_rad(rotation_deg.z),
+    )
+    .to_homogeneous()
+}
+
+pub fn scale(scale: Vec3) -> Mat4 {
+    Mat4::new_nonuniform_scaling(&scale)
+}
+
+/// Shifts every coordinate by multiples of the others, `xy` is how much x
+/// moves per unit of y.
+pub fn shear(xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Mat4 {
+    Mat4::new(
+        1.0, xy, xz, 0.0,
+        yx, 1.0, yz, 0.0,
+        zx, zy, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    )
+}
+
+/// An invertible affine transform from object to world space, with the
+/// matrices needed to move rays, points and normals between the spaces.
+#[derive(Clone, Copy, Debug)]
+pub struct Affine {
+    pub to_world: Mat4,
+    pub to_object: Mat4,
+    /// The inverse transpose of the linear part, which keeps normals
+    /// perpendicular to the surface under non-uniform scale and shear.
+    normal_to_world: Matrix3<f32>,
+}
+
+impl Affine {
+    pub fn new(to_world: Mat4) -> Self {
+        let to_object = to_world
+            .try_inverse()
+            .expect("transform is not invertible");
+        Self::with_inverse(to_world, to_object)
+    }
+
+    /// For transforms whose inverse is known, skips inverting the matrix.
+    pub fn with_inverse(to_world: Mat4, to_object: Mat4) -> Self {
+        Affine {
+            to_world,
+            to_object,
+            normal_to_world: to_object.fixed_slice::<U3, U3>(0, 0).transpose(),
+        }
+    }
+
+    pub fn point_to_world(&self, point: &Vec3) -> Vec3 {
+        self.to_world.transform_point(&Point3::from(*point)).coords
+    }
+    pub fn point_to_object(&self, point: &Vec3) -> Vec3 {
+        self.to_object.transform_point(&Point3::from(*point)).coords
+    }
+    pub fn vector_to_world(&self, vector: &Vec3) -> Vec3 {
+        self.to_world.transform_vector(vector)
+    }
+    pub fn vector_to_object(&self, vector: &Vec3) -> Vec3 {
+        self.to_object.transform_vector(vector)
+    }
+    pub fn normal_to_world(&self, normal: &Vec3) -> Vec3 {
+        (self.normal_to_world * normal).normalize()
+    }
+
+    /// The direction is not normalised, so `t` is the same in both spaces.
+    pub fn ray_to_object(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.point_to_object(&ray.origin),
+            direction: self.vector_to_object(&ray.direction),
+            ..*ray
+        }
+    }
+
+    /// Moves a hit in object space to world space.
+    pub fn hit_to_world<'a>(&self, mut hit: HitRecord<'a>) -> HitRecord<'a> {
+        hit.p = self.point_to_world(&hit.p);
+        hit.normal = self.normal_to_world(&hit.normal);
+        hit
+    }
+
+    /// Forwards a solid angle pdf to the object. Directions seen from the
+    /// object are squeezed or stretched by non-uniform scale and shear, the
+    /// Jacobian of that change is |det A⁻¹| / |A⁻¹ω|³ for a unit ω.
+    pub fn pdf(&self, object: &dyn Geometry, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        let direction = direction.normalize();
+        let object_direction = self.vector_to_object(&direction);
+        let jacobian = self.to_object.fixed_slice::<U3, U3>(0, 0).determinant().abs()
+            / object_direction.magnitude().powi(3);
+        object.pdf(&self.point_to_object(origin), &object_direction, time) * jacobian
+    }
+
+    pub fn sample_direction(
+        &self,
+        object: &dyn Geometry,
+        origin: &Vec3,
+        time: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Vec3 {
+        let direction = object.sample_direction(&self.point_to_object(origin), time, sampler);
+        self.vector_to_world(&direction)
+    }
+}
+
+/// A pose at a point in time, applied as scale, then rotation, then
+/// translation.
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    pub time: f32,
+    pub translation: Vec3,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    /// Euler angles in degrees as for `rotation`.
+    pub fn new(time: f32, translation: Vec3, rotation_deg: Vec3, scale: Vec3) -> Self {
+        Keyframe {
+            time,
+            translation,
+            rotation: UnitQuaternion::from_euler_angles(
+                deg_to_rad(rotation_deg.x),
+                deg_to_rad(rotation_deg.y),
+                deg_to_rad(rotation_deg.z),
+            ),
+            scale,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        translation(self.translation) * self.rotation.to_homogeneous() * scale(self.scale)
+    }
+
+    /// The inverse of `matrix`, undoing the steps in reverse order.
+    pub fn inverse_matrix(&self) -> Mat4 {
+        scale(self.scale.map(|s| 1.0 / s))
+            * self.rotation.inverse().to_homogeneous()
+            * translation(-self.translation)
+    }
+}
+
+/// A transform that changes over time. Translation and scale are
+/// interpolated linearly between the keyframes and rotation along the
+/// shortest arc, before the first and after the last keyframe it holds.
+#[derive(Clone, Debug)]
+pub struct Keyframes {
+    keys: Vec<Keyframe>,
+}
+
+impl Keyframes {
+    pub fn new(mut keys: Vec<Keyframe>) -> Self {
+        assert!(!keys.is_empty(), "keyframes need at least one key");
+        keys.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        Keyframes { keys }
+    }
+
+    pub fn at(&self, time: f32) -> Mat4 {
+        self.pose_at(time).matrix()
+    }
+
+    /// The interpolated pose, which is cheaper to invert than its matrix.
+    pub fn pose_at(&self, time: f32) -> Keyframe {
+        let next = self.keys.iter().position(|key| key.time > time);
+        let (a, b) = match next {
+            Some(0) => return self.keys[0],
+            None => return self.keys[self.keys.len() - 1],
+            Some(i) => (&self.keys[i - 1], &self.keys[i]),
+        };
+        let s = (time - a.time) / (b.time - a.time);
+        let rotation = a
+            .rotation
+            .try_slerp(&b.rotation, s, 1.0e-6)
+            .unwrap_or_else(|| a.rotation.nlerp(&b.rotation, s));
+        Keyframe {
+            time,
+            translation: a.translation.lerp(&b.translation, s),
+            rotation,
+            scale: a.scale.lerp(&b.scale, s),
+        }
+    }
+
+    /// The box around `bbox` over the whole motion. Every segment is
+    /// sampled, and padded for the arcs that rotation traces between the
+    /// samples, whose radius is at most that of the farthest corner.
+    pub fn bounds(&self, bbox: &AABB) -> AABB {
+        const STEPS: usize = 32;
+        let mut bounds = bbox.transformed(&self.keys[0].matrix());
+        let radius = (0..8).map(|i| bbox.corner(i).magnitude()).fold(0.0, f32::max);
+        for pair in self.keys.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            for step in 1..=STEPS {
+                let time = a.time + (b.time - a.time) * step as f32 / STEPS as f32;
+                bounds = surrounding_box(bounds, bbox.transformed(&self.at(time)));
+            }
+            let step_angle = a.rotation.angle_to(&b.rotation) / STEPS as f32;
+            let max_scale = a.scale.abs().max().max(b.scale.abs().max());
+            let padding = Vec3::repeat(radius * max_scale * (1.0 - (step_angle / 2.0).cos()));
+            bounds = AABB {
+                min: bounds.min - padding,
+                max: bounds.max + padding,
+            };
+        }
+        bounds
+    }
+}
+
+/// Places an object with any affine transform: translation, rotation,
+/// non-uniform scale and shear, optionally after keyframed motion.
 #[derive(Clone)]
 pub struct Transform {
     pub object: Box<dyn Geometry>,
-    pub offset: Vec3,
-    pub rotation: Rotation3<f32>,
+    /// Applied after the motion, or alone without one.
+    pub transform: Affine,
+    pub motion: Option<Keyframes>,
+    /// Covers the whole motion.
     pub bbox: AABB,
 }
 
 impl Transform {
+    /// Translates after rotating by Euler angles in degrees.
     pub fn new(object: Box<dyn Geometry>, offset: Vec3, rotation_deg: Vec3) -> Self {
-        let rotation = Rotation3::from_euler_angles(
-            deg_to_rad(rotation_deg.x),
-            deg_to_rad(rotation_deg.y),
-            deg_to_rad(rotation_deg.z),
-        );
-        let bb_min_rot = rotation * object.aabb().min + offset;
-        let bb_max_rot = rotation * object.aabb().max + offset;
-
-        let bb_min = Vec3::new(
-            bb_min_rot.x.min(bb_max_rot.x),
-            bb_min_rot.y.min(bb_max_rot.y),
-            bb_min_rot.z.min(bb_max_rot.z),
-        );
+        Self::from_matrix(object, translation(offset) * rotation(rotation_deg))
+    }
 
-        let bb_max = Vec3::new(
-            bb_min_rot.x.max(bb_max_rot.x),
-            bb_min_rot.y.max(bb_max_rot.y),
-            bb_min_rot.z.max(bb_max_rot.z),
-        );
+    pub fn from_matrix(object: Box<dyn Geometry>, to_world: Mat4) -> Self {
+        let bbox = object.aabb().transformed(&to_world);
+        Self {
+            object,
+            transform: Affine::new(to_world),
+            motion: None,
+            bbox,
+        }
+    }
 
+    /// Moves the object along keyframes, rays are intersected with it at
+    /// their time.
+    pub fn animated(object: Box<dyn Geometry>, motion: Keyframes) -> Self {
+        let bbox = motion.bounds(&object.aabb());
         Self {
             object,
-            offset,
-            rotation,
-            bbox: AABB {
-                min: bb_min,
-                max: bb_max,
-            },
+            transform: Affine::new(Mat4::identity()),
+            motion: Some(motion),
+            bbox,
+        }
+    }
+
+    /// Applies another transform after this one, without nesting.
+    pub fn then(mut self, matrix: Mat4) -> Self {
+        let to_world = matrix * self.transform.to_world;
+        self.bbox = self.bbox.transformed(&matrix);
+        self.transform = Affine::new(to_world);
+        self
+    }
+
+    /// Runs for every ray, so the pose is inverted by parts instead of
+    /// inverting its matrix.
+    fn transform_at(&self, time: f32) -> Affine {
+        match &self.motion {
+            Some(motion) => {
+                let pose = motion.pose_at(time);
+                Affine::with_inverse(
+                    self.transform.to_world * pose.matrix(),
+                    pose.inverse_matrix() * self.transform.to_object,
+                )
+            }
+            None => self.transform,
         }
     }
 }
 
 impl Geometry for Transform {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
-        let inv_rot = self.rotation.inverse();
-        let moved_ray = Ray::new(
-            inv_rot * (ray.origin - self.offset),
-            inv_rot * ray.direction,
-            ray.wavelength
-        );
-        // moved_ray.albedo_normal_ray = ray.albedo_normal_ray;
-
-        if let Some(mut hit_rec) = self.object.hit(&moved_ray, tmin, tmax) {
-            hit_rec.p = self.rotation * hit_rec.p + self.offset;
-            hit_rec.normal = self.rotation * hit_rec.normal;
-            Some(hit_rec)
-        } else {
-            None
-        }
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        let transform = self.transform_at(ray.time);
+        let object_ray = transform.ray_to_object(ray);
+        let hit = self.object.hit(&object_ray, tmin, tmax)?;
+        Some(transform.hit_to_world(hit))
     }
 
     fn aabb(&self) -> AABB {
         self.bbox
     }
+    fn pdf(&self, origin: &Vec3, direction: &Vec3, time: f32) -> f32 {
+        self.transform_at(time).pdf(&*self.object, origin, direction, time)
+    }
+    fn sample_direction(&self, origin: &Vec3, time: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        self.transform_at(time).sample_direction(&*self.object, origin, time, sampler)
+    }
+    fn is_inside(&self, point: Vec3, time: f32) -> bool {
+        self.object.is_inside(self.transform_at(time).point_to_object(&point), time)
+    }
+    fn object_id(&self) -> u32 {
+        self.object.object_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::geometry::sphere::Sphere;
+    use crate::material::{color::Reflectance, lambertian::Lambertian};
+
+    fn unit_sphere() -> Box<dyn Geometry> {
+        Box::new(Sphere {
+            center: Vec3::zeros(),
+            radius: 1.0,
+            material: Box::new(Lambertian { reflectance: Reflectance::Uniform(0.5) }),
+        })
+    }
+
+    fn sheared() -> Mat4 {
+        rotation(Vec3::new(20.0, 30.0, 40.0))
+            * shear(0.5, 0.0, 0.2, 0.0, 0.0, -0.3)
+            * scale(Vec3::new(2.0, 0.5, 1.0))
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_to_the_surface() {
+        let affine = Affine::new(sheared());
+        let normal = Vec3::new(1.0, 2.0, 3.0).normalize();
+        let tangents = [Vec3::new(2.0, -1.0, 0.0), Vec3::new(0.0, 3.0, -2.0)];
+        let world_normal = affine.normal_to_world(&normal);
+        assert!((world_normal.magnitude() - 1.0).abs() < 1.0e-5);
+        for tangent in tangents.iter() {
+            assert!(normal.dot(tangent).abs() < 1.0e-6);
+            let world_tangent = affine.vector_to_world(tangent);
+            assert!(world_normal.dot(&world_tangent).abs() < 1.0e-5);
+        }
+    }
+
+    #[test]
+    fn pdf_jacobian_matches_the_mapped_solid_angle() {
+        let affine = Affine::new(sheared());
+        let direction = Vec3::new(0.3, -0.5, 0.8).normalize();
+        // Two small perpendicular steps span a patch of directions, the
+        // Jacobian is the ratio of its object and world solid angles.
+        let u = direction.cross(&Vec3::new(0.0, 0.0, 1.0)).normalize() * 1.0e-3;
+        let v = direction.cross(&u);
+        let to_object = |d: Vec3| affine.vector_to_object(&d).normalize();
+        let world_area = u.cross(&v).magnitude();
+        let object_center = to_object(direction);
+        let object_area = (to_object(direction + u) - object_center)
+            .cross(&(to_object(direction + v) - object_center))
+            .magnitude();
+        let object_direction = affine.vector_to_object(&direction);
+        let jacobian = affine.to_object.fixed_slice::<U3, U3>(0, 0).determinant().abs()
+            / object_direction.magnitude().powi(3);
+        assert!((object_area / world_area / jacobian - 1.0).abs() < 1.0e-2);
+    }
+
+    #[test]
+    fn scaled_sphere_pdf_integrates_to_one() {
+        let ellipsoid = Transform::from_matrix(
+            unit_sphere(),
+            translation(Vec3::new(0.0, 0.0, -5.0)) * scale(Vec3::new(2.0, 1.0, 0.5)),
+        );
+        // Integrates over a cap of directions around -z holding the ellipsoid.
+        let cos_max = 0.85f32;
+        let cap = 2.0 * PI * (1.0 - cos_max);
+        let n = 400;
+        let mut integral = 0.0;
+        let mut hits = 0;
+        for i in 0..n {
+            for j in 0..n {
+                let cos_theta = 1.0 - (i as f32 + 0.5) / n as f32 * (1.0 - cos_max);
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let phi = 2.0 * PI * (j as f32 + 0.5) / n as f32;
+                let direction = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), -cos_theta);
+                let pdf = ellipsoid.pdf(&Vec3::zeros(), &direction, 0.0);
+                integral += pdf * cap / (n * n) as f32;
+                let ray = Ray::new(Vec3::zeros(), direction, 550.0, 0.0);
+                if ellipsoid.hit(&ray, 0.001, f32::MAX).is_some() {
+                    hits += 1;
+                }
+            }
+        }
+        assert!((integral - 1.0).abs() < 1.0e-2, "pdf integrates to {}", integral);
+        // The ellipsoid is seen well inside the cap.
+        assert!(hits > 0 && hits < n * n / 2);
+    }
+
+    #[test]
+    fn bounds_hold_the_transformed_corners() {
+        let transform = Transform::from_matrix(unit_sphere(), sheared());
+        let bbox = transform.aabb();
+        for corner in 0..8 {
+            let point = sheared().transform_point(&Point3::from(AABB {
+                min: Vec3::repeat(-1.0),
+                max: Vec3::repeat(1.0),
+            }
+            .corner(corner)));
+            for axis in 0..3 {
+                assert!(bbox.min[axis] <= point[axis] + 1.0e-5 && point[axis] <= bbox.max[axis] + 1.0e-5);
+            }
+        }
+        // A rotated cube reaches out to its corners.
+        let turned = Transform::new(unit_sphere(), Vec3::zeros(), Vec3::new(0.0, 0.0, 45.0));
+        assert!((turned.aabb().max.x - 2.0f32.sqrt()).abs() < 1.0e-5);
+        assert!((turned.aabb().max.z - 1.0).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn then_applies_after_the_transform() {
+        let offset = translation(Vec3::new(1.0, 2.0, 3.0));
+        let moved = Transform::from_matrix(unit_sphere(), sheared()).then(offset);
+        let direct = Transform::from_matrix(unit_sphere(), offset * sheared());
+        assert!((moved.transform.to_world - direct.transform.to_world).norm() < 1.0e-5);
+        assert!((moved.transform.to_object - direct.transform.to_object).norm() < 1.0e-5);
+        assert!((moved.aabb().min - direct.aabb().min).norm() < 1.0e-5);
+    }
 }
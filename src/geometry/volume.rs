@@ -1,10 +1,7 @@
-use std::f32::consts::PI;
-use rand::{random};
-
 use crate::geometry::{Geometry, HitRecord, aabb::{AABB}};
 use crate::material::Material;
 use crate::ray::Ray;
-use crate::vector::{Vec2, Vec3, onb_local, random_to_sphere, clamp};
+use crate::vector::{Vec2, Vec3};
 
 #[derive(Clone)]
 pub struct ConstantMedium {
@@ -16,19 +13,18 @@ pub struct ConstantMedium {
 impl ConstantMedium {
     pub fn new(boundary: Box<dyn Geometry>, density: f32, material: Box<dyn Material>) -> Self {
         Self {
-            boundary: boundary,
+            boundary,
             phase_function: material,
             neg_inv_density: -1.0 / density
         }
     }
 }
 
-impl Geometry for ConstantMedium {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
-        // if ray.albedo_normal_ray {
-        //     return None;
-        // }
-
+impl ConstantMedium {
+    /// Scatters `free_path` into the medium, by the memorylessness of the
+    /// exponential distribution the rest of it carries over to the next
+    /// piece of a concave boundary.
+    fn hit_free_path(&self, ray: &Ray, tmin: f32, tmax: f32, free_path: f32) -> Option<HitRecord<'_>> {
         if let Some(mut hit1) = self.boundary.hit(ray, f32::MIN, f32::MAX) {
             if let Some(mut hit2) = self.boundary.hit(ray, hit1.t + 0.0001, f32::MAX) {
                 if hit1.t < tmin { hit1.t = tmin; }
@@ -40,40 +36,54 @@ impl Geometry for ConstantMedium {
 
                 let ray_length = ray.direction.magnitude();
                 let distance_inside_boundary = (hit2.t - hit1.t) * ray_length;
-                let hit_distance = self.neg_inv_density * random::<f32>().ln();
 
-                if hit_distance > distance_inside_boundary {
+                if free_path > distance_inside_boundary {
                     // Extend ray to check for more hits in concave boundaries
-                    return self.hit(ray, hit2.t + 0.0001, f32::MAX);
+                    return self.hit_free_path(ray, hit2.t + 0.0001, tmax, free_path - distance_inside_boundary);
                 }
 
-                let t = hit1.t + hit_distance / ray_length;
+                let t = hit1.t + free_path / ray_length;
 
                 Some(HitRecord {
                     t,
                     p: ray.at(t),
                     normal: Vec3::new(1.0, 0.0, 0.0),
-                    material: self.phase_function.clone(),
-                    uv: Vec2::new(0.0, 0.0)
+                    material: &*self.phase_function,
+                    uv: Vec2::new(0.0, 0.0),
+                    object_id: 0,
+                    material_id: 0,
                 })
 
             } else { None }
         } else { None }
     }
 
+}
+
+impl Geometry for ConstantMedium {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord<'_>> {
+        // if ray.albedo_normal_ray {
+        //     return None;
+        // }
+
+        // Never take the log of zero, the free path would be infinite.
+        let free_path = self.neg_inv_density * (1.0 - ray.medium_sample).ln();
+        self.hit_free_path(ray, tmin, tmax, free_path)
+    }
+
     fn aabb(&self) -> AABB {
         self.boundary.aabb()
     }
 }
 
-#[derive(Clone)]
-pub struct NonUniformMedium {
-    boundary:Box<dyn Geometry>,
-    phase_function: Box<dyn Material>,
-    // density: Box<dyn Texture>,
-    density: f32,
-    max_density: f32,
-}
+// #[derive(Clone)]
+// pub struct NonUniformMedium {
+//     boundary:Box<dyn Geometry>,
+//     phase_function: Box<dyn Material>,
+//     // density: Box<dyn Texture>,
+//     density: f32,
+//     max_density: f32,
+// }
 
 // impl NonUniformMedium {
 //     // pub fn new(boundary: impl Geometry, density: Box<dyn Texture>, max_density: f32, material: Box<dyn Material>) -> Self {
@@ -0,0 +1,225 @@
+use smallvec::SmallVec;
+
+use crate::camera::Camera;
+use crate::color::{cie_y_integral, get_tristimulus};
+use crate::film::{FirstHit, PixelSample};
+use crate::geometry::{Geometry, HittableList};
+use crate::material::ScatterRecord;
+use crate::pdf::{MixturePdf, Pdf};
+use crate::ray::Ray;
+use crate::sampler::{
+    Sampler, BOUNCE_DIMENSION, DIMENSIONS_PER_BOUNCE, MEDIUM_DIMENSION_OFFSET, PIXEL_DIMENSION,
+};
+use crate::vector::{Vec2, Vec3};
+
+/// Light path expressions the radiance of a path is split into, decided by
+/// the first scattering event after the camera. Together they cover every path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightPath {
+    /// Emitters seen directly by the camera.
+    Emission,
+    /// One diffuse or glossy bounce between the camera and the emitter.
+    DirectDiffuse,
+    /// Two or more bounces, starting with a diffuse or glossy one.
+    Indirect,
+    /// Paths starting with a mirror or refractive bounce.
+    Specular,
+    /// Paths starting with a scattering event inside a medium.
+    Volume,
+}
+
+impl LightPath {
+    pub const ALL: [LightPath; 5] = [
+        LightPath::Emission,
+        LightPath::DirectDiffuse,
+        LightPath::Indirect,
+        LightPath::Specular,
+        LightPath::Volume,
+    ];
+
+    pub fn index(&self) -> usize {
+        LightPath::ALL.iter().position(|p| p == self).unwrap()
+    }
+
+    pub fn name(&self) -> &'static str {
+        use LightPath::*;
+        match self {
+            Emission => "emission",
+            DirectDiffuse => "direct_diffuse",
+            Indirect => "indirect",
+            Specular => "specular",
+            Volume => "volume",
+        }
+    }
+}
+
+/// Radiance picked up from one emitter hit along a path.
+pub struct PathContribution {
+    pub radiance: f32,
+    pub light_path: LightPath,
+    /// Object id of the emitter, see `Tagged`.
+    pub object_id: u32,
+}
+
+pub struct PathSample {
+    pub radiance: f32,
+    pub contributions: SmallVec<[PathContribution; 4]>,
+}
+
+enum FirstEvent {
+    Surface,
+    Specular,
+    Volume,
+}
+
+/// Traces sample `n` of pixel (x, y) of a `width` by `height` film, the
+/// radiance is divided by the pdf of the camera ray.
+pub fn render_sample(
+    camera: &Camera,
+    world: &dyn Geometry,
+    attractors: &HittableList,
+    sampler: &dyn Sampler,
+    (width, height): (usize, usize),
+    (x, y): (usize, usize),
+    n: u32,
+) -> PixelSample {
+    let mut sampler = dyn_clone::clone_box(sampler);
+    sampler.start_sample(x, y, n);
+    sampler.set_dimension(PIXEL_DIMENSION);
+    let jitter = sampler.next_2d();
+    let u = (x as f32 + jitter.x) / width as f32;
+    let v = (height as f32 - (y as f32 + jitter.y)) / height as f32;
+
+    let (ray, ray_pdf) = camera.get_ray_tri(u, v, &mut *sampler);
+    let wavelength = ray.wavelength;
+    let mut first_hit = FirstHit::default();
+    let mut path = trace_path(ray, world, attractors, 50, Some(&mut first_hit), &mut *sampler);
+    first_hit.albedo /= ray_pdf;
+    for contribution in path.contributions.iter_mut() {
+        contribution.radiance /= ray_pdf;
+    }
+    let radiance = path.radiance / ray_pdf;
+
+    PixelSample {
+        position: Vec2::new(x as f32, y as f32) + jitter,
+        tristimulus: radiance * get_tristimulus(wavelength),
+        wavelength,
+        radiance,
+        first_hit,
+        contributions: path.contributions,
+    }
+}
+
+/// Traces a path and returns its spectral radiance, split into the
+/// contributions of every emitter it hit.
+pub fn trace_path(
+    mut ray: Ray,
+    world: &dyn Geometry,
+    attractors: &HittableList,
+    max_depth: u32,
+    mut first_hit: Option<&mut FirstHit>,
+    sampler: &mut dyn Sampler,
+) -> PathSample {
+    let mut sample = PathSample {
+        radiance: 0.0,
+        contributions: SmallVec::new(),
+    };
+    let mut throughput = 1.0;
+    let mut first_event: Option<FirstEvent> = None;
+    let mut non_specular_bounces = 0;
+
+    for depth in 0..max_depth {
+        let bounce_dimension = BOUNCE_DIMENSION + depth * DIMENSIONS_PER_BOUNCE;
+        sampler.set_dimension(bounce_dimension + MEDIUM_DIMENSION_OFFSET);
+        ray.medium_sample = sampler.next_f32();
+        sampler.set_dimension(bounce_dimension);
+        let hit_rec = match world.hit(&ray, 0.001, f32::MAX) {
+            Some(hit_rec) => hit_rec,
+            // let temperature = 6500.0;
+            // boltzmann(ray.wavelength, temperature) / boltzmann((WIENS_CONSTANT / temperature) * 1.0e9, temperature)
+            None => break,
+        };
+
+        let emitted = throughput * hit_rec.material.emitted(&ray, &hit_rec);
+        if emitted != 0.0 && !emitted.is_nan() {
+            let light_path = match first_event {
+                None => LightPath::Emission,
+                Some(FirstEvent::Specular) => LightPath::Specular,
+                Some(FirstEvent::Volume) => LightPath::Volume,
+                Some(FirstEvent::Surface) if non_specular_bounces == 1 => LightPath::DirectDiffuse,
+                Some(FirstEvent::Surface) => LightPath::Indirect,
+            };
+            sample.radiance += emitted;
+            sample.contributions.push(PathContribution {
+                radiance: emitted,
+                light_path,
+                object_id: hit_rec.object_id,
+            });
+        }
+
+        let scatter = hit_rec.material.scatter(&ray, &hit_rec, sampler);
+
+        if let Some(first_hit) = first_hit.take() {
+            let attenuation = match &scatter {
+                Some(ScatterRecord::Diffuse { attenuation, .. }) => *attenuation,
+                Some(ScatterRecord::Specular { attenuation, .. }) => *attenuation,
+                None => 0.0,
+            };
+            *first_hit = FirstHit {
+                normal: hit_rec.normal.normalize(),
+                depth: hit_rec.t * ray.direction.magnitude(),
+                position: hit_rec.p,
+                albedo: attenuation * get_tristimulus(ray.wavelength) / cie_y_integral(),
+                object_id: hit_rec.object_id,
+                material_id: hit_rec.material_id,
+            };
+        }
+
+        match scatter {
+            Some(ScatterRecord::Diffuse { attenuation, pdf }) => {
+                let attractors_pdf: Box<dyn Pdf<Vec3>> =
+                    Box::new(attractors.generate_mixture_pdf(hit_rec.p, ray.time));
+                let mixture_pdf = MixturePdf::new_power(vec![attractors_pdf, pdf], 2.0);
+                let scattered_ray = Ray {
+                    origin: hit_rec.p,
+                    direction: mixture_pdf.sample(sampler),
+                    wavelength: ray.wavelength,
+                    time: ray.time,
+                    medium_sample: ray.medium_sample,
+                };
+                let pdf_val = mixture_pdf.value(scattered_ray.direction);
+                if pdf_val == 0.0 {
+                    break;
+                }
+                throughput *= attenuation
+                    * hit_rec.material.scattering_pdf(&scattered_ray, &hit_rec)
+                    / pdf_val;
+                if throughput.is_nan() {
+                    break;
+                }
+                if first_event.is_none() {
+                    first_event = Some(if hit_rec.material.is_solid() {
+                        FirstEvent::Surface
+                    } else {
+                        FirstEvent::Volume
+                    });
+                }
+                non_specular_bounces += 1;
+                ray = scattered_ray;
+            }
+            Some(ScatterRecord::Specular {
+                attenuation,
+                ray: specular_ray,
+            }) => {
+                throughput *= attenuation;
+                if first_event.is_none() {
+                    first_event = Some(FirstEvent::Specular);
+                }
+                ray = specular_ray;
+            }
+            None => break,
+        }
+    }
+
+    sample
+}
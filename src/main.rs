@@ -1,178 +1,488 @@
 #[macro_use]
 extern crate smallvec;
 
+mod adaptive;
+mod animation;
+mod bench;
 mod camera;
+mod checkpoint;
 mod color;
+mod colorspace;
 mod constants;
+mod controls;
+mod denoise;
+mod film;
+mod filter;
 mod geometry;
+mod integrator;
 mod material;
 mod mc;
 mod output;
 mod pdf;
 mod ray;
+mod sampler;
 mod scenes;
+mod sequence;
+mod tile;
+mod tonemap;
 mod vector;
 
-use minifb::{Key, ScaleMode, Window, WindowOptions};
-use rand::random;
+use minifb::{Key, MouseButton, MouseMode, ScaleMode, Window, WindowOptions};
 use rayon::prelude::*;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::color::{cie_to_rgb, find_exposure, get_tristimulus};
-use crate::geometry::{bvh::BVHNode, sphere::Sphere, Geometry, HittableList};
-use crate::material::{ScatterRecord};
-use crate::output::{get_next_output_image_name, write_exr_xyz, write_png};
-use crate::pdf::{GeometryPdf, MixturePdf, Pdf};
-use crate::ray::Ray;
+use crate::adaptive::AdaptiveSampling;
+use crate::animation::Animation;
+use crate::camera::ApertureShape;
+use crate::checkpoint::{render_fingerprint, save_checkpoint, Checkpoint};
+use crate::controls::{CameraHome, Controls, PreviewView, HELP};
+use crate::colorspace::{ChromaticAdaptation, ColorSpace, Illuminant, WhiteBalance, WhitePoint};
+use crate::denoise::Denoiser;
+use crate::film::{AovBuffers, Film, LightLayers, SpectralBuffer, TileFilm};
+use crate::filter::{BoxFilter, Filter};
+use crate::integrator::render_sample;
+use crate::output::{
+    get_next_output_image_name, write_envi, write_exr, write_exr_spectral, write_png,
+    write_sample_heatmap, SpectralFormat,
+};
+use crate::sampler::{Sampler, SamplerKind};
+use crate::sequence::Sequence;
+use crate::tile::{draw_rect_outline, draw_tile_marker, tiles, Rect};
+use crate::tonemap::{Clamp, DisplayTransform, Exposure, ToneMapper};
 use crate::vector::Vec3;
 
 fn main() {
+    if std::env::args().any(|arg| arg == "--bench-bvh") {
+        bench::bvh();
+        return;
+    }
+
     let width = 500;
     let height = 500;
     let samples = 2000;
-    let exposure_compensation = 1.0;
+    let tile_size = 32;
+    // Renders only this part of the frame, e.g. `Some(Rect { x0: 150, y0: 150, x1: 350, y1: 350 })`.
+    // The EXR keeps the full resolution and stores the crop as its data window,
+    // the ENVI cube only holds the crop. It is clipped to the image.
+    let crop: Option<Rect> = None;
+    let crop = crop.map(|crop| {
+        crop.intersect(&Rect::new(width, height))
+            .unwrap_or_else(|| panic!("The crop {:?} misses the {}x{} image", crop, width, height))
+    });
+    // `--filter <name>` picks the pixel reconstruction filter, e.g. `gaussian`
+    // or `mitchell`. The default box of radius 0.5 is a plain pixel mean.
+    let filter: Box<dyn Filter> = parsed_argument(
+        "--filter",
+        filter::find,
+        "box, tent, gaussian, mitchell, blackman-harris",
+    )
+    .unwrap_or_else(|| Box::new(BoxFilter { radius: 0.5 }));
+    // `--tonemap <name>` compresses the highlights with `reinhard`, `aces`,
+    // `agx` or `hable` instead of clipping them.
+    let tone_mapper: Box<dyn ToneMapper> =
+        parsed_argument("--tonemap", tonemap::find, "clamp, reinhard, aces, agx, hable")
+            .unwrap_or_else(|| Box::new(Clamp));
+    // `--white <white>` picks what comes out neutral, e.g. `2000` for a
+    // 2000 K black body, an illuminant such as `d50` or the pixel `250,300`.
+    // `--adaptation <name>` is the chromatic adaptation transform to it.
+    let white_balance = WhiteBalance::new(
+        parsed_argument(
+            "--white",
+            WhitePoint::parse,
+            "a temperature in kelvin, a, d50, d55, d65, d75, e, f2, f7, f11 or a pixel x,y",
+        )
+        .unwrap_or(WhitePoint::Illuminant(Illuminant::D65)),
+        parsed_argument(
+            "--adaptation",
+            ChromaticAdaptation::parse,
+            "bradford, von-kries, cat02, xyz-scaling, none",
+        )
+        .unwrap_or(ChromaticAdaptation::Bradford),
+    );
+    let mut display = DisplayTransform {
+        white_balance,
+        ..DisplayTransform::new(Exposure::Auto(0.0), tone_mapper)
+    };
+    // `--color-space <name>` writes the EXR in `linear-srgb`, `rec2020`,
+    // `display-p3`, `acescg` or `aces2065-1` instead of XYZ.
+    let output_color_space = parsed_argument(
+        "--color-space",
+        ColorSpace::parse,
+        "xyz, linear-srgb, rec2020, display-p3, acescg, aces2065-1",
+    )
+    .unwrap_or(ColorSpace::Xyz);
+    // `--spectral exr|envi` writes the spectral radiance, e.g. 31 bins of
+    // 10 nm, next to the XYZ output.
+    let spectral_output = parsed_argument("--spectral", SpectralFormat::parse, "exr, envi");
+    let spectral_bins = 31;
+    let spectral_range = 400.0..710.0;
+    // Writes normal, depth, position, albedo and id layers into the EXR.
+    let aov_output = true;
+    // Writes the beauty split per light path expression and per light in the attractors.
+    let light_layers_output = true;
+    // Denoises the XYZ buffer before tone mapping, guided by the AOVs when enabled.
+    // Only the beauty is denoised, the light layers stay as rendered.
+    let denoiser = Denoiser::default();
+    let denoise_preview = false;
+    let denoise_output = false;
+    // Stops sampling converged pixels, `samples` becomes the maximum per pixel.
+    let adaptive_sampling: Option<AdaptiveSampling> = None;
+    // Saves the film every `checkpoint_interval` passes and when the render
+    // stops. A run with `--resume` continues from it if it is of the same
+    // render and not finished yet.
+    let checkpoint_path: Option<&str> = Some("output/checkpoint/render.ckpt");
+    let checkpoint_interval = 50;
+    // Renders with the same seed are bit identical, whatever the thread count.
+    let seed: u64 = 0;
+    // `--sampler <name>`: low discrepancy `sobol` converges fastest,
+    // `stratified` and `halton` are alternatives, `blue-noise` spreads the
+    // error of low sample counts as blue noise and `independent` is plain
+    // random sampling.
+    let sampler_kind = parsed_argument(
+        "--sampler",
+        SamplerKind::parse,
+        "independent, stratified, halton, sobol, blue-noise",
+    )
+    .unwrap_or(SamplerKind::Sobol);
+    // `--aperture <shape>` sets the lens of the still render to a `circle`
+    // or a `hexagon`, which shapes the bokeh.
+    let aperture_shape = parsed_argument("--aperture", ApertureShape::parse, "circle, hexagon");
+    // Samples per pixel of every frame of a `--sequence` render.
+    let sequence_samples = 128;
+
+    // `--sequence [script]` renders every frame of an animation without the
+    // preview window, see `Animation` for the script format. Without a
+    // script `scenes::animated::SCRIPT` is played.
+    let mut args = std::env::args().skip_while(|arg| arg != "--sequence");
+    if args.next().is_some() {
+        let animation = match args.next() {
+            Some(path) => Animation::load(&path),
+            None => Animation::parse(scenes::animated::SCRIPT),
+        }
+        .unwrap_or_else(|e| panic!("{}", e));
+        let sequence = Sequence {
+            width,
+            height,
+            samples: sequence_samples,
+            tile_size,
+            filter: &*filter,
+            sampler: &*sampler_kind.create(sequence_samples, seed),
+            output_color_space,
+        };
+        sequence.render(&animation, scenes::animated::scene, &mut display);
+        return;
+    }
+
+    // `--scene <name>` renders one of `scenes::SCENES`: `instances` has ten
+    // thousand instances of one BVH, `motion` shows motion blur, `lenses`
+    // glass parts made with CSG, `sdf` distance fields and `shapes` the
+    // quadric and torus primitives.
+    let scene_name = argument_value("--scene").unwrap_or_else(|| String::from(scenes::SCENES[0].0));
+    let scene = scenes::find(&scene_name).unwrap_or_else(|| {
+        let names = scenes::SCENES.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        panic!("Unknown scene {}, expected one of {}", scene_name, names.join(", "))
+    });
 
     let mut win = window(width, height);
-    let mut win_buffer: Vec<u32>;
-    let mut tristimulus_buffer: Vec<Vec3> = vec![Vec3::zeros(); (width * height) as usize];
-
-    let (world, attractors, camera) = scenes::lights::scene(width, height);
-
-    for n in 0..samples {
-        tristimulus_buffer = (0..height)
-            .into_par_iter()
-            .flat_map(|y| {
-                (0..width)
-                    .map(|x| {
-                        let u = (x as f32 + random::<f32>()) / width as f32;
-                        let v = (height as f32 - (y as f32 + random::<f32>())) / height as f32;
-
-                        let (ray, ray_pdf) = camera.get_ray_tri(u, v);
-                        let tristimulus_value =
-                            ray_tristimulus(&ray, &world, &attractors, 50) / ray_pdf;
-
-                        let offset = (y * width + x) as usize;
-
-                        if n > 0 {
-                            running_mean(&tristimulus_buffer[offset], &tristimulus_value, n)
-                        } else {
-                            tristimulus_value
-                        }
-                    })
-                    .collect::<Vec<Vec3>>()
-            })
-            .collect::<Vec<Vec3>>();
-
-        println!("Samples per pixel: {}", n);
-        let max_intensity = find_exposure(&tristimulus_buffer) * exposure_compensation;
-        let ln_4 = 4.0f32.ln();
-
-        win_buffer = tristimulus_buffer
-            .iter()
-            .map(|tri| {
-                let tri_scaled =
-                    tri / max_intensity + Vec3::new(1.0, 1.0, 1.0).map(|v| v.ln()) / ln_4;
-                (cie_to_rgb(&tri_scaled) * 255.99).map(|v| v as u8)
-            })
-            .map(|v| ((v.x as u32) << 16) | ((v.y as u32) << 8) | v.z as u32)
-            .collect();
-
-        let mut paused = false;
-        let mut exit = false;
-
-        loop {
-            win.update_with_buffer(&win_buffer, width, height).unwrap();
 
-            if !win.is_open() || win.is_key_down(Key::Escape) || win.is_key_released(Key::Escape) {
-                exit = true;
-                paused = false;
+    let (world, attractors, mut camera) = scene(width, height);
+    if let Some(shape) = aperture_shape {
+        camera.aperture_shape = shape;
+    }
+    let camera_home = CameraHome::new(&camera);
+
+    let mut film = Film::new(width, height);
+    film.spectral = spectral_output
+        .as_ref()
+        .map(|_| SpectralBuffer::new(width * height, spectral_bins, spectral_range));
+    if aov_output {
+        film.aovs = Some(AovBuffers::new(width * height));
+    }
+    if light_layers_output {
+        let light_ids = attractors.objects.iter().map(|light| light.object_id()).collect();
+        film.light_layers = Some(LightLayers::new(width * height, light_ids));
+    }
+
+    let fingerprint =
+        render_fingerprint(&scene_name, &*world, &camera, sampler_kind, &*filter, seed, samples);
+    let resume = std::env::args().any(|arg| arg == "--resume");
+    let mut first_pass = 0;
+    if let Some(path) = checkpoint_path.filter(|path| resume && Path::new(path).exists()) {
+        match Checkpoint::load(path) {
+            Ok(checkpoint) if !checkpoint.matches(&film, fingerprint) => {
+                println!("Ignoring {}, it is of a different render", path)
+            }
+            Ok(checkpoint) if checkpoint.passes >= samples => {
+                println!("Ignoring {}, its render is already finished", path)
+            }
+            Ok(checkpoint) => {
+                println!("Resuming from {} after {} passes", path, checkpoint.passes);
+                first_pass = checkpoint.passes;
+                film = checkpoint.film;
+            }
+            Err(e) => println!("Ignoring {}: {}", path, e),
+        }
+    }
+    let mut passes = first_pass;
+
+    let sampler: Box<dyn Sampler> = sampler_kind.create(samples, seed);
+
+    let mut exit = false;
+    // Dragging with shift in the preview limits the samples to a region of
+    // interest inside the crop, a click without dragging clears it.
+    let mut region_of_interest: Option<Rect> = None;
+    let mut drag_start: Option<(usize, usize)> = None;
+    println!("{}", HELP);
+    let mut controls = Controls::new();
+    let has_aovs = film.aovs.is_some();
+    // The checkpoint only matches the camera of the scene.
+    let mut camera_moved = false;
+    // Every tile renders into a film of its own, reused while the tiles
+    // stay the same.
+    let mut tile_films: Vec<Mutex<TileFilm>> = Vec::new();
+
+    let mut n = first_pass;
+    while n < samples {
+        while controls.paused && controls.camera.is_empty() && !exit {
+            let beauty = film.tristimulus();
+            let win_buffer =
+                preview_buffer(&film, &beauty, &display, controls.view, region_of_interest.as_ref());
+            win.update_with_buffer(&win_buffer, width, height).unwrap();
+            controls.update(&win, &mut display.exposure, has_aovs);
+            update_region_of_interest(&win, width, height, &mut drag_start, &mut region_of_interest);
+            exit |= exit_requested(&win);
+        }
+        if exit {
+            break;
+        }
+        if !controls.camera.is_empty() {
+            std::mem::take(&mut controls.camera).apply(&mut camera, &camera_home);
+            controls.paused = false;
+            film.clear();
+            n = 0;
+            passes = 0;
+            if checkpoint_path.is_some() && !camera_moved {
+                println!("The camera moved, no longer saving checkpoints");
             }
+            camera_moved = true;
+        }
+
+        let active_pixels = adaptive_sampling
+            .as_ref()
+            .map(|adaptive| adaptive.active_pixels(&film));
+        let crop_region = crop.unwrap_or_else(|| Rect::new(width, height));
+        let region = region_of_interest
+            .and_then(|roi| roi.intersect(&crop_region))
+            .unwrap_or(crop_region);
+        let active_count = region
+            .pixels()
+            .filter(|(x, y)| active_pixels.as_ref().is_none_or(|active| active[y * width + x]))
+            .count();
+        if active_count == 0 {
+            println!("All pixels converged after {} passes", n);
+            // Nothing is left to resume.
+            passes = samples;
+            break;
+        }
+
+        let tiles = tiles(&region, tile_size);
+        if !tile_films.iter().map(|t| t.lock().unwrap().tile).eq(tiles.iter().copied()) {
+            tile_films = tiles
+                .iter()
+                .map(|tile| Mutex::new(TileFilm::new(&film, *tile, &*filter)))
+                .collect();
+        }
+        let tile_started = tiles.iter().map(|_| AtomicBool::new(false)).collect::<Vec<_>>();
+        // Set when the camera moves, the remaining tiles are skipped.
+        let cancel = AtomicBool::new(false);
+        let (sender, receiver) = channel();
+
+        thread::scope(|scope| {
+            // The window has to stay on the main thread, the tiles render on
+            // the rayon pool.
+            scope.spawn(|| {
+                tiles.par_iter().enumerate().for_each_with(sender, |sender, (i, tile)| {
+                    if cancel.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    tile_started[i].store(true, Ordering::Relaxed);
+                    let mut tile_film = tile_films[i].lock().unwrap();
+                    tile_film.film.clear();
+                    for (x, y) in tile.pixels() {
+                        if active_pixels.as_ref().is_none_or(|active| active[y * width + x]) {
+                            let sample = render_sample(
+                                &camera,
+                                &*world,
+                                &attractors,
+                                &*sampler,
+                                (width, height),
+                                (x, y),
+                                n,
+                            );
+                            tile_film.add_sample(sample, &*filter);
+                        }
+                    }
+                    sender.send(i).unwrap();
+                });
+            });
+
+            // Tiles are added to the film in order, whatever order they finish
+            // in, so the overlapping borders stay deterministic.
+            let mut finished = vec![false; tiles.len()];
+            let mut next_tile = 0;
+            let mut last_preview = Instant::now();
+            while next_tile < tiles.len() {
+                let disconnected = match receiver.recv_timeout(Duration::from_millis(16)) {
+                    Ok(i) => {
+                        finished[i] = true;
+                        false
+                    }
+                    Err(RecvTimeoutError::Timeout) => false,
+                    Err(RecvTimeoutError::Disconnected) => true,
+                };
+                while next_tile < tiles.len() && finished[next_tile] {
+                    film.add_tile(&tile_films[next_tile].lock().unwrap());
+                    next_tile += 1;
+                }
+                if disconnected {
+                    break;
+                }
 
-            if win.is_key_down(Key::S) || win.is_key_released(Key::S) {
-                exit = true;
-                paused = false;
+                if last_preview.elapsed() > Duration::from_millis(100) {
+                    let beauty = film.tristimulus();
+                    let mut win_buffer = preview_buffer(
+                        &film,
+                        &beauty,
+                        &display,
+                        controls.view,
+                        region_of_interest.as_ref(),
+                    );
+                    for (i, tile) in tiles.iter().enumerate().skip(next_tile) {
+                        if tile_started[i].load(Ordering::Relaxed) {
+                            draw_tile_marker(&mut win_buffer, width, tile);
+                        }
+                    }
+                    win.update_with_buffer(&win_buffer, width, height).unwrap();
+                    exit |= exit_requested(&win);
+                    controls.update(&win, &mut display.exposure, has_aovs);
+                    update_region_of_interest(&win, width, height, &mut drag_start, &mut region_of_interest);
+                    if !controls.camera.is_empty() {
+                        cancel.store(true, Ordering::Relaxed);
+                    }
+                    last_preview = Instant::now();
+                }
             }
+        });
+        // The film is cleared before the next pass.
+        if cancel.into_inner() {
+            continue;
+        }
 
-            if !paused {
-                break;
+        passes = n + 1;
+        if let Some(path) = checkpoint_path.filter(|_| !camera_moved) {
+            if passes % checkpoint_interval == 0 {
+                save_checkpoint(path, &film, passes, fingerprint).unwrap();
             }
         }
-        if exit == true {
+
+        println!("Samples per pixel: {}, active pixels: {}", n, active_count);
+        let mut beauty = film.tristimulus();
+        if denoise_preview && controls.view == PreviewView::Beauty {
+            beauty = denoiser.denoise(
+                &beauty,
+                &film.variance_of_mean(),
+                film.aov_means().as_ref(),
+                width,
+                height,
+            );
+        }
+        let win_buffer =
+            preview_buffer(&film, &beauty, &display, controls.view, region_of_interest.as_ref());
+        win.update_with_buffer(&win_buffer, width, height).unwrap();
+        controls.update(&win, &mut display.exposure, has_aovs);
+        update_region_of_interest(&win, width, height, &mut drag_start, &mut region_of_interest);
+        // Finishes the pass in flight, so the checkpoint only holds whole passes.
+        if exit || exit_requested(&win) {
             break;
         }
+        n += 1;
     }
 
-    let image_name_base = &*get_next_output_image_name("output/png/").unwrap();
-    write_exr_xyz(
-        &tristimulus_buffer,
-        width,
-        height,
+    if let Some(path) = checkpoint_path.filter(|_| !camera_moved) {
+        save_checkpoint(path, &film, passes, fingerprint).unwrap();
+    }
+
+    let mut beauty = film.tristimulus();
+    if denoise_output {
+        beauty = denoiser.denoise(
+            &beauty,
+            &film.variance_of_mean(),
+            film.aov_means().as_ref(),
+            width,
+            height,
+        );
+    }
+
+    let image_name_base = &*get_next_output_image_name("output/png/");
+    write_exr(
+        &film,
+        &beauty,
+        output_color_space,
+        &display.white_balance,
+        crop.as_ref(),
         format!("output/exr/{}.exr", image_name_base),
     );
     write_png(
-        &tristimulus_buffer,
+        &beauty,
         width,
         height,
-        exposure_compensation,
+        &display,
         format!("output/png/{}.png", image_name_base),
     );
-}
-
-fn ray_tristimulus<'a>(
-    ray: &Ray,
-    world: &Box<dyn Geometry>,
-    attractors: &'a HittableList,
-    depth: u32,
-) -> Vec3 {
-    if depth <= 0 {
-        return Vec3::zeros();
+    if adaptive_sampling.is_some() {
+        write_sample_heatmap(
+            &film,
+            format!("output/samples/{}.png", image_name_base),
+        );
     }
-
-    if let Some(hit_rec) = world.hit(&ray, 0.001, f32::MAX) {
-        let emitted_intensity = hit_rec.material.emitted(&ray, &hit_rec);
-        let emitted = emitted_intensity * get_tristimulus(ray.wavelength);
-
-        if let Some(scatter_record) = hit_rec.material.scatter(&ray, &hit_rec) {
-            match scatter_record {
-                ScatterRecord::Diffuse { attenuation, pdf } => {
-                    let attractors_pdf: Box<dyn Pdf<Vec3>> =
-                        Box::new(attractors.generate_mixture_pdf(hit_rec.p));
-                    let mixture_pdf = MixturePdf::new_power(vec![attractors_pdf, pdf], 2.0);
-                    let scattered_ray = Ray {
-                        origin: hit_rec.p,
-                        direction: mixture_pdf.sample(),
-                        wavelength: ray.wavelength,
-                    };
-                    let pdf_val = mixture_pdf.value(scattered_ray.direction);
-                    if pdf_val == 0.0 {
-                        return Vec3::zeros();
-                    }
-                    let tri = emitted
-                        + attenuation
-                            * hit_rec.material.scattering_pdf(&scattered_ray, &hit_rec)
-                            * &ray_tristimulus(&scattered_ray, world, attractors, depth - 1)
-                            / pdf_val;
-                    if tri.x.is_nan() {
-                        Vec3::zeros()
-                    } else {
-                        tri
-                    }
-                }
-                ScatterRecord::Specular {
-                    attenuation,
-                    ray: specular_ray,
-                } => attenuation * &ray_tristimulus(&specular_ray, world, attractors, depth - 1),
-            }
-        } else {
-            emitted
+    if let (Some(format), Some(spectral_buffer)) = (&spectral_output, &film.spectral_means()) {
+        match format {
+            SpectralFormat::Exr => write_exr_spectral(
+                spectral_buffer,
+                width,
+                height,
+                crop.as_ref(),
+                format!("output/exr/{}_spectral.exr", image_name_base),
+            ),
+            SpectralFormat::Envi => write_envi(
+                spectral_buffer,
+                width,
+                height,
+                crop.as_ref(),
+                format!("output/envi/{}", image_name_base),
+            ),
         }
-        
-    } else {
-        // let temperature = 6500.0;
-        // get_tristimulus(ray.wavelength) * boltzmann(ray.wavelength, temperature) / boltzmann((WIENS_CONSTANT / temperature) * 1.0e9, temperature)
-        Vec3::zeros()
     }
 }
 
+/// The argument following `flag` on the command line.
+fn argument_value(flag: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != flag).nth(1)
+}
+
+/// The argument following `flag`, panics listing the `expected` values if
+/// `parse` does not know it.
+fn parsed_argument<T>(flag: &str, parse: impl Fn(&str) -> Option<T>, expected: &str) -> Option<T> {
+    argument_value(flag).map(|value| {
+        parse(&value).unwrap_or_else(|| panic!("Unknown {} {}, expected one of {}", flag, value, expected))
+    })
+}
+
 fn window(width: usize, height: usize) -> Window {
     let mut window = Window::new(
         "Maxwell",
@@ -191,6 +501,71 @@ fn window(width: usize, height: usize) -> Window {
     window
 }
 
-fn running_mean(last_mean: &Vec3, new_value: &Vec3, n: u32) -> Vec3 {
-    last_mean + (new_value - last_mean) / (n + 1) as f32
+fn exit_requested(win: &Window) -> bool {
+    !win.is_open()
+        || win.is_key_down(Key::Escape)
+        || win.is_key_released(Key::Escape)
+        || win.is_key_down(Key::S)
+        || win.is_key_released(Key::S)
+}
+
+/// The image pixel under the mouse, the preview is scaled to fit the window
+/// with its aspect ratio kept.
+fn mouse_pixel(win: &Window, width: usize, height: usize) -> Option<(usize, usize)> {
+    let (mouse_x, mouse_y) = win.get_unscaled_mouse_pos(MouseMode::Clamp)?;
+    let (window_width, window_height) = win.get_size();
+    let scale = (window_width as f32 / width as f32).min(window_height as f32 / height as f32);
+    let x = (mouse_x - (window_width as f32 - width as f32 * scale) / 2.0) / scale;
+    let y = (mouse_y - (window_height as f32 - height as f32 * scale) / 2.0) / scale;
+    Some((
+        (x.max(0.0) as usize).min(width - 1),
+        (y.max(0.0) as usize).min(height - 1),
+    ))
+}
+
+fn update_region_of_interest(
+    win: &Window,
+    width: usize,
+    height: usize,
+    drag_start: &mut Option<(usize, usize)>,
+    region_of_interest: &mut Option<Rect>,
+) {
+    let shift = win.is_key_down(Key::LeftShift) || win.is_key_down(Key::RightShift);
+    let mouse = mouse_pixel(win, width, height);
+    match (win.get_mouse_down(MouseButton::Left), *drag_start, mouse) {
+        (true, None, Some(pixel)) if shift => *drag_start = Some(pixel),
+        (false, Some(start), Some(end)) => {
+            *drag_start = None;
+            *region_of_interest = if start == end {
+                None
+            } else {
+                Some(Rect::from_corners(start, end))
+            };
+        }
+        (false, Some(_), None) => *drag_start = None,
+        _ => {}
+    }
+}
+
+/// The preview of the current view with the region of interest outlined.
+fn preview_buffer(
+    film: &Film,
+    beauty: &Vec<Vec3>,
+    display: &DisplayTransform,
+    view: PreviewView,
+    region_of_interest: Option<&Rect>,
+) -> Vec<u32> {
+    let mut buffer = window_buffer(&view.rgb(film, beauty, display));
+    if let Some(roi) = region_of_interest {
+        draw_rect_outline(&mut buffer, film.width, roi, 0xffaa00);
+    }
+    buffer
+}
+
+/// Packs display encoded RGB into the 0RGB pixels of the window.
+fn window_buffer(rgb: &[Vec3]) -> Vec<u32> {
+    rgb.iter()
+        .map(|rgb| (rgb * 255.99).map(|v| v as u8))
+        .map(|v| ((v.x as u32) << 16) | ((v.y as u32) << 8) | v.z as u32)
+        .collect()
 }
@@ -1,10 +1,6 @@
-use std::f32::consts::PI;
-use rand::random;
-
 use crate::material::{Material, HitRecord, ScatterRecord, reflect, refract, schlick};
 use crate::ray::Ray;
-use crate::pdf::{Pdf};
-use crate::vector::{Vec3, onb_local};
+use crate::sampler::Sampler;
 
 #[derive(Clone)]
 pub struct Sf10Glass;
@@ -24,7 +20,7 @@ impl Sf10Glass {
 }
 
 impl Material for Sf10Glass {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
         let ior = Sf10Glass::get_index_of_refraction(ray.wavelength);
         let mut normal = hit.normal;
 
@@ -41,15 +37,15 @@ impl Material for Sf10Glass {
 
         let scattered = if etai_over_etat * sin_theta > 1.0 {
             let reflected = reflect(&unit_direction, &normal);
-            Ray::new(hit.p, reflected, ray.wavelength)
+            Ray::new(hit.p, reflected, ray.wavelength, ray.time)
         } else {
             let reflect_prob = schlick(cos_theta, etai_over_etat);
-            let refracted_or_reflected = if random::<f32>() < reflect_prob  {
+            let refracted_or_reflected = if sampler.next_f32() < reflect_prob  {
                 reflect(&unit_direction, &normal)               
             } else {
                 refract(&unit_direction, &normal, etai_over_etat)
             };
-            Ray::new(hit.p, refracted_or_reflected, ray.wavelength)
+            Ray::new(hit.p, refracted_or_reflected, ray.wavelength, ray.time)
         };
 
         Some(ScatterRecord::Specular {
@@ -1,13 +1,6 @@
-use crate::constants::{BOLTZMANNS_CONSTANT, PLANCKS_CONSTANT, SPEED_OF_LIGHT, WIENS_CONSTANT};
 use crate::geometry::HitRecord;
-use crate::material::{
-    color::Reflectance,
-    lambertian::Lambertian,
-    spectrum::{BlackBody, Spectrum},
-    Material, ScatterRecord,
-};
+use crate::material::{spectrum::Spectrum, Material};
 use crate::ray::Ray;
-use crate::vector::deg_to_rad;
 
 #[derive(Clone)]
 pub struct DiffuseEmissive {
@@ -19,7 +12,7 @@ impl DiffuseEmissive {
     pub fn new(spectrum: Box<dyn Spectrum>, intensity: f32) -> Self {
         DiffuseEmissive {
             spectrum,
-            intensity: intensity,
+            intensity,
         }
     }
 }
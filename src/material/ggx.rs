@@ -1,11 +1,12 @@
-use rand::random;
 use std::f32::consts::PI;
 
 use crate::material::{reflect, HitRecord, Material, ScatterRecord};
 use crate::pdf::Pdf;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vector::{onb_local, Vec3};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone)]
 pub struct GGX {
     pub reflectance: f32,
@@ -13,11 +14,11 @@ pub struct GGX {
 }
 
 impl Material for GGX {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
         if self.roughness < 0.04 {
             // Treat as perfectly specular/mirror
             let reflected = reflect(&ray.direction.normalize(), &hit.normal);
-            let specular_ray = Ray::new(hit.p, reflected, ray.wavelength);
+            let specular_ray = Ray::new(hit.p, reflected, ray.wavelength, ray.time);
             Some(ScatterRecord::Specular {
                 attenuation: self.reflectance,
                 ray: specular_ray,
@@ -52,10 +53,10 @@ impl Pdf<Vec3> for GGXPdf {
         let cosine_theta = direction.normalize().dot(&self.w);
         ggx_pdf_value(cosine_theta, self.roughness)
     }
-    fn sample(&self) -> Vec3 {
+    fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
         onb_local(
             &self.w,
-            &random_ggx_direction(self.roughness * self.roughness),
+            &random_ggx_direction(self.roughness * self.roughness, sampler),
         )
     }
 }
@@ -71,9 +72,9 @@ fn ggx_pdf_value(cosine_theta: f32, alpha:f32) -> f32 {
     }
 }
 
-pub fn random_ggx_direction(alpha_squared: f32) -> Vec3 {
-    let r1 = random::<f32>();
-    let r2 = random::<f32>();
+pub fn random_ggx_direction(alpha_squared: f32, sampler: &mut dyn Sampler) -> Vec3 {
+    let r = sampler.next_2d();
+    let (r1, r2) = (r.x, r.y);
 
     let z = ((1.0 - r2) / (r2 * (alpha_squared - 1.0) + 1.0)).sqrt();
     let sine = (1.0 - z * z).sqrt();
@@ -3,6 +3,8 @@ use std::f32::consts::PI;
 use crate::material::{Material, HitRecord, ScatterRecord};
 use crate::pdf::UniformPdf;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
+
 #[derive(Clone)]
 pub struct Isotropic {
     // pub albedo: Box<dyn Texture>,
@@ -10,7 +12,7 @@ pub struct Isotropic {
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, _ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, _ray: &Ray, _hit: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
         let pdf = UniformPdf {};
         Some(ScatterRecord::Diffuse {
             // attenuation: self.albedo.value(hit.uv, hit.p),
@@ -21,7 +23,7 @@ impl Material for Isotropic {
     fn scattering_pdf(&self, _ray_scatterd: &Ray, _hit: &HitRecord) -> f32 {
         1.0 / (4.0 * PI)
     }
-    // fn is_solid(&self) -> bool {
-    //     false
-    // }
+    fn is_solid(&self) -> bool {
+        false
+    }
 }
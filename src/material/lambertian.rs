@@ -3,6 +3,7 @@ use std::f32::consts::PI;
 use crate::material::{Material, HitRecord, ScatterRecord, color::Reflectance};
 use crate::pdf::CosinePdf;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 
 #[derive(Clone)]
 pub struct Lambertian {
@@ -10,9 +11,8 @@ pub struct Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterRecord> {
-        let w = hit.normal.normalize();
-        let pdf = CosinePdf { w };
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let pdf = CosinePdf::new(hit.normal);
 
         // let scatter_direction = hit.normal + random_unit_vec();
         // let scattered = Ray::new(hit.p, scatter_direction);
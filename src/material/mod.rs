@@ -12,6 +12,7 @@ use crate::geometry::HitRecord;
 use crate::vector::Vec3;
 use crate::ray::Ray;
 use crate::pdf::Pdf;
+use crate::sampler::Sampler;
 
 
 pub enum ScatterRecord {
@@ -25,7 +26,7 @@ pub enum ScatterRecord {
     },
 }
 pub trait Material: Sync + Send + DynClone {
-    fn scatter(&self, _ray: &Ray, _hit: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, _ray: &Ray, _hit: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
         // (Ray, Scatter direction, pdf)
         None
     }
@@ -35,6 +36,10 @@ pub trait Material: Sync + Send + DynClone {
     fn emitted(&self, _ray: &Ray, _hit: &HitRecord) -> f32 {
         0.0
     }
+    /// False for phase functions, which scatter inside a medium.
+    fn is_solid(&self) -> bool {
+        true
+    }
     // fn box_clone(&self) -> Box<dyn Material>;
 }
 dyn_clone::clone_trait_object!(Material);
@@ -48,11 +53,11 @@ dyn_clone::clone_trait_object!(Material);
 // }
 
 pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
-    v - 2.0 * v.dot(&n) * n
+    v - 2.0 * v.dot(n) * n
 }
 
 pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f32) -> Vec3 {
-    let cos_theta = (-uv).dot(&n).min(1.0);
+    let cos_theta = (-uv).dot(n).min(1.0);
     let r_out_parallel = etai_over_etat * (uv + cos_theta * n);
     let r_out_perp = -(1.0 - r_out_parallel.magnitude_squared()).sqrt() * n;
     r_out_parallel + r_out_perp
@@ -2,7 +2,7 @@ use dyn_clone::DynClone;
 use crate::constants::{BOLTZMANNS_CONSTANT, SPEED_OF_LIGHT, PLANCKS_CONSTANT, WIENS_CONSTANT};
 
 pub trait Spectrum: Sync + Send + DynClone {
-    fn value(&self, wavelength: f32) -> f32 {
+    fn value(&self, _wavelength: f32) -> f32 {
         0.0
     }
 }
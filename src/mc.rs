@@ -1,10 +1,6 @@
-use rand::{random, Rng, thread_rng};
 use crate::color::{X, Y, Z};
 use crate::pdf::{Pdf1D, Pdf, MixturePdf};
-
-pub fn get_wavelength_uniform() -> f32 {
-    random::<f32>() * 400.0 + 380.0
-}
+use crate::sampler::Sampler;
 
 pub struct WavelengthSampler {
     pdf: Box<dyn Pdf<f32>>
@@ -22,18 +18,18 @@ impl WavelengthSampler {
         }
     }
 
-    pub fn get_wavelengths(&self) -> (f32, f32) {
+    pub fn get_wavelengths(&self, sampler: &mut dyn Sampler) -> (f32, f32) {
         // let mut rng = thread_rng();
         // (rng.gen_range(380.0, 780.0), 1.0)
-        sample_clamped(&self.pdf, 0.001)
+        sample_clamped(&*self.pdf, 0.001, sampler)
     }
 }
 
-fn sample_clamped(ws: &Box<dyn Pdf<f32>>, min_pdf: f32) -> (f32, f32) {
+fn sample_clamped(ws: &dyn Pdf<f32>, min_pdf: f32, sampler: &mut dyn Sampler) -> (f32, f32) {
     let mut wavelength;
     let mut pdf;
     loop {
-        wavelength = ws.sample();
+        wavelength = ws.sample(sampler);
         pdf = ws.value(wavelength);
         if pdf > min_pdf {
             break;
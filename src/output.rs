@@ -1,85 +1,327 @@
-use smallvec;
-
 use exr::image::simple::*;
 use exr::prelude::*;
-use std::{convert::TryInto, fs};
+use std::{convert::TryInto, fs, io::Write, path::Path};
 use image::{ImageBuffer, Rgb};
 
 use crate::vector::Vec3;
-use crate::color::{find_exposure, cie_to_rgb};
+use crate::colorspace::{ChromaticAdaptation, ColorSpace, WhiteBalance, WHITE_E};
+use crate::film::{Film, SpectralBuffer};
+use crate::tile::Rect;
+use crate::tonemap::DisplayTransform;
+
+/// Colour layers use R, G and B in every colour space, the chromaticities
+/// attribute tells what they hold.
+const RGB_CHANNELS: [&str; 3] = ["R", "G", "B"];
+
+pub enum SpectralFormat {
+    /// Multi-channel OpenEXR, one `S0.<wavelength>nm` channel per bin.
+    Exr,
+    /// ENVI hyperspectral cube, a `.hdr` header next to a band sequential `.img` file.
+    Envi,
+}
+
+impl SpectralFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "exr" => Some(SpectralFormat::Exr),
+            "envi" => Some(SpectralFormat::Envi),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `beauty`, the XYZ means of the film or a denoised version of
+/// them, with the other outputs of the film. With a `crop` only the pixels
+/// inside it are stored, as the data window of the full resolution image.
+pub fn write_exr(
+    film: &Film,
+    beauty: &Vec<Vec3>,
+    color_space: ColorSpace,
+    white_balance: &WhiteBalance,
+    crop: Option<&Rect>,
+    output_path: String,
+) {
+    let (width, height) = (film.width, film.height);
+    let from_xyz = white_balance.xyz_to_rgb(color_space, beauty, width);
+    let converted = beauty
+        .iter()
+        .map(|tri| from_xyz * tri)
+        .collect::<Vec<Vec3>>();
+
+    let mut beauty = vec3_layer("beauty", RGB_CHANNELS, &converted, (width, height));
+    let (white_x, white_y) = color_space.adopted_white();
+    beauty.attributes.adopted_neutral = Some(exr::math::Vec2(white_x, white_y));
+
+    let samples = Layer::new(
+        "samples".try_into().unwrap(),
+        (width, height),
+        smallvec![Channel::new("count".try_into().unwrap(), false, Samples::U32(film.sample_counts.clone()))],
+    );
+
+    let mut layers: Layers = smallvec![beauty, samples];
+
+    if let Some(aovs) = &film.aov_means() {
+        // The albedo of a grey surface is equal energy white, keep it neutral.
+        let from_albedo = color_space.adapted_xyz_to_rgb(WHITE_E, ChromaticAdaptation::Bradford);
+        let albedo = aovs.albedo.iter().map(|a| from_albedo * a).collect::<Vec<Vec3>>();
+        let ids = Layer::new(
+            "id".try_into().unwrap(),
+            (width, height),
+            smallvec![
+                Channel::new("object".try_into().unwrap(), false, Samples::U32(aovs.object_id.clone())),
+                Channel::new("material".try_into().unwrap(), false, Samples::U32(aovs.material_id.clone())),
+            ],
+        );
+        let depth = Layer::new(
+            "depth".try_into().unwrap(),
+            (width, height),
+            smallvec![Channel::new("Z".try_into().unwrap(), true, Samples::F32(aovs.depth.clone()))],
+        );
+
+        layers.push(vec3_layer("normal", ["X", "Y", "Z"], &aovs.normal, (width, height)));
+        layers.push(depth);
+        layers.push(vec3_layer("position", ["X", "Y", "Z"], &aovs.position, (width, height)));
+        layers.push(vec3_layer("albedo", RGB_CHANNELS, &albedo, (width, height)));
+        layers.push(ids);
+    }
+
+    if let Some(light_layers) = &film.light_layer_means() {
+        for (name, buffer) in light_layers.named_layers() {
+            let converted = buffer.iter().map(|tri| from_xyz * tri).collect::<Vec<Vec3>>();
+            layers.push(vec3_layer(&name, RGB_CHANNELS, &converted, (width, height)));
+        }
+    }
+
+    let layers = layers
+        .into_iter()
+        .map(|layer| match crop {
+            Some(crop) => crop_layer(layer, width, crop),
+            None => layer,
+        })
+        .map(|layer| {
+            layer
+                .with_compression(Compression::RLE)
+                .with_block_format(None, attributes::LineOrder::Increasing)
+        })
+        .collect();
+
+    let mut image = Image::new_from_layers(layers, IntRect::from_dimensions((width, height)));
+    image.attributes.chromaticities = Some(exr_chromaticities(color_space));
+    create_parent_dir(&output_path);
+    image
+        .write_to_file(output_path, write_options::high())
+        .unwrap();
+}
+
+fn vec3_layer(name: &str, channel_names: [&str; 3], buffer: &Vec<Vec3>, size: (usize, usize)) -> Layer {
+    let [x_name, y_name, z_name] = channel_names;
 
-pub fn write_exr_xyz(tristimulus_buffer: &Vec<Vec3>, width: usize, height: usize, output_path: String) {
     let x = Channel::new(
-        "R".try_into().unwrap(),
+        x_name.try_into().unwrap(),
         true,
-        Samples::F32(tristimulus_buffer.iter().map(|tri| tri.x).collect()),
+        Samples::F32(buffer.iter().map(|v| v.x).collect()),
     );
 
     let y = Channel::new(
-        "G".try_into().unwrap(),
+        y_name.try_into().unwrap(),
         true,
-        Samples::F32(tristimulus_buffer.iter().map(|tri| tri.y).collect()),
+        Samples::F32(buffer.iter().map(|v| v.y).collect()),
     );
 
     let z = Channel::new(
-        "B".try_into().unwrap(),
+        z_name.try_into().unwrap(),
         true,
-        Samples::F32(tristimulus_buffer.iter().map(|tri| tri.z).collect()),
+        Samples::F32(buffer.iter().map(|v| v.z).collect()),
     );
 
-    let layer = Layer::new(
-        "test-image".try_into().unwrap(),
+    Layer::new(name.try_into().unwrap(), size, smallvec![x, y, z])
+}
+
+/// Keeps the pixels inside `crop` and positions them with the data window.
+fn crop_layer(mut layer: Layer, width: usize, crop: &Rect) -> Layer {
+    fn crop_values<T: Copy>(values: &[T], width: usize, crop: &Rect) -> Vec<T> {
+        crop.pixels().map(|(x, y)| values[y * width + x]).collect()
+    }
+    for channel in layer.channels.iter_mut() {
+        channel.samples = match &channel.samples {
+            Samples::F16(values) => Samples::F16(crop_values(values, width, crop)),
+            Samples::F32(values) => Samples::F32(crop_values(values, width, crop)),
+            Samples::U32(values) => Samples::U32(crop_values(values, width, crop)),
+        };
+    }
+    layer.data_size = exr::math::Vec2(crop.width(), crop.height());
+    layer.attributes.data_position = exr::math::Vec2(crop.x0 as i32, crop.y0 as i32);
+    layer
+}
+
+fn exr_chromaticities(color_space: ColorSpace) -> attributes::Chromaticities {
+    let [red, green, blue] = color_space.primaries();
+    let white = color_space.white();
+    attributes::Chromaticities {
+        red: exr::math::Vec2(red.0, red.1),
+        green: exr::math::Vec2(green.0, green.1),
+        blue: exr::math::Vec2(blue.0, blue.1),
+        white: exr::math::Vec2(white.0, white.1),
+    }
+}
+
+pub fn write_exr_spectral(
+    spectral_buffer: &SpectralBuffer,
+    width: usize,
+    height: usize,
+    crop: Option<&Rect>,
+    output_path: String,
+) {
+    let channels = (0..spectral_buffer.bins)
+        .map(|bin| {
+            // Channel naming follows the spectral OpenEXR layout, which uses
+            // a comma as the decimal separator.
+            let wavelength = format!("{:.6}", spectral_buffer.bin_center(bin)).replace('.', ",");
+            let name = format!("S0.{}nm", wavelength);
+            Channel::new(
+                name.as_str().try_into().unwrap(),
+                true,
+                Samples::F32(spectral_buffer.band(bin)),
+            )
+        })
+        .collect();
+
+    let mut layer = Layer::new(
+        "spectral".try_into().unwrap(),
         (width, height),
-        smallvec![x, y, z],
+        channels,
+    );
+    layer.attributes.custom.insert(
+        "spectralLayoutVersion".try_into().unwrap(),
+        attributes::AttributeValue::Text("1.0".try_into().unwrap()),
+    );
+    layer.attributes.custom.insert(
+        "emissiveUnits".try_into().unwrap(),
+        attributes::AttributeValue::Text("W.m^-2.sr^-1".try_into().unwrap()),
     );
 
+    if let Some(crop) = crop {
+        layer = crop_layer(layer, width, crop);
+    }
     let layer = layer
         .with_compression(Compression::RLE)
         .with_block_format(None, attributes::LineOrder::Increasing);
 
-    let mut image = Image::new_from_single_layer(layer);
-    let chromaticities = exr::meta::attributes::Chromaticities {
-        red: exr::math::Vec2(1.0, 0.0),
-        green: exr::math::Vec2(0.0, 1.0),
-        blue: exr::math::Vec2(0.0, 0.0),
-        white: exr::math::Vec2(1.0 / 3.0, 1.0 / 3.0),
-    };
-    image.attributes.chromaticities = Some(chromaticities);
-    image
+    create_parent_dir(&output_path);
+    Image::new_from_layers(smallvec![layer], IntRect::from_dimensions((width, height)))
         .write_to_file(output_path, write_options::high())
         .unwrap();
 }
 
-pub fn write_png(tristimulus_buffer: &Vec<Vec3>, width: usize, height: usize, exposure_compensation: f32, output_path: String) {
-    let max_intensity = find_exposure(&tristimulus_buffer) * exposure_compensation;
-    let ln_4 = 4.0f32.ln();
-    let image_vec = tristimulus_buffer
+/// Writes an ENVI standard cube, `output_path` is the path without extension.
+/// With a `crop` only its pixels are written, its corner is kept as the
+/// header's image start.
+pub fn write_envi(
+    spectral_buffer: &SpectralBuffer,
+    width: usize,
+    height: usize,
+    crop: Option<&Rect>,
+    output_path: String,
+) {
+    let full = Rect::new(width, height);
+    let crop = crop.unwrap_or(&full);
+    let wavelengths = (0..spectral_buffer.bins)
+        .map(|bin| format!("{:.6}", spectral_buffer.bin_center(bin)))
+        .collect::<Vec<String>>();
+
+    let header = format!(
+        "ENVI\n\
+        description = {{Maxwell spectral render}}\n\
+        samples = {}\n\
+        lines = {}\n\
+        bands = {}\n\
+        header offset = 0\n\
+        file type = ENVI Standard\n\
+        data type = 4\n\
+        interleave = bsq\n\
+        byte order = 0\n\
+        x start = {}\n\
+        y start = {}\n\
+        wavelength units = Nanometers\n\
+        wavelength = {{{}}}\n",
+        crop.width(),
+        crop.height(),
+        spectral_buffer.bins,
+        crop.x0,
+        crop.y0,
+        wavelengths.join(", ")
+    );
+
+    create_parent_dir(&output_path);
+    fs::write(format!("{}.hdr", output_path), header).unwrap();
+
+    // Band sequential, little endian 32 bit floats.
+    let mut file = fs::File::create(format!("{}.img", output_path)).unwrap();
+    for bin in 0..spectral_buffer.bins {
+        let band = spectral_buffer.band(bin);
+        let bytes = crop
+            .pixels()
+            .flat_map(|(x, y)| band[y * width + x].to_le_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        file.write_all(&bytes).unwrap();
+    }
+}
+
+fn create_parent_dir(output_path: &str) {
+    if let Some(parent) = Path::new(output_path).parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+}
+
+pub fn write_png(tristimulus_buffer: &Vec<Vec3>, width: usize, height: usize, display: &DisplayTransform, output_path: String) {
+    let image_vec = display
+        .apply(tristimulus_buffer, width)
         .iter()
-        .map(|tri| {
-            let tri_scaled = tri / max_intensity + Vec3::new(1.0, 1.0, 1.0).map(|v| v.ln()) / ln_4;
-            (cie_to_rgb(&tri_scaled) * 255.99).map(|v| v as u8)
-        })
+        .map(|rgb| (rgb * 255.99).map(|v| v as u8))
         .flat_map(|v| vec![v.x, v.y, v.z])
         .collect::<Vec<u8>>();
         // .map(|v| image::Rgb([v.x, v.y, v.z]))
         // .collect::<Vec<Rgb<u8>>>();
     let image_buffer: ImageBuffer<Rgb<u8>, std::vec::Vec<u8>> = ImageBuffer::from_raw(width as u32, height as u32, image_vec).unwrap();
+    create_parent_dir(&output_path);
     image_buffer.save(output_path).unwrap();
 }
 
-pub fn get_next_output_image_name(path: &str) -> Option<String> {
-    let paths = fs::read_dir(path).unwrap();
-    let mut names =
-    paths.filter_map(|entry| {
-    entry.ok().and_then(|e|
-        e.path().file_name()
-        .and_then(|n| n.to_str().map(|s| String::from(s)))
-    )
-    }).collect::<Vec<String>>();
-
-    names.sort();
-    if let Some(name) = names.last() {
-        let s: String = name.chars().take(name.len() - 4).collect();
-        Some(format!("{:03}", (s.parse::<i32>().unwrap() + 1)))
-    } else { None }
+/// Writes the number of samples taken per pixel as a heatmap, from black
+/// through red and yellow to white at the most sampled pixel.
+pub fn write_sample_heatmap(film: &Film, output_path: String) {
+    create_parent_dir(&output_path);
+    let max_count = film.sample_counts.iter().max().copied().unwrap_or(0).max(1) as f32;
+    let image_vec = film
+        .sample_counts
+        .iter()
+        .map(|count| *count as f32 / max_count)
+        .map(|t| Vec3::new(3.0 * t, 3.0 * t - 1.0, 3.0 * t - 2.0))
+        .map(|rgb| (rgb.map(|v| v.clamp(0.0, 1.0)) * 255.99).map(|v| v as u8))
+        .flat_map(|v| vec![v.x, v.y, v.z])
+        .collect::<Vec<u8>>();
+    let image_buffer: ImageBuffer<Rgb<u8>, std::vec::Vec<u8>> =
+        ImageBuffer::from_raw(film.width as u32, film.height as u32, image_vec).unwrap();
+    image_buffer.save(output_path).unwrap();
+}
+
+/// The number after the highest numbered image or image sequence directory
+/// in `path`, names that do not start with a number are skipped. Starts at
+/// `000` when there is none yet or the directory does not exist.
+pub fn get_next_output_image_name(path: &str) -> String {
+    let last = fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|name| {
+            let digits: String = name.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse::<i32>().ok()
+        })
+        .max();
+    format!("{:03}", last.map_or(0, |last| last + 1))
+}
+
+/// Frame `frame` of the image sequence `name`, e.g. `output/png/012/0005.png`.
+pub fn sequence_frame_path(directory: &str, name: &str, frame: i32, extension: &str) -> String {
+    format!("{}/{}/{:04}.{}", directory, name, frame, extension)
 }
@@ -1,14 +1,15 @@
 use std::ops::Range;
-use rand::{Rng, thread_rng, random};
 use std::f32::consts::PI;
 
 use crate::vector::{Vec3, onb_local, random_unit_vec};
 use crate::geometry::Geometry;
+use crate::sampler::Sampler;
+
 pub trait Pdf<T>: Sync + Send {
-    fn value(&self, x: T) -> f32 {
+    fn value(&self, _x: T) -> f32 {
         0.0
     }
-    fn sample(&self) -> T;
+    fn sample(&self, sampler: &mut dyn Sampler) -> T;
 }
 
 pub struct Pdf1D {
@@ -29,7 +30,7 @@ impl Pdf1D {
             v / pdf_area
         }).collect();
         let cum_pdf = pdf.iter().fold(vec![], |mut acc, v| {
-            if acc.len() > 0 {
+            if !acc.is_empty() {
                 acc.push(acc.last().unwrap() + v / pdf_sum)
             } else {
                 acc.push(*v / pdf_sum)
@@ -42,8 +43,8 @@ impl Pdf1D {
 
 impl Pdf<f32> for Pdf1D {
 
-    fn sample(&self) -> f32 {        
-        let rnd_num = random::<f32>();
+    fn sample(&self, sampler: &mut dyn Sampler) -> f32 {
+        let rnd_num = sampler.next_f32();
 
         let index_match = self.cum_pdf.binary_search_by(|v| {           
             v.partial_cmp(&rnd_num).unwrap()
@@ -53,8 +54,7 @@ impl Pdf<f32> for Pdf1D {
             Err(i) => i
         };
         let indexf_normed = (index as f32 + (rnd_num - self.cum_pdf[index])) / self.cum_pdf.len() as f32;
-        let wavelength = (self.range.end - self.range.start) * indexf_normed + self.range.start;
-        wavelength
+        (self.range.end - self.range.start) * indexf_normed + self.range.start
     }
 
     fn value(&self, wavelength: f32) -> f32 {
@@ -100,14 +100,14 @@ impl Pdf<Vec3> for CosinePdf {
             cosine / PI
         }
     }
-    fn sample(&self) -> Vec3 {
-        onb_local(&self.w, &random_cosine_direction())
+    fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        onb_local(&self.w, &random_cosine_direction(sampler))
     }
 }
 
-pub fn random_cosine_direction() -> Vec3 {
-    let r1 = random::<f32>();
-    let r2 = random::<f32>();
+pub fn random_cosine_direction(sampler: &mut dyn Sampler) -> Vec3 {
+    let r = sampler.next_2d();
+    let (r1, r2) = (r.x, r.y);
     let z = (1.0 - r2).sqrt();
 
     let phi = 2.0 * PI * r1;
@@ -122,50 +122,23 @@ impl Pdf<Vec3> for UniformPdf {
     fn value(&self, _direction: Vec3) -> f32 {
         1.0 / (4.0 * PI)
     }
-    fn sample(&self) -> Vec3 {
-        random_unit_vec()
+    fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        random_unit_vec(sampler)
     }
 }
 
 pub struct GeometryPdf<'a> {
     pub origin: Vec3,
-    pub geometry: &'a Box<dyn Geometry + 'a>,
+    pub time: f32,
+    pub geometry: &'a dyn Geometry,
 }
 
 impl<'a> Pdf<Vec3> for GeometryPdf<'a> {
     fn value(&self, direction: Vec3) -> f32 {
-        self.geometry.pdf(&self.origin, &direction)
-    }
-    fn sample(&self) -> Vec3 {
-        self.geometry.sample_direction(&self.origin)
-    }
-}
-
-pub struct DirectionalPdf {
-    pub direction: Vec3,
-    pub epsilon: f32
-}
-
-impl DirectionalPdf {
-    pub fn new(direction: Vec3) -> Self {
-        DirectionalPdf {
-            direction,
-            epsilon: 0.001
-        }
-    }
-}
-
-impl Pdf<Vec3> for DirectionalPdf {
-    fn value(&self, direction: Vec3) -> f32 {
-        let cosine_theta = self.direction.dot(&direction);
-        if (1.0 - cosine_theta) <= self.epsilon {
-            1.0
-        } else {
-            0.0
-        }
+        self.geometry.pdf(&self.origin, &direction, self.time)
     }
-    fn sample(&self) -> Vec3 {
-        self.direction
+    fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.geometry.sample_direction(&self.origin, self.time, sampler)
     }
 }
 
@@ -216,11 +189,9 @@ impl<'a> Pdf<Vec3> for MixturePdf<'a, Vec3> {
             }
         }
     }
-    fn sample(&self) -> Vec3 {
-        let mut rng = thread_rng();
-        let r = rng.gen_range(0, self.pdfs.len());
-        let pdf_idx = r as usize;
-        self.pdfs[pdf_idx].sample()
+    fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let pdf_idx = sampler.next_index(self.pdfs.len());
+        self.pdfs[pdf_idx].sample(sampler)
     }
 }
 
@@ -231,10 +202,8 @@ impl<'a> Pdf<f32> for MixturePdf<'a, f32> {
             acc + p.value(x) * weight
         })
     }
-    fn sample(&self) -> f32 {
-        let mut rng = thread_rng();
-        let r = rng.gen_range(0, self.pdfs.len());
-        let pdf_idx = r as usize;
-        self.pdfs[pdf_idx].sample()
+    fn sample(&self, sampler: &mut dyn Sampler) -> f32 {
+        let pdf_idx = sampler.next_index(self.pdfs.len());
+        self.pdfs[pdf_idx].sample(sampler)
     }
 }
\ No newline at end of file
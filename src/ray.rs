@@ -4,19 +4,27 @@ pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
     pub wavelength: f32,
+    /// Within the shutter interval of the camera, moving geometry is hit
+    /// where it is at this time.
+    pub time: f32,
+    /// Uniform in [0, 1), participating media draw their free path from it.
+    /// The integrator fills it from its own sampler dimension every bounce.
+    pub medium_sample: f32,
     // pub pdf: f32,
 }
 
 impl Ray {
-    pub fn new(origin: Vec3, direction: Vec3, wavelength: f32) -> Self {
+    pub fn new(origin: Vec3, direction: Vec3, wavelength: f32, time: f32) -> Self {
         Self {
             origin,
             direction,
             wavelength,
+            time,
+            medium_sample: 0.5,
             // pdf
         }
     }
     pub fn at (&self, t: f32) -> Vec3 {
-        return self.origin + t * self.direction;
+        self.origin + t * self.direction
     }
 }
\ No newline at end of file
@@ -0,0 +1,204 @@
+use std::sync::Arc;
+
+use crate::sampler::{hash, Sampler};
+use crate::vector::Vec2;
+
+const GOLDEN_RATIO: f64 = 1.618033988749895;
+/// The plastic number, the 2D generalisation of the golden ratio.
+const PLASTIC_NUMBER: f64 = 1.324717957244746;
+
+/// Blue noise dithered low discrepancy sampling. Over the samples of a pixel
+/// every dimension follows the golden ratio sequence, pairs follow the R2
+/// sequence, and the starting point of each pixel comes from a blue noise
+/// mask. The error then shows up as high frequency noise between pixels,
+/// which is much less visible and filters away well. Every dimension reads
+/// the mask at its own toroidal offset.
+#[derive(Clone)]
+pub struct BlueNoiseSampler {
+    pub seed: u64,
+    pub mask: Arc<BlueNoiseMask>,
+    x: usize,
+    y: usize,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl BlueNoiseSampler {
+    /// Generates a 64x64 mask, which takes a moment.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            mask: Arc::new(BlueNoiseMask::new(64, seed)),
+            x: 0,
+            y: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn mask_value(&self, dimension: u32) -> f64 {
+        let offset = hash(&[self.seed, dimension as u64]);
+        let size = self.mask.size;
+        let x = (self.x + (offset as u32) as usize) % size;
+        let y = (self.y + ((offset >> 32) as u32) as usize) % size;
+        self.mask.values[y * size + x] as f64
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_sample(&mut self, x: usize, y: usize, sample_index: u32) {
+        self.x = x;
+        self.y = y;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.dimension = dimension;
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        let alpha = 1.0 / GOLDEN_RATIO;
+        let value = fract(self.mask_value(self.dimension) + self.sample_index as f64 * alpha);
+        self.dimension += 1;
+        value
+    }
+
+    fn next_2d(&mut self) -> Vec2 {
+        let alpha = (1.0 / PLASTIC_NUMBER, 1.0 / (PLASTIC_NUMBER * PLASTIC_NUMBER));
+        let n = self.sample_index as f64;
+        let value = Vec2::new(
+            fract(self.mask_value(self.dimension) + n * alpha.0),
+            fract(self.mask_value(self.dimension + 1) + n * alpha.1),
+        );
+        self.dimension += 2;
+        value
+    }
+}
+
+fn fract(x: f64) -> f32 {
+    ((x - x.floor()) as f32).min(1.0 - f32::EPSILON)
+}
+
+/// A tileable square of values in [0, 1) without low frequencies, made with
+/// the void and cluster method of Ulichney, 1993.
+pub struct BlueNoiseMask {
+    pub size: usize,
+    pub values: Vec<f32>,
+}
+
+impl BlueNoiseMask {
+    pub fn new(size: usize, seed: u64) -> Self {
+        let pixels = size * size;
+        let sigma = 1.5;
+
+        // Toroidal Gaussian energy of a single point, by offset.
+        let kernel = (0..pixels)
+            .map(|i| {
+                let wrap = |d: usize| d.min(size - d) as f32;
+                let (dx, dy) = (wrap(i % size), wrap(i / size));
+                (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+            })
+            .collect::<Vec<f32>>();
+
+        let mut pattern = Pattern {
+            size,
+            kernel,
+            points: vec![false; pixels],
+            energy: vec![0.0; pixels],
+        };
+
+        // Start from a random tenth of the pixels and move points from the
+        // tightest cluster to the largest void until that changes nothing.
+        let initial_count = (pixels / 10).max(1);
+        let mut i = 0;
+        while pattern.count() < initial_count {
+            let pixel = (hash(&[seed, i]) % pixels as u64) as usize;
+            if !pattern.points[pixel] {
+                pattern.toggle(pixel);
+            }
+            i += 1;
+        }
+        for _ in 0..pixels {
+            let cluster = pattern.tightest_cluster();
+            pattern.toggle(cluster);
+            let void = pattern.largest_void();
+            pattern.toggle(void);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut ranks = vec![0; pixels];
+
+        // Rank the initial points by removing them, tightest cluster first.
+        let initial = pattern.points.clone();
+        let initial_energy = pattern.energy.clone();
+        for rank in (0..initial_count).rev() {
+            let cluster = pattern.tightest_cluster();
+            pattern.toggle(cluster);
+            ranks[cluster] = rank;
+        }
+
+        // Rank the remaining pixels by filling the largest void first.
+        pattern.points = initial;
+        pattern.energy = initial_energy;
+        for rank in initial_count..pixels {
+            let void = pattern.largest_void();
+            pattern.toggle(void);
+            ranks[void] = rank;
+        }
+
+        let values = ranks
+            .iter()
+            .map(|rank| (*rank as f32 + 0.5) / pixels as f32)
+            .collect();
+        Self { size, values }
+    }
+}
+
+struct Pattern {
+    size: usize,
+    kernel: Vec<f32>,
+    points: Vec<bool>,
+    /// Sum of the kernels of all points, at every pixel.
+    energy: Vec<f32>,
+}
+
+impl Pattern {
+    fn count(&self) -> usize {
+        self.points.iter().filter(|p| **p).count()
+    }
+
+    fn toggle(&mut self, pixel: usize) {
+        let size = self.size;
+        let sign = if self.points[pixel] { -1.0 } else { 1.0 };
+        self.points[pixel] = !self.points[pixel];
+        let (px, py) = (pixel % size, pixel / size);
+        for (i, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (i % size + size - px) % size;
+            let dy = (i / size + size - py) % size;
+            *energy += sign * self.kernel[dy * size + dx];
+        }
+    }
+
+    /// The point with the most energy.
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    /// The empty pixel with the least energy.
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, occupied: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut best: Option<usize> = None;
+        for (i, point) in self.points.iter().enumerate() {
+            if *point == occupied && best.is_none_or(|b| better(self.energy[i], self.energy[b])) {
+                best = Some(i);
+            }
+        }
+        best.unwrap()
+    }
+}
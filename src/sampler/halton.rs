@@ -0,0 +1,79 @@
+use crate::sampler::{hash, to_unit_f32, IndependentSampler, Sampler};
+use crate::vector::Vec2;
+
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89,
+    97, 101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191,
+    193, 197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293,
+    307, 311,
+];
+
+/// The Halton sequence, dimension `d` is the radical inverse in base of the
+/// `d`th prime. Every pixel shifts every dimension by its own random offset
+/// (a Cranley-Patterson rotation) so neighbouring pixels do not correlate.
+/// Dimensions past the prime table fall back to independent numbers.
+#[derive(Clone)]
+pub struct HaltonSampler {
+    pub seed: u64,
+    pixel_seed: u64,
+    sample_index: u32,
+    dimension: u32,
+    fallback: IndependentSampler,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel_seed: 0,
+            sample_index: 0,
+            dimension: 0,
+            fallback: IndependentSampler::new(seed),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, x: usize, y: usize, sample_index: u32) {
+        self.pixel_seed = hash(&[x as u64, y as u64, self.seed]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+        self.fallback.start_sample(x, y, sample_index);
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.dimension = dimension;
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        match PRIMES.get(dimension as usize) {
+            Some(base) => {
+                let offset = to_unit_f32(hash(&[self.pixel_seed, dimension as u64]) as u32);
+                let value = radical_inverse(self.sample_index, *base) + offset;
+                (value - value.floor()).min(1.0 - f32::EPSILON)
+            }
+            None => self.fallback.next_f32(),
+        }
+    }
+
+    fn next_2d(&mut self) -> Vec2 {
+        let x = self.next_f32();
+        Vec2::new(x, self.next_f32())
+    }
+}
+
+/// Mirrors the digits of `index` in `base` around the decimal point.
+pub fn radical_inverse(mut index: u32, base: u32) -> f32 {
+    let inverse_base = 1.0 / base as f64;
+    let mut inverse_base_n = 1.0;
+    let mut reversed = 0u64;
+    while index > 0 {
+        let next = index / base;
+        reversed = reversed * base as u64 + (index - next * base) as u64;
+        inverse_base_n *= inverse_base;
+        index = next;
+    }
+    (reversed as f64 * inverse_base_n) as f32
+}
@@ -0,0 +1,54 @@
+use crate::sampler::{hash, to_unit_f32, Sampler};
+use crate::vector::Vec2;
+
+/// Independent uniform random numbers from PCG32 (XSH RR), see
+/// https://www.pcg-random.org. The dimensions are ignored.
+#[derive(Clone)]
+pub struct IndependentSampler {
+    pub seed: u64,
+    state: u64,
+    increment: u64,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            state: 0,
+            increment: 1,
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(self.increment);
+        let xor_shifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rotation = (old_state >> 59) as u32;
+        xor_shifted.rotate_right(rotation)
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, x: usize, y: usize, sample_index: u32) {
+        self.state = 0;
+        self.increment = (hash(&[x as u64, y as u64, self.seed]) << 1) | 1;
+        self.next_u32();
+        self.state = self
+            .state
+            .wrapping_add(hash(&[sample_index as u64, self.seed]));
+        self.next_u32();
+    }
+
+    fn set_dimension(&mut self, _dimension: u32) {}
+
+    fn next_f32(&mut self) -> f32 {
+        to_unit_f32(self.next_u32())
+    }
+
+    fn next_2d(&mut self) -> Vec2 {
+        let x = self.next_f32();
+        Vec2::new(x, self.next_f32())
+    }
+}
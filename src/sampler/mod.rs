@@ -0,0 +1,201 @@
+pub mod independent;
+pub mod stratified;
+pub mod halton;
+pub mod sobol;
+pub mod blue_noise;
+
+use dyn_clone::DynClone;
+use std::ops::Range;
+
+use crate::vector::Vec2;
+
+pub use independent::IndependentSampler;
+pub use stratified::StratifiedSampler;
+pub use halton::HaltonSampler;
+pub use sobol::SobolSampler;
+pub use blue_noise::BlueNoiseSampler;
+
+/// First dimension of the pixel position, a 2D sample.
+pub const PIXEL_DIMENSION: u32 = 0;
+/// First dimension of the lens position, a 2D sample.
+pub const LENS_DIMENSION: u32 = 2;
+/// The choice of wavelength pdf and the wavelength itself.
+pub const WAVELENGTH_DIMENSION: u32 = 4;
+/// The time within the shutter interval.
+pub const TIME_DIMENSION: u32 = 6;
+/// First dimension of the first bounce.
+pub const BOUNCE_DIMENSION: u32 = 7;
+/// Scattering choice, mixture pdf choice, light choice, a 2D direction and
+/// the free path through a medium.
+pub const DIMENSIONS_PER_BOUNCE: u32 = 6;
+/// Offset of the free path within the dimensions of a bounce.
+pub const MEDIUM_DIMENSION_OFFSET: u32 = 5;
+
+/// Source of the random numbers of a single camera sample. A prototype is
+/// cloned for every pixel sample and started with `start_sample`, so a render
+/// only depends on the seed and not on how the work is scheduled.
+///
+/// Every part of a path reads its numbers from its own dimensions, see the
+/// dimension constants, so the low discrepancy samplers can distribute each
+/// of them well over the samples of a pixel.
+pub trait Sampler: Sync + Send + DynClone {
+    /// Starts sample `sample_index` of pixel (x, y) at dimension zero.
+    fn start_sample(&mut self, x: usize, y: usize, sample_index: u32);
+    /// Continues with the numbers of `dimension`.
+    fn set_dimension(&mut self, dimension: u32);
+    /// Uniform in [0, 1), advances one dimension.
+    fn next_f32(&mut self) -> f32;
+    /// Uniform in [0, 1)², advances two dimensions.
+    fn next_2d(&mut self) -> Vec2;
+
+    fn next_range(&mut self, range: Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next_f32()
+    }
+
+    /// Uniform in 0..len.
+    fn next_index(&mut self, len: usize) -> usize {
+        ((self.next_f32() * len as f32) as usize).min(len - 1)
+    }
+}
+dyn_clone::clone_trait_object!(Sampler);
+
+/// The samplers a render can be configured with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+impl SamplerKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "independent" => Some(SamplerKind::Independent),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            "blue-noise" => Some(SamplerKind::BlueNoise),
+            _ => None,
+        }
+    }
+
+    /// The stratified sampler needs the number of samples per pixel up front.
+    pub fn create(&self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+/// Mixes the values into a single well distributed 64 bit hash.
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e3779b97f4a7c15, |h, v| mix(h ^ v))
+}
+
+/// Maps the high 24 bits to [0, 1), the most a f32 can represent there.
+pub fn to_unit_f32(x: u32) -> f32 {
+    (x >> 8) as f32 / (1u32 << 24) as f32
+}
+
+/// The SplitMix64 finaliser.
+fn mix(h: u64) -> u64 {
+    let mut z = h.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerKind; 5] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+        SamplerKind::BlueNoise,
+    ];
+
+    /// Dimension `dimension` of the first `samples` samples of one pixel.
+    fn samples_1d(kind: SamplerKind, samples: u32, dimension: u32) -> Vec<f32> {
+        let mut sampler = kind.create(samples, 7);
+        (0..samples)
+            .map(|i| {
+                sampler.start_sample(3, 5, i);
+                sampler.set_dimension(dimension);
+                sampler.next_f32()
+            })
+            .collect()
+    }
+
+    fn samples_2d(kind: SamplerKind, samples: u32, dimension: u32) -> Vec<Vec2> {
+        let mut sampler = kind.create(samples, 7);
+        (0..samples)
+            .map(|i| {
+                sampler.start_sample(3, 5, i);
+                sampler.set_dimension(dimension);
+                sampler.next_2d()
+            })
+            .collect()
+    }
+
+    fn assert_one_per_stratum(strata: impl Iterator<Item = usize>, count: usize) {
+        let mut hits = vec![0; count];
+        for stratum in strata {
+            hits[stratum] += 1;
+        }
+        assert!(hits.iter().all(|&h| h == 1), "strata hit {:?}", hits);
+    }
+
+    #[test]
+    fn samples_are_in_the_unit_interval() {
+        for kind in KINDS.iter() {
+            let mut sampler = kind.create(64, 7);
+            for i in 0..64 {
+                sampler.start_sample(3, 5, i);
+                for _ in 0..70 {
+                    let value = sampler.next_f32();
+                    assert!((0.0..1.0).contains(&value), "{:?} gave {}", kind, value);
+                    let point = sampler.next_2d();
+                    assert!((0.0..1.0).contains(&point.x) && (0.0..1.0).contains(&point.y));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_stratifies_every_dimension() {
+        for dimension in [0, 5, 40].iter() {
+            let values = samples_1d(SamplerKind::Sobol, 16, *dimension);
+            assert_one_per_stratum(values.iter().map(|v| (v * 16.0) as usize), 16);
+            let points = samples_2d(SamplerKind::Sobol, 16, *dimension);
+            assert_one_per_stratum(points.iter().map(|p| (p.x * 4.0) as usize + 4 * (p.y * 4.0) as usize), 16);
+        }
+    }
+
+    #[test]
+    fn halton_stratifies_each_dimension_in_its_base() {
+        for (dimension, base) in [(0, 2), (1, 3), (2, 5)].iter() {
+            let count = base * base;
+            let values = samples_1d(SamplerKind::Halton, count, *dimension);
+            assert_one_per_stratum(values.iter().map(|v| (v * count as f32) as usize), count as usize);
+        }
+    }
+
+    #[test]
+    fn stratified_hits_every_stratum_once() {
+        for dimension in [0, 5, 40].iter() {
+            let values = samples_1d(SamplerKind::Stratified, 16, *dimension);
+            assert_one_per_stratum(values.iter().map(|v| (v * 16.0) as usize), 16);
+            let points = samples_2d(SamplerKind::Stratified, 16, *dimension);
+            assert_one_per_stratum(points.iter().map(|p| (p.x * 4.0) as usize + 4 * (p.y * 4.0) as usize), 16);
+        }
+    }
+}
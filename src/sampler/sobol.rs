@@ -0,0 +1,91 @@
+use crate::sampler::{hash, to_unit_f32, Sampler};
+use crate::vector::Vec2;
+
+/// Owen scrambled Sobol points. Every dimension, or pair of dimensions for
+/// 2D samples, takes the first two Sobol dimensions of a shuffled sample
+/// index and scrambles them with its own seed. This pads any number of
+/// dimensions from a 2D sequence while each stays well stratified. From
+/// Burley, "Practical Hash-based Owen Scrambling", 2020.
+#[derive(Clone)]
+pub struct SobolSampler {
+    pub seed: u64,
+    pixel_seed: u64,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel_seed: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn scrambled(&self, sobol_dimension: u32) -> u32 {
+        let dimension_seed = hash(&[self.pixel_seed, self.dimension as u64]);
+        let index = nested_uniform_scramble(self.sample_index, dimension_seed as u32);
+        let scramble_seed = hash(&[dimension_seed, sobol_dimension as u64]) as u32;
+        nested_uniform_scramble(sobol(index, sobol_dimension), scramble_seed)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, x: usize, y: usize, sample_index: u32) {
+        self.pixel_seed = hash(&[x as u64, y as u64, self.seed]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.dimension = dimension;
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        let value = to_unit_f32(self.scrambled(0));
+        self.dimension += 1;
+        value
+    }
+
+    fn next_2d(&mut self) -> Vec2 {
+        let value = Vec2::new(to_unit_f32(self.scrambled(0)), to_unit_f32(self.scrambled(1)));
+        self.dimension += 2;
+        value
+    }
+}
+
+/// The first two dimensions of the Sobol sequence as 32 bit fractions.
+fn sobol(index: u32, dimension: u32) -> u32 {
+    if dimension == 0 {
+        index.reverse_bits()
+    } else {
+        let mut direction = 1u32 << 31;
+        let mut result = 0;
+        let mut index = index;
+        while index != 0 {
+            if index & 1 != 0 {
+                result ^= direction;
+            }
+            index >>= 1;
+            direction ^= direction >> 1;
+        }
+        result
+    }
+}
+
+/// An Owen scramble of the bits of `x`, most significant bit first.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Hash where every bit only depends on the bits below it.
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
@@ -0,0 +1,103 @@
+use crate::sampler::{hash, to_unit_f32, Sampler};
+use crate::vector::Vec2;
+
+/// Jittered stratification per dimension. 1D dimensions are split into
+/// `samples_per_pixel` strata, 2D dimensions into a grid of about as many
+/// cells, and every dimension visits its strata in its own random order.
+/// Past `samples_per_pixel` samples the strata repeat.
+#[derive(Clone)]
+pub struct StratifiedSampler {
+    pub samples_per_pixel: u32,
+    pub seed: u64,
+    pixel_seed: u64,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            seed,
+            pixel_seed: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// The stratum of the current sample among `strata`, and its jitter.
+    fn stratum(&self, strata: u32) -> (u32, u64) {
+        let dimension_seed = hash(&[self.pixel_seed, self.dimension as u64]);
+        let stratum = permute(self.sample_index % strata, strata, dimension_seed as u32);
+        (stratum, hash(&[dimension_seed, self.sample_index as u64]))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, x: usize, y: usize, sample_index: u32) {
+        self.pixel_seed = hash(&[x as u64, y as u64, self.seed]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn set_dimension(&mut self, dimension: u32) {
+        self.dimension = dimension;
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        let strata = self.samples_per_pixel;
+        let (stratum, jitter) = self.stratum(strata);
+        self.dimension += 1;
+        ((stratum as f32 + to_unit_f32(jitter as u32)) / strata as f32).min(1.0 - f32::EPSILON)
+    }
+
+    fn next_2d(&mut self) -> Vec2 {
+        let nx = (self.samples_per_pixel as f32).sqrt().ceil() as u32;
+        let ny = self.samples_per_pixel.div_ceil(nx);
+        let (stratum, jitter) = self.stratum(nx * ny);
+        self.dimension += 2;
+        let jitter_x = to_unit_f32(jitter as u32);
+        let jitter_y = to_unit_f32((jitter >> 32) as u32);
+        Vec2::new(
+            ((stratum % nx) as f32 + jitter_x) / nx as f32,
+            ((stratum / nx) as f32 + jitter_y) / ny as f32,
+        )
+        .map(|v| v.min(1.0 - f32::EPSILON))
+    }
+}
+
+/// Element `i` of a random permutation of 0..l chosen by `p`, without
+/// storing the permutation. From Kensler, "Correlated Multi-Jittered
+/// Sampling", 2013.
+pub fn permute(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
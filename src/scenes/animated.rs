@@ -0,0 +1,101 @@
+use crate::animation::Animation;
+use crate::camera::Camera;
+use crate::geometry::{
+    aabox::AABox,
+    aarect::{AARect, AARectType},
+    bvh::{BvhOptions, Qbvh},
+    sphere::Sphere,
+    transform::Transform,
+    FlipNormals, Geometry, HittableList, Tagged,
+};
+use crate::material::{
+    color::Reflectance, emissive::DiffuseEmissive, lambertian::Lambertian, spectrum::BlackBody,
+};
+use crate::vector::{Vec2, Vec3};
+
+/// Two seconds at 24 frames per second: the camera swings around, the box
+/// spins and jumps, the sphere darkens and the light flashes up.
+pub const SCRIPT: &str = "
+frames 0 47
+shutter 0.5
+
+0   camera.origin       0 3 10      spline
+16  camera.origin       5 3 8       spline
+32  camera.origin       8 4 2       spline
+47  camera.origin       7 5 -4
+
+0   box.translation     1.5 0.6 0   smooth
+24  box.translation     1.5 2.0 0   smooth
+47  box.translation     1.5 0.6 0
+0   box.rotation        0 0 0
+47  box.rotation        0 270 0
+
+0   sphere.reflectance  0.8
+47  sphere.reflectance  0.2
+
+0   light.intensity     10          step
+24  light.intensity     30          smooth
+47  light.intensity     10
+";
+
+pub fn scene(
+    width: usize,
+    height: usize,
+    animation: &Animation,
+    frame: f32,
+) -> (Box<dyn Geometry>, Box<HittableList>, Camera) {
+    let lookfrom = Vec3::new(0.0, 3.0, 10.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).magnitude();
+    let aspect = width as f32 / height as f32;
+    let mut camera = Camera::new(lookfrom, lookat, vup, 35.0, aspect, 0.0, dist_to_focus);
+    animation.apply_to_camera(&mut camera, frame);
+
+    let diffuse = |reflectance| Box::new(Lambertian { reflectance: Reflectance::Uniform(reflectance) });
+
+    let mut objects: Vec<Box<dyn Geometry>> = vec![
+        Box::new(Sphere {
+            center: Vec3::new(-1.5, 1.0, 0.0),
+            radius: 1.0,
+            material: diffuse(animation.float("sphere.reflectance", frame, 0.7)),
+        }),
+        Box::new(Transform::animated(
+            Box::new(AABox::new(Vec3::repeat(1.2), diffuse(0.6))),
+            animation.keyframes("box", frame),
+        )),
+        Tagged::new(
+            FlipNormals::new(AARect {
+                xy0: Vec2::new(-100.0, -100.0),
+                xy1: Vec2::new(100.0, 100.0),
+                k: 0.0,
+                material: diffuse(0.5),
+                rect_type: AARectType::XZ,
+            }
+            .boxed())
+            .boxed(),
+            1,
+            1,
+        )
+        .boxed(),
+    ];
+
+    let intensity = animation.float("light.intensity", frame, 10.0);
+    let light: Box<dyn Geometry> = Tagged::new(
+        Box::new(Sphere {
+            center: Vec3::new(3.0, 8.0, 4.0),
+            radius: 1.5,
+            material: Box::new(DiffuseEmissive::new(Box::new(BlackBody::new(5500.0)), intensity)),
+        }),
+        2,
+        2,
+    )
+    .boxed();
+    objects.push(light.clone());
+    let lights = Box::new(HittableList {
+        objects: vec![light],
+    });
+
+    let world = Box::new(Qbvh::build(objects, &BvhOptions::default()));
+    (world, lights, camera)
+}
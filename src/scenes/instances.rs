@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::geometry::{
+    aarect::{AARect, AARectType},
+    bvh::{BvhOptions, Qbvh},
+    instance::Instance,
+    sphere::Sphere,
+    transform::{rotation, scale, translation},
+    FlipNormals, Geometry, HittableList, Tagged,
+};
+use crate::material::{
+    color::Reflectance, emissive::DiffuseEmissive, lambertian::Lambertian, spectrum::BlackBody,
+};
+use crate::sampler::{IndependentSampler, Sampler};
+use crate::vector::{random_unit_vec, Vec2, Vec3};
+
+/// Ten thousand copies of one cluster of spheres. The cluster is built into
+/// a BVH once and every copy is an `Instance` of it, under a second BVH.
+pub fn scene(width: usize, height: usize) -> (Box<dyn Geometry>, Box<HittableList>, Camera) {
+    let lookfrom = Vec3::new(0.0, 12.0, 30.0);
+    let lookat = Vec3::new(0.0, 0.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).magnitude();
+    let aspect = width as f32 / height as f32;
+    let camera = Camera::new(lookfrom, lookat, vup, 40.0, aspect, 0.0, dist_to_focus);
+
+    let options = BvhOptions::default();
+    let mut sampler = IndependentSampler::new(0);
+    sampler.start_sample(0, 0, 0);
+
+    let spheres = (0..500)
+        .map(|_| {
+            let center = random_unit_vec(&mut sampler) * sampler.next_f32().sqrt() * 0.4
+                + Vec3::new(0.0, 0.5, 0.0);
+            let sphere: Box<dyn Geometry> = Box::new(Sphere {
+                center,
+                radius: 0.03,
+                material: Box::new(Lambertian {
+                    reflectance: Reflectance::Uniform(0.7),
+                }),
+            });
+            sphere
+        })
+        .collect();
+    let cluster: Arc<dyn Geometry> = Arc::new(Qbvh::build(spheres, &options));
+
+    let mut objects: Vec<Box<dyn Geometry>> = Vec::new();
+    for i in 0..100 {
+        for j in 0..100 {
+            let position = Vec3::new(i as f32 - 49.5, 0.0, j as f32 - 49.5) * 0.5;
+            let size = 0.5 + 0.5 * sampler.next_f32();
+            let to_world = translation(position)
+                * rotation(Vec3::new(0.0, 360.0 * sampler.next_f32(), 0.0))
+                * scale(Vec3::new(size, size * 1.5, size));
+            objects.push(Box::new(Instance::new(cluster.clone(), to_world)));
+        }
+    }
+
+    objects.push(
+        Tagged::new(
+            FlipNormals::new(Box::new(AARect {
+                xy0: Vec2::new(-1000.0, -1000.0),
+                xy1: Vec2::new(1000.0, 1000.0),
+                k: 0.0,
+                material: Box::new(Lambertian {
+                    reflectance: Reflectance::Uniform(0.5),
+                }),
+                rect_type: AARectType::XZ,
+            }))
+            .boxed(),
+            1,
+            1,
+        )
+        .boxed(),
+    );
+
+    let light: Box<dyn Geometry> = Tagged::new(
+        Box::new(Sphere {
+            center: Vec3::new(5.0, 15.0, 5.0),
+            radius: 2.0,
+            material: Box::new(DiffuseEmissive::new(Box::new(BlackBody::new(5500.0)), 10.0)),
+        }),
+        2,
+        2,
+    )
+    .boxed();
+    objects.push(light.clone());
+    let lights = Box::new(HittableList {
+        objects: vec![light],
+    });
+
+    let world = Box::new(Qbvh::build(objects, &options));
+    (world, lights, camera)
+}
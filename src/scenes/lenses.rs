@@ -0,0 +1,112 @@
+use crate::camera::Camera;
+use crate::geometry::{
+    aabox::AABox,
+    aarect::{AARect, AARectType},
+    bvh::{BvhOptions, Qbvh},
+    csg::Csg,
+    cylinder::Cylinder,
+    sphere::Sphere,
+    transform::{rotation, translation, Transform},
+    FlipNormals, Geometry, HittableList, Tagged,
+};
+use crate::material::{
+    color::Reflectance, dielectric::Sf10Glass, emissive::DiffuseEmissive, lambertian::Lambertian,
+    spectrum::BlackBody,
+};
+use crate::vector::{Vec2, Vec3};
+
+/// Optical parts built with CSG: a biconvex lens as the intersection of
+/// two spheres, a prism as a box cut by a rotated box, a concave lens as
+/// a disk with two spheres taken away and two fused beads as the union of
+/// two spheres.
+pub fn scene(width: usize, height: usize) -> (Box<dyn Geometry>, Box<HittableList>, Camera) {
+    let lookfrom = Vec3::new(0.0, 3.0, 9.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).magnitude();
+    let aspect = width as f32 / height as f32;
+    let camera = Camera::new(lookfrom, lookat, vup, 35.0, aspect, 0.0, dist_to_focus);
+
+    let sphere = |center: Vec3, radius: f32| -> Box<dyn Geometry> {
+        Box::new(Sphere { center, radius, material: Box::new(Sf10Glass) })
+    };
+
+    // Two spheres of radius 2 overlapping by 0.6 along z.
+    let biconvex = Csg::intersection(
+        sphere(Vec3::new(-2.0, 1.0, -1.7), 2.0),
+        sphere(Vec3::new(-2.0, 1.0, 1.7), 2.0),
+    );
+
+    // A right angle prism, the upper half of a box turned by 45 degrees.
+    let block = Box::new(Transform::from_matrix(
+        Box::new(AABox::new(Vec3::repeat(1.2), Box::new(Sf10Glass))),
+        translation(Vec3::new(0.0, 0.61, 0.0)),
+    ));
+    let diamond = Box::new(Transform::new(
+        Box::new(AABox::new(Vec3::new(0.6, 0.6, 2.0) * 2.0f32.sqrt(), Box::new(Sf10Glass))),
+        Vec3::new(0.0, 0.01, 0.0),
+        Vec3::new(0.0, 0.0, 45.0),
+    ));
+    let prism = Csg::intersection(block, diamond);
+
+    // A thin disk facing along z, hollowed out on both sides.
+    let blank = Box::new(Transform::from_matrix(
+        Box::new(Cylinder {
+            center: Vec3::zeros(),
+            radius: 0.8,
+            height: 0.3,
+            closed: true,
+            material: Box::new(Sf10Glass),
+        }),
+        translation(Vec3::new(2.0, 1.0, -0.15)) * rotation(Vec3::new(90.0, 0.0, 0.0)),
+    ));
+    let concave = Csg::difference(
+        Csg::difference(blank, sphere(Vec3::new(2.0, 1.0, 2.1), 2.0)).boxed(),
+        sphere(Vec3::new(2.0, 1.0, -2.1), 2.0),
+    );
+
+    // Without the surfaces inside each other, unlike two separate spheres.
+    let beads = Csg::union(
+        sphere(Vec3::new(-0.35, 0.5, -2.5), 0.5),
+        sphere(Vec3::new(0.35, 0.5, -2.5), 0.5),
+    );
+
+    let mut objects: Vec<Box<dyn Geometry>> = vec![
+        biconvex.boxed(),
+        prism.boxed(),
+        concave.boxed(),
+        beads.boxed(),
+        Tagged::new(
+            FlipNormals::new(AARect {
+                xy0: Vec2::new(-100.0, -100.0),
+                xy1: Vec2::new(100.0, 100.0),
+                k: 0.0,
+                material: Box::new(Lambertian { reflectance: Reflectance::Uniform(0.5) }),
+                rect_type: AARectType::XZ,
+            }
+            .boxed())
+            .boxed(),
+            1,
+            1,
+        )
+        .boxed(),
+    ];
+
+    let light: Box<dyn Geometry> = Tagged::new(
+        Box::new(Sphere {
+            center: Vec3::new(0.0, 6.0, -4.0),
+            radius: 0.5,
+            material: Box::new(DiffuseEmissive::new(Box::new(BlackBody::new(5500.0)), 50.0)),
+        }),
+        2,
+        2,
+    )
+    .boxed();
+    objects.push(light.clone());
+    let lights = Box::new(HittableList {
+        objects: vec![light],
+    });
+
+    let world = Box::new(Qbvh::build(objects, &BvhOptions::default()));
+    (world, lights, camera)
+}
@@ -2,11 +2,11 @@ use crate::camera::Camera;
 use crate::geometry::{
     aabox::AABox,
     aarect::{AARect, AARectType},
-    bvh::BVHNode,
+    bvh::{BvhOptions, Qbvh},
+    quad::{Quad, QuadSampling},
     sphere::Sphere,
-    transform::Transform,
     volume::ConstantMedium,
-    FlipNormals, Geometry, HittableList,
+    FlipNormals, Geometry, HittableList, Tagged,
 };
 use crate::material::{
     color::Reflectance,
@@ -36,11 +36,16 @@ pub fn scene(width: usize, height: usize) -> (Box<dyn Geometry>, Box<HittableLis
     let blackbody = Box::new(BlackBody::new(6500.0));
 
     let lights_vec: Vec<Box<dyn Geometry>> = vec![
-        Box::new(Sphere {
-            center: Vec3::new(0.0, 2.5, 0.0),
-            radius: 0.25,
-            material: Box::new(DiffuseEmissive::new(blackbody.clone(), 5.0)),
-        }),
+        Tagged::new(
+            Box::new(Sphere {
+                center: Vec3::new(0.0, 2.5, 0.0),
+                radius: 0.25,
+                material: Box::new(DiffuseEmissive::new(blackbody.clone(), 5.0)),
+            }),
+            3,
+            3,
+        )
+        .boxed(),
         // Box::new(AARect {
         //     xy0: Vec2::new(-0.2,-0.2),
         //     xy1: Vec2::new(0.2,0.2),
@@ -48,19 +53,41 @@ pub fn scene(width: usize, height: usize) -> (Box<dyn Geometry>, Box<HittableLis
         //     material: Box::new(DiffuseEmissive::new(blackbody, 20.0)),
         //     rect_type: AARectType::XZ
         // }),
-        Box::new(AARect {
-            xy0: Vec2::new(-0.2, -0.2),
-            xy1: Vec2::new(0.2, 0.2),
-            k: 2.0,
-            material: Box::new(FalloffEmissive::new(
-                blackbody.clone(),
-                100.0,
-                deg_to_rad(3.0).cos(),
-                deg_to_rad(5.0).cos(),
-                2.0,
-            )),
-            rect_type: AARectType::XZ,
-        }),
+        Tagged::new(
+            Box::new(AARect {
+                xy0: Vec2::new(-0.2, -0.2),
+                xy1: Vec2::new(0.2, 0.2),
+                k: 2.0,
+                material: Box::new(FalloffEmissive::new(
+                    blackbody.clone(),
+                    100.0,
+                    deg_to_rad(3.0).cos(),
+                    deg_to_rad(5.0).cos(),
+                    2.0,
+                )),
+                rect_type: AARectType::XZ,
+            }),
+            4,
+            4,
+        )
+        .boxed(),
+        // A warm panel to the left, tilted down towards the centre. Small
+        // enough to sample its area.
+        Tagged::new(
+            Quad {
+                sampling: QuadSampling::Area,
+                ..Quad::new(
+                    Vec3::new(-1.4, 1.2, -0.4),
+                    Vec3::new(0.0, 0.0, 0.8),
+                    Vec3::new(-0.4, -0.4, 0.0),
+                    Box::new(DiffuseEmissive::new(Box::new(BlackBody::new(3500.0)), 8.0)),
+                )
+            }
+            .boxed(),
+            5,
+            5,
+        )
+        .boxed(),
     ];
 
     let medium_boundary = Box::new(AABox::new(
@@ -69,19 +96,29 @@ pub fn scene(width: usize, height: usize) -> (Box<dyn Geometry>, Box<HittableLis
     ));
 
     let mut objects: Vec<Box<dyn Geometry>> = vec![
-        FlipNormals::new(Box::new(AARect {
-            xy0: Vec2::new(-1000.0, -1000.0),
-            xy1: Vec2::new(1000.0, 1000.0),
-            k: 0.0,
-            material: lambertian,
-            rect_type: AARectType::XZ,
-        }))
+        Tagged::new(
+            FlipNormals::new(Box::new(AARect {
+                xy0: Vec2::new(-1000.0, -1000.0),
+                xy1: Vec2::new(1000.0, 1000.0),
+                k: 0.0,
+                material: lambertian,
+                rect_type: AARectType::XZ,
+            }))
+            .boxed(),
+            1,
+            1,
+        )
+        .boxed(),
+        Tagged::new(
+            Box::new(ConstantMedium::new(
+                medium_boundary,
+                0.1,
+                Box::new(Isotropic { albedo: 1.0 }),
+            )),
+            2,
+            2,
+        )
         .boxed(),
-        Box::new(ConstantMedium::new(
-            medium_boundary,
-            0.1,
-            Box::new(Isotropic { albedo: 1.0 }),
-        )),
     ];
 
     for light in &lights_vec {
@@ -92,6 +129,6 @@ pub fn scene(width: usize, height: usize) -> (Box<dyn Geometry>, Box<HittableLis
         objects: lights_vec,
     });
 
-    let world = BVHNode::build(objects, 0);
+    let world = Box::new(Qbvh::build(objects, &BvhOptions::default()));
     (world, lights, camera)
 }
@@ -1,2 +1,28 @@
 pub mod spheres_7;
-pub mod lights;
\ No newline at end of file
+pub mod lights;
+pub mod instances;
+pub mod motion;
+pub mod animated;
+pub mod lenses;
+pub mod sdf;
+pub mod shapes;
+
+use crate::camera::Camera;
+use crate::geometry::{Geometry, HittableList};
+
+/// Builds the world, the lights to sample and the camera for an image size.
+pub type Scene = fn(usize, usize) -> (Box<dyn Geometry>, Box<HittableList>, Camera);
+
+/// The scenes `--scene` can pick by name, the first is the default.
+pub const SCENES: &[(&str, Scene)] = &[
+    ("lights", lights::scene),
+    ("instances", instances::scene),
+    ("motion", motion::scene),
+    ("lenses", lenses::scene),
+    ("sdf", sdf::scene),
+    ("shapes", shapes::scene),
+];
+
+pub fn find(name: &str) -> Option<Scene> {
+    SCENES.iter().find(|(n, _)| *n == name).map(|(_, scene)| *scene)
+}
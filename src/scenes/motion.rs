@@ -0,0 +1,81 @@
+use crate::camera::Camera;
+use crate::geometry::{
+    aabox::AABox,
+    aarect::{AARect, AARectType},
+    bvh::{BvhOptions, Qbvh},
+    sphere::{MovingSphere, Sphere},
+    transform::{Keyframe, Keyframes, Transform},
+    FlipNormals, Geometry, HittableList, Tagged,
+};
+use crate::material::{
+    color::Reflectance, emissive::DiffuseEmissive, lambertian::Lambertian, spectrum::BlackBody,
+};
+use crate::vector::{Vec2, Vec3};
+
+/// A falling sphere, a spinning box and a slightly shaking camera, with the
+/// shutter open from time 0 to 1.
+pub fn scene(width: usize, height: usize) -> (Box<dyn Geometry>, Box<HittableList>, Camera) {
+    let lookfrom = Vec3::new(0.0, 2.0, 10.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).magnitude();
+    let aspect = width as f32 / height as f32;
+    let mut camera = Camera::new(lookfrom, lookat, vup, 35.0, aspect, 0.0, dist_to_focus);
+    camera.set_shutter(0.0, 1.0);
+    camera.motion = Some(Keyframes::new(vec![
+        Keyframe::new(0.0, Vec3::zeros(), Vec3::zeros(), Vec3::repeat(1.0)),
+        Keyframe::new(1.0, Vec3::new(0.05, 0.0, 0.0), Vec3::new(0.0, 0.5, 0.0), Vec3::repeat(1.0)),
+    ]));
+
+    let diffuse = |reflectance| Box::new(Lambertian { reflectance: Reflectance::Uniform(reflectance) });
+
+    let mut objects: Vec<Box<dyn Geometry>> = vec![
+        Box::new(MovingSphere {
+            center0: Vec3::new(-1.5, 2.0, 0.0),
+            center1: Vec3::new(-1.5, 1.0, 0.0),
+            time0: 0.0,
+            time1: 1.0,
+            radius: 0.6,
+            material: diffuse(0.7),
+        }),
+        Box::new(Transform::animated(
+            Box::new(AABox::new(Vec3::repeat(1.2), diffuse(0.6))),
+            Keyframes::new(vec![
+                Keyframe::new(0.0, Vec3::new(1.5, 0.6, 0.0), Vec3::zeros(), Vec3::repeat(1.0)),
+                Keyframe::new(1.0, Vec3::new(1.5, 0.6, 0.0), Vec3::new(0.0, 45.0, 0.0), Vec3::repeat(1.0)),
+            ]),
+        )),
+        Tagged::new(
+            FlipNormals::new(AARect {
+                xy0: Vec2::new(-100.0, -100.0),
+                xy1: Vec2::new(100.0, 100.0),
+                k: 0.0,
+                material: diffuse(0.5),
+                rect_type: AARectType::XZ,
+            }
+            .boxed())
+            .boxed(),
+            1,
+            1,
+        )
+        .boxed(),
+    ];
+
+    let light: Box<dyn Geometry> = Tagged::new(
+        Box::new(Sphere {
+            center: Vec3::new(3.0, 8.0, 4.0),
+            radius: 1.5,
+            material: Box::new(DiffuseEmissive::new(Box::new(BlackBody::new(5500.0)), 10.0)),
+        }),
+        2,
+        2,
+    )
+    .boxed();
+    objects.push(light.clone());
+    let lights = Box::new(HittableList {
+        objects: vec![light],
+    });
+
+    let world = Box::new(Qbvh::build(objects, &BvhOptions::default()));
+    (world, lights, camera)
+}
@@ -0,0 +1,107 @@
+use crate::camera::Camera;
+use crate::geometry::{
+    aabb::AABB,
+    aarect::{AARect, AARectType},
+    bvh::{BvhOptions, Qbvh},
+    sdf::{mandelbulb, SdfExpr, SdfGeometry},
+    sphere::Sphere,
+    FlipNormals, Geometry, HittableList, Tagged,
+};
+use crate::material::{
+    color::Reflectance, emissive::DiffuseEmissive, lambertian::Lambertian, spectrum::BlackBody,
+};
+use crate::vector::{Vec2, Vec3};
+
+/// Shapes from distance fields: a Mandelbulb from a closure, a twisted bar
+/// blended into a torus, a grid of spheres from one repeated sphere and a
+/// die from boolean operations.
+pub fn scene(width: usize, height: usize) -> (Box<dyn Geometry>, Box<HittableList>, Camera) {
+    let lookfrom = Vec3::new(0.0, 3.0, 9.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).magnitude();
+    let aspect = width as f32 / height as f32;
+    let camera = Camera::new(lookfrom, lookat, vup, 35.0, aspect, 0.0, dist_to_focus);
+
+    let diffuse = |value: f32| Box::new(Lambertian { reflectance: Reflectance::Uniform(value) });
+
+    let center = Vec3::new(-2.2, 1.2, 0.0);
+    let bulb = SdfGeometry::new(
+        move |p: Vec3| mandelbulb(p - center, 8.0, 12),
+        AABB { min: center - Vec3::repeat(1.2), max: center + Vec3::repeat(1.2) },
+        diffuse(0.7),
+    );
+
+    let bar = SdfExpr::cuboid(Vec3::new(0.3, 1.0, 0.3)).twist(1.5);
+    let ring = SdfExpr::torus(0.6, 0.15).translate(Vec3::new(0.0, -0.6, 0.0));
+    let mut twisted = SdfGeometry::new(
+        bar.smooth_union(ring, 0.2).translate(Vec3::new(0.0, 1.0, 0.0)),
+        AABB { min: Vec3::new(-0.8, 0.0, -0.8), max: Vec3::new(0.8, 2.05, 0.8) },
+        diffuse(0.5),
+    );
+    // The twist stretches distances by up to √(1 + (1.5 · 0.42)²).
+    twisted.step_scale = 0.8;
+
+    let grid = SdfGeometry::new(
+        SdfExpr::sphere(0.15)
+            .repeat(Vec3::new(0.4, 0.4, 0.4))
+            .translate(Vec3::new(2.2, 0.2, 0.0)),
+        AABB { min: Vec3::new(1.6, 0.0, -0.6), max: Vec3::new(2.8, 1.6, 0.6) },
+        diffuse(0.3),
+    );
+
+    // A cube with its corners rounded off by a sphere and a pip cut into
+    // its top and front, made at unit size and scaled down.
+    let pips = SdfExpr::sphere(0.25)
+        .translate(Vec3::new(0.0, 1.1, 0.0))
+        .union(SdfExpr::sphere(0.25).translate(Vec3::new(0.0, 0.0, 1.1)));
+    let die_center = Vec3::new(1.1, 0.35, 1.6);
+    let die = SdfGeometry::new(
+        SdfExpr::cuboid(Vec3::repeat(1.0))
+            .intersection(SdfExpr::sphere(1.4))
+            .difference(pips)
+            .scale(0.35)
+            .translate(die_center),
+        AABB { min: die_center - Vec3::repeat(0.36), max: die_center + Vec3::repeat(0.36) },
+        diffuse(0.8),
+    );
+
+    let mut objects: Vec<Box<dyn Geometry>> = vec![
+        Box::new(bulb),
+        Box::new(twisted),
+        Box::new(grid),
+        Box::new(die),
+        Tagged::new(
+            FlipNormals::new(AARect {
+                xy0: Vec2::new(-100.0, -100.0),
+                xy1: Vec2::new(100.0, 100.0),
+                k: 0.0,
+                material: diffuse(0.5),
+                rect_type: AARectType::XZ,
+            }
+            .boxed())
+            .boxed(),
+            1,
+            1,
+        )
+        .boxed(),
+    ];
+
+    let light: Box<dyn Geometry> = Tagged::new(
+        Box::new(Sphere {
+            center: Vec3::new(2.0, 6.0, 4.0),
+            radius: 0.5,
+            material: Box::new(DiffuseEmissive::new(Box::new(BlackBody::new(5500.0)), 50.0)),
+        }),
+        2,
+        2,
+    )
+    .boxed();
+    objects.push(light.clone());
+    let lights = Box::new(HittableList {
+        objects: vec![light],
+    });
+
+    let world = Box::new(Qbvh::build(objects, &BvhOptions::default()));
+    (world, lights, camera)
+}
@@ -0,0 +1,108 @@
+use crate::camera::Camera;
+use crate::geometry::{
+    aarect::{AARect, AARectType},
+    bvh::{BvhOptions, Qbvh},
+    cone::Cone,
+    cylinder::Cylinder,
+    disk::Disk,
+    paraboloid::Paraboloid,
+    torus::Torus,
+    transform::{rotation, shear, translation, Transform},
+    FlipNormals, Geometry, HittableList, Tagged,
+};
+use crate::material::{
+    color::Reflectance, emissive::DiffuseEmissive, ggx::GGX, lambertian::Lambertian,
+    spectrum::BlackBody,
+};
+use crate::vector::{Vec2, Vec3};
+
+/// The quadric and quartic primitives: a cylinder leaning by a shear, a
+/// cone, a mirrored paraboloid dish, lit by a torus ring light and a disk
+/// light.
+pub fn scene(width: usize, height: usize) -> (Box<dyn Geometry>, Box<HittableList>, Camera) {
+    let lookfrom = Vec3::new(0.0, 4.0, 10.0);
+    let lookat = Vec3::new(0.0, 1.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).magnitude();
+    let aspect = width as f32 / height as f32;
+    let camera = Camera::new(lookfrom, lookat, vup, 35.0, aspect, 0.0, dist_to_focus);
+
+    let mut objects: Vec<Box<dyn Geometry>> = vec![
+        Box::new(
+            Transform::from_matrix(
+                Box::new(Cylinder {
+                    center: Vec3::zeros(),
+                    radius: 0.6,
+                    height: 1.5,
+                    closed: true,
+                    material: Box::new(Lambertian { reflectance: Reflectance::Normal(0.8, 600.0, 40.0) }),
+                }),
+                shear(0.3, 0.0, 0.0, 0.0, 0.0, 0.0),
+            )
+            .then(translation(Vec3::new(-2.5, 0.0, 0.0))),
+        ),
+        Box::new(Cone {
+            top_radius: 0.3,
+            ..Cone::new(
+                Vec3::new(0.0, 0.0, 0.0),
+                0.8,
+                1.6,
+                Box::new(Lambertian { reflectance: Reflectance::Normal(0.8, 470.0, 40.0) }),
+            )
+        }),
+        Box::new(Paraboloid {
+            center: Vec3::new(2.5, 0.0, 0.0),
+            radius: 1.0,
+            height: 1.0,
+            material: Box::new(GGX { reflectance: 0.9, roughness: 0.02 }),
+        }),
+        Tagged::new(
+            FlipNormals::new(AARect {
+                xy0: Vec2::new(-100.0, -100.0),
+                xy1: Vec2::new(100.0, 100.0),
+                k: 0.0,
+                material: Box::new(Lambertian { reflectance: Reflectance::Uniform(0.5) }),
+                rect_type: AARectType::XZ,
+            }
+            .boxed())
+            .boxed(),
+            1,
+            1,
+        )
+        .boxed(),
+    ];
+
+    let ring: Box<dyn Geometry> = Tagged::new(
+        Box::new(Torus {
+            center: Vec3::new(0.0, 2.6, 0.0),
+            major_radius: 0.8,
+            minor_radius: 0.05,
+            material: Box::new(DiffuseEmissive::new(Box::new(BlackBody::new(3000.0)), 20.0)),
+        }),
+        2,
+        2,
+    )
+    .boxed();
+    // Turned over to face down, the disk only emits on the side of its normal.
+    let panel: Box<dyn Geometry> = Tagged::new(
+        Box::new(Transform::from_matrix(
+            Box::new(Disk::new(
+                Vec3::zeros(),
+                1.0,
+                Box::new(DiffuseEmissive::new(Box::new(BlackBody::new(6500.0)), 10.0)),
+            )),
+            translation(Vec3::new(0.0, 6.0, 2.0)) * rotation(Vec3::new(180.0, 0.0, 0.0)),
+        )),
+        3,
+        3,
+    )
+    .boxed();
+    objects.push(ring.clone());
+    objects.push(panel.clone());
+    let lights = Box::new(HittableList {
+        objects: vec![ring, panel],
+    });
+
+    let world = Box::new(Qbvh::build(objects, &BvhOptions::default()));
+    (world, lights, camera)
+}
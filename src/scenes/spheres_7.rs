@@ -186,6 +186,6 @@
 //         )),
 //     ];
 
-//     let world = BVHNode::build(objects, 0);
+//     let world = BVHNode::build(objects, &BvhOptions::default());
 //     (world, lights, camera)
 // }
@@ -0,0 +1,97 @@
+use rayon::prelude::*;
+use std::time::Instant;
+
+use crate::animation::Animation;
+use crate::camera::Camera;
+use crate::colorspace::ColorSpace;
+use crate::film::{Film, TileFilm};
+use crate::filter::Filter;
+use crate::geometry::{Geometry, HittableList};
+use crate::integrator::render_sample;
+use crate::output::{get_next_output_image_name, sequence_frame_path, write_exr, write_png};
+use crate::sampler::Sampler;
+use crate::tile::{tiles, Rect};
+use crate::tonemap::{DisplayTransform, Exposure};
+
+/// Builds the scene for a frame, with the animated values read from the
+/// animation.
+pub type AnimatedScene =
+    fn(usize, usize, &Animation, f32) -> (Box<dyn Geometry>, Box<HittableList>, Camera);
+
+/// Settings of a batch render, taken from the locals in `main`.
+pub struct Sequence<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub samples: u32,
+    pub tile_size: usize,
+    pub filter: &'a dyn Filter,
+    pub sampler: &'a dyn Sampler,
+    pub output_color_space: ColorSpace,
+}
+
+impl Sequence<'_> {
+    /// Renders every frame of the animation without the preview window and
+    /// writes them as numbered EXR and PNG images into a directory of their
+    /// own. The exposure picked for the first frame is kept for the rest, so
+    /// an automatic exposure does not flicker, and a first frame much darker
+    /// or brighter than the others sets it for all of them. Scripts that
+    /// set an `exposure` use it instead.
+    pub fn render(&self, animation: &Animation, scene: AnimatedScene, display: &mut DisplayTransform) {
+        let name = get_next_output_image_name("output/png/");
+        if let Some(stops) = animation.exposure {
+            display.exposure = Exposure::Manual(stops);
+        }
+        let (width, height) = (self.width, self.height);
+        for frame in animation.frames.clone() {
+            let start = Instant::now();
+            let (world, attractors, camera) = scene(width, height, animation, frame as f32);
+            let mut film = Film::new(width, height);
+            let mut tile_films = tiles(&Rect::new(width, height), self.tile_size)
+                .into_iter()
+                .map(|tile| TileFilm::new(&film, tile, self.filter))
+                .collect::<Vec<_>>();
+            for n in 0..self.samples {
+                tile_films.par_iter_mut().for_each(|tile_film| {
+                    tile_film.film.clear();
+                    for pixel in tile_film.tile.pixels() {
+                        let sample = render_sample(
+                            &camera,
+                            &*world,
+                            &attractors,
+                            self.sampler,
+                            (width, height),
+                            pixel,
+                            n,
+                        );
+                        tile_film.add_sample(sample, self.filter);
+                    }
+                });
+                // In order, so the overlapping borders add up the same way.
+                for tile_film in &tile_films {
+                    film.add_tile(tile_film);
+                }
+            }
+
+            let tristimulus = film.tristimulus();
+            if frame == *animation.frames.start() {
+                display.exposure.lock(&tristimulus);
+            }
+            write_exr(
+                &film,
+                &tristimulus,
+                self.output_color_space,
+                &display.white_balance,
+                None,
+                sequence_frame_path("output/exr", &name, frame, "exr"),
+            );
+            write_png(
+                &tristimulus,
+                width,
+                height,
+                display,
+                sequence_frame_path("output/png", &name, frame, "png"),
+            );
+            println!("Frame {} of {} in {:.1?}", frame, name, start.elapsed());
+        }
+    }
+}
@@ -0,0 +1,115 @@
+/// A rectangle of pixels, `x1` and `y1` are exclusive. Used for the tiles
+/// that are rendered as one unit of work and for crop regions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Rect {
+    pub fn new(width: usize, height: usize) -> Self {
+        Rect {
+            x0: 0,
+            y0: 0,
+            x1: width,
+            y1: height,
+        }
+    }
+
+    /// The rectangle spanned by two pixels, both included.
+    pub fn from_corners(a: (usize, usize), b: (usize, usize)) -> Self {
+        Rect {
+            x0: a.0.min(b.0),
+            y0: a.1.min(b.1),
+            x1: a.0.max(b.0) + 1,
+            y1: a.1.max(b.1) + 1,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let rect = Rect {
+            x0: self.x0.max(other.x0),
+            y0: self.y0.max(other.y0),
+            x1: self.x1.min(other.x1),
+            y1: self.y1.min(other.y1),
+        };
+        if rect.x0 < rect.x1 && rect.y0 < rect.y1 {
+            Some(rect)
+        } else {
+            None
+        }
+    }
+
+    /// Pixel coordinates in row order.
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> {
+        let (x0, x1) = (self.x0, self.x1);
+        (self.y0..self.y1).flat_map(move |y| (x0..x1).map(move |x| (x, y)))
+    }
+}
+
+/// Splits the region into tiles of at most `tile_size` pixels square,
+/// ordered from the centre of the region outwards so the preview fills in
+/// from where the subject usually is.
+pub fn tiles(region: &Rect, tile_size: usize) -> Vec<Rect> {
+    let mut tiles = (region.y0..region.y1)
+        .step_by(tile_size)
+        .flat_map(|y0| {
+            (region.x0..region.x1).step_by(tile_size).map(move |x0| Rect {
+                x0,
+                y0,
+                x1: (x0 + tile_size).min(region.x1),
+                y1: (y0 + tile_size).min(region.y1),
+            })
+        })
+        .collect::<Vec<Rect>>();
+
+    let distance_to_centre = |tile: &Rect| {
+        let dx = (tile.x0 + tile.x1) as isize - (region.x0 + region.x1) as isize;
+        let dy = (tile.y0 + tile.y1) as isize - (region.y0 + region.y1) as isize;
+        dx * dx + dy * dy
+    };
+    tiles.sort_by_key(distance_to_centre);
+    tiles
+}
+
+/// Outlines a region in the preview.
+pub fn draw_rect_outline(buffer: &mut [u32], width: usize, rect: &Rect, colour: u32) {
+    for x in rect.x0..rect.x1 {
+        buffer[rect.y0 * width + x] = colour;
+        buffer[(rect.y1 - 1) * width + x] = colour;
+    }
+    for y in rect.y0..rect.y1 {
+        buffer[y * width + rect.x0] = colour;
+        buffer[y * width + rect.x1 - 1] = colour;
+    }
+}
+
+/// Marks the corners of a tile that is being rendered in the preview.
+pub fn draw_tile_marker(buffer: &mut [u32], width: usize, tile: &Rect) {
+    let colour = 0xffffff;
+    let length = ((tile.x1 - tile.x0).min(tile.y1 - tile.y0) / 4).max(1);
+    for i in 0..length {
+        for &(x, y) in &[
+            (tile.x0 + i, tile.y0),
+            (tile.x0, tile.y0 + i),
+            (tile.x1 - 1 - i, tile.y0),
+            (tile.x1 - 1, tile.y0 + i),
+            (tile.x0 + i, tile.y1 - 1),
+            (tile.x0, tile.y1 - 1 - i),
+            (tile.x1 - 1 - i, tile.y1 - 1),
+            (tile.x1 - 1, tile.y1 - 1 - i),
+        ] {
+            buffer[y * width + x] = colour;
+        }
+    }
+}
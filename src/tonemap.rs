@@ -0,0 +1,212 @@
+use crate::color::find_exposure;
+use crate::colorspace::{ColorSpace, Mat3, WhiteBalance};
+use crate::vector::Vec3;
+
+/// Compresses scene linear RGB into the displayable [0, 1] range.
+pub trait ToneMapper: Sync + Send {
+    /// `luminance` weighs the channels into luminance, the Y row of the
+    /// RGB to XYZ matrix of the display space.
+    fn map(&self, rgb: Vec3, luminance: &Vec3) -> Vec3;
+}
+
+/// The tone mapper called `name`: `clamp`, `reinhard`, `aces`, `agx` or `hable`.
+pub fn find(name: &str) -> Option<Box<dyn ToneMapper>> {
+    match name {
+        "clamp" => Some(Box::new(Clamp)),
+        "reinhard" => Some(Box::new(ReinhardExtended { white: 4.0 })),
+        "aces" => Some(Box::new(AcesFilmic)),
+        "agx" => Some(Box::new(AgX)),
+        "hable" => Some(Box::new(Hable::default())),
+        _ => None,
+    }
+}
+
+/// Plain exposure, anything above one is clipped.
+pub struct Clamp;
+
+impl ToneMapper for Clamp {
+    fn map(&self, rgb: Vec3, _luminance: &Vec3) -> Vec3 {
+        rgb
+    }
+}
+
+/// Reinhard operator on luminance, `white` is the luminance that maps to one.
+pub struct ReinhardExtended {
+    pub white: f32,
+}
+
+impl ToneMapper for ReinhardExtended {
+    fn map(&self, rgb: Vec3, luminance: &Vec3) -> Vec3 {
+        let l = luminance.dot(&rgb);
+        if l <= 0.0 {
+            return Vec3::zeros();
+        }
+        let l_mapped = l * (1.0 + l / (self.white * self.white)) / (1.0 + l);
+        rgb * (l_mapped / l)
+    }
+}
+
+/// Stephen Hill's fit of the ACES reference rendering and output transforms.
+pub struct AcesFilmic;
+
+impl ToneMapper for AcesFilmic {
+    fn map(&self, rgb: Vec3, _luminance: &Vec3) -> Vec3 {
+        let input = Mat3::new(
+            0.59719, 0.35458, 0.04823,
+            0.07600, 0.90834, 0.01566,
+            0.02840, 0.13383, 0.83777,
+        );
+        let output = Mat3::new(
+             1.60475, -0.53108, -0.07367,
+            -0.10208,  1.10813, -0.00605,
+            -0.00327, -0.07276,  1.07602,
+        );
+        let v = input * rgb;
+        let fitted = v.map(|x| (x * (x + 0.0245786) - 0.000090537) / (x * (0.983729 * x + 0.432951) + 0.238081));
+        output * fitted
+    }
+}
+
+/// Troy Sobotka's AgX, using the polynomial fit of its default contrast curve.
+pub struct AgX;
+
+impl ToneMapper for AgX {
+    fn map(&self, rgb: Vec3, _luminance: &Vec3) -> Vec3 {
+        let inset = Mat3::new(
+            0.84247905, 0.0784336, 0.079223745,
+            0.042328242, 0.87846863, 0.07916613,
+            0.042375654, 0.0784336, 0.879143,
+        );
+        let outset = Mat3::new(
+            1.196879, -0.09802088, -0.09902974,
+            -0.052896854, 1.1519032, -0.098961174,
+            -0.052971635, -0.09804345, 1.1510737,
+        );
+        let min_ev = -12.47393;
+        let max_ev = 4.026069;
+
+        let log_encoded = (inset * rgb).map(|x| {
+            (x.max(1.0e-10).log2().max(min_ev).min(max_ev) - min_ev) / (max_ev - min_ev)
+        });
+        let contrast = log_encoded.map(|x| {
+            let x2 = x * x;
+            let x4 = x2 * x2;
+            15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2
+                + 0.1191 * x
+                - 0.00232
+        });
+        // The curve outputs display encoded values, return to linear.
+        (outset * contrast).map(|x| x.max(0.0).powf(2.2))
+    }
+}
+
+/// John Hable's filmic curve from Uncharted 2.
+pub struct Hable {
+    pub shoulder_strength: f32,
+    pub linear_strength: f32,
+    pub linear_angle: f32,
+    pub toe_strength: f32,
+    pub toe_numerator: f32,
+    pub toe_denominator: f32,
+    pub white: f32,
+}
+
+impl Hable {
+    fn curve(&self, x: f32) -> f32 {
+        let a = self.shoulder_strength;
+        let b = self.linear_strength;
+        let c = self.linear_angle;
+        let d = self.toe_strength;
+        let e = self.toe_numerator;
+        let f = self.toe_denominator;
+        (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+    }
+}
+
+impl Default for Hable {
+    fn default() -> Self {
+        Hable {
+            shoulder_strength: 0.15,
+            linear_strength: 0.50,
+            linear_angle: 0.10,
+            toe_strength: 0.20,
+            toe_numerator: 0.02,
+            toe_denominator: 0.30,
+            white: 11.2,
+        }
+    }
+}
+
+impl ToneMapper for Hable {
+    fn map(&self, rgb: Vec3, _luminance: &Vec3) -> Vec3 {
+        let white_scale = 1.0 / self.curve(self.white);
+        rgb.map(|x| self.curve(x.max(0.0)) * white_scale)
+    }
+}
+
+pub enum Exposure {
+    /// Exposure value in stops, zero leaves the buffer unscaled.
+    Manual(f32),
+    /// Maps one standard deviation above the mean luminance to one,
+    /// offset by the given number of stops.
+    Auto(f32),
+}
+
+impl Exposure {
+    /// Brightens or darkens by a number of stops, for both modes.
+    pub fn adjust(&mut self, stops: f32) {
+        match self {
+            Exposure::Manual(ev) => *ev += stops,
+            Exposure::Auto(compensation) => *compensation += stops,
+        }
+    }
+
+    /// Turns an automatic exposure into the manual one it picks for the
+    /// buffer, so later images are scaled the same.
+    pub fn lock(&mut self, tristimulus_buffer: &Vec<Vec3>) {
+        *self = Exposure::Manual(self.scale(tristimulus_buffer).log2());
+    }
+
+    pub fn scale(&self, tristimulus_buffer: &Vec<Vec3>) -> f32 {
+        match self {
+            Exposure::Manual(ev) => 2.0f32.powf(*ev),
+            Exposure::Auto(compensation) => {
+                2.0f32.powf(*compensation) / find_exposure(tristimulus_buffer)
+            }
+        }
+    }
+}
+
+/// Converts the accumulated XYZ buffer into display encoded RGB, shared by
+/// the preview window and the PNG output.
+pub struct DisplayTransform {
+    pub exposure: Exposure,
+    pub tone_mapper: Box<dyn ToneMapper>,
+    pub color_space: ColorSpace,
+    pub white_balance: WhiteBalance,
+}
+
+impl DisplayTransform {
+    pub fn new(exposure: Exposure, tone_mapper: Box<dyn ToneMapper>) -> Self {
+        Self {
+            exposure,
+            tone_mapper,
+            color_space: ColorSpace::LinearSrgb,
+            white_balance: WhiteBalance::default(),
+        }
+    }
+
+    /// Returns display encoded values in [0, 1].
+    pub fn apply(&self, tristimulus_buffer: &Vec<Vec3>, width: usize) -> Vec<Vec3> {
+        let scale = self.exposure.scale(tristimulus_buffer);
+        let from_xyz = self.white_balance.xyz_to_rgb(self.color_space, tristimulus_buffer, width);
+        let luminance = self.color_space.rgb_to_xyz().row(1).transpose();
+        tristimulus_buffer
+            .iter()
+            .map(|tri| {
+                let rgb = self.tone_mapper.map(from_xyz * tri * scale, &luminance);
+                rgb.map(|v| self.color_space.encode(v.clamp(0.0, 1.0)))
+            })
+            .collect()
+    }
+}
@@ -1,20 +1,26 @@
-use nalgebra::{Vector2, Vector3};
+use nalgebra::{Matrix4, Vector2, Vector3};
 use std::f32::consts::PI;
-use rand::{thread_rng, Rng, random};
+
+use crate::sampler::Sampler;
 
 pub type Vec2 = Vector2<f32>;
 pub type Vec3 = Vector3<f32>;
+/// Affine transform in homogeneous coordinates.
+pub type Mat4 = Matrix4<f32>;
 
-pub fn random_unit_in_disk() -> Vec3 {
-    let mut rng = thread_rng();
-    let mut p;
-    loop {
-        p = Vec3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), 0.0);
-        if p.magnitude_squared() < 1.0 {
-            break;
-        }
+/// Maps a 2D sample to the unit disk with Shirley's concentric mapping,
+/// which keeps the stratification of the sample intact.
+pub fn random_unit_in_disk(sampler: &mut dyn Sampler) -> Vec3 {
+    let offset = 2.0 * sampler.next_2d() - Vec2::new(1.0, 1.0);
+    if offset.x == 0.0 && offset.y == 0.0 {
+        return Vec3::zeros();
     }
-    p
+    let (r, theta) = if offset.x.abs() > offset.y.abs() {
+        (offset.x, PI / 4.0 * (offset.y / offset.x))
+    } else {
+        (offset.y, PI / 2.0 - PI / 4.0 * (offset.x / offset.y))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
 }
 
 pub fn deg_to_rad(deg: f32) -> f32 {
@@ -32,9 +38,9 @@ pub fn onb_local(w: &Vec3, direction: &Vec3) -> Vec3 {
     direction.x * u + direction.y * v + direction.z * w
 }
 
-pub fn random_to_sphere(radius: f32, distance_squared: f32) -> Vec3 {
-    let r1 = random::<f32>();
-    let r2 = random::<f32>();
+pub fn random_to_sphere(radius: f32, distance_squared: f32, sampler: &mut dyn Sampler) -> Vec3 {
+    let r = sampler.next_2d();
+    let (r1, r2) = (r.x, r.y);
     let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).sqrt() - 1.0);
 
     let phi = 2.0 * PI * r1;
@@ -50,10 +56,10 @@ pub fn clamp(x: f32) -> f32 {
     else { x }
 }
 
-pub fn random_unit_vec() -> Vector3<f32> {
-    let mut rng = thread_rng();
-    let a = rng.gen_range(0.0, 2.0 * PI);
-    let z = rng.gen_range(-1.0, 1.0) as f32;
+pub fn random_unit_vec(sampler: &mut dyn Sampler) -> Vector3<f32> {
+    let r = sampler.next_2d();
+    let a = 2.0 * PI * r.x;
+    let z = 2.0 * r.y - 1.0;
     let r = (1.0 - z * z).sqrt();
     Vector3::new(r * a.cos(), r * a.sin(), z)
 }
\ No newline at end of file